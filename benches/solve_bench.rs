@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
 
-use spsolve::matrix;
+use spsolve::matrix::{gen, Dataset, Kind};
 use spsolve::Solver;
 
 #[derive(Clone)]
@@ -86,6 +86,49 @@ fn benchmark_inputs(c: &mut Criterion, group_name: &str, inputs: &[Input], n_rhs
             });
         });
 
+        // Indices that are passed to the C solvers without conversion.
+        #[cfg(any(feature = "klu", feature = "csparse"))]
+        let (a_i32, a_p32): (Vec<i32>, Vec<i32>) = (
+            input.a_i.iter().map(|&i| i as i32).collect(),
+            input.a_p.iter().map(|&p| p as i32).collect(),
+        );
+
+        #[cfg(feature = "klu")]
+        group.bench_with_input(
+            BenchmarkId::new("klu::solve_i32", input.n),
+            input,
+            |b, d| {
+                b.iter(|| {
+                    let mut b = rhs.clone();
+
+                    let solver = spsolve::klu::KLU::default();
+                    solver
+                        .solve(d.n, &a_i32, &a_p32, &d.a_x, &mut b, d.trans)
+                        .unwrap();
+
+                    black_box(b);
+                });
+            },
+        );
+
+        #[cfg(feature = "csparse")]
+        group.bench_with_input(
+            BenchmarkId::new("csparse::solve_i32", input.n),
+            input,
+            |b, d| {
+                b.iter(|| {
+                    let mut b = rhs.clone();
+
+                    let solver = spsolve::csparse::CSparse::default();
+                    solver
+                        .solve(d.n, &a_i32, &a_p32, &d.a_x, &mut b, d.trans)
+                        .unwrap();
+
+                    black_box(b);
+                });
+            },
+        );
+
         #[cfg(feature = "rsparse")]
         group.bench_with_input(
             BenchmarkId::new("rsparse::solve", input.n),
@@ -104,6 +147,20 @@ fn benchmark_inputs(c: &mut Criterion, group_name: &str, inputs: &[Input], n_rhs
             },
         );
 
+        #[cfg(feature = "snlu")]
+        group.bench_with_input(BenchmarkId::new("snlu::solve", input.n), input, |b, d| {
+            b.iter(|| {
+                let mut b = rhs.clone();
+
+                let solver = spsolve::snlu::SNLU::default();
+                solver
+                    .solve(d.n, &d.a_i, &d.a_p, &d.a_x, &mut b, d.trans)
+                    .unwrap();
+
+                black_box(b);
+            });
+        });
+
         if n <= 25_000 {
             #[cfg(feature = "basiclu")]
             group.bench_with_input(
@@ -127,55 +184,57 @@ fn benchmark_inputs(c: &mut Criterion, group_name: &str, inputs: &[Input], n_rhs
     group.finish();
 }
 
-pub fn bbus_solve_benchmark(c: &mut Criterion) {
-    let trans = false;
-    let inputs = [
-        // matrix::activsg200_bbus(!trans),
-        matrix::activsg500_bbus(!trans),
-        matrix::activsg2000_bbus(!trans),
-        matrix::activsg10k_bbus(!trans),
-        matrix::activsg25k_bbus(!trans),
-        matrix::activsg70k_bbus(!trans),
-    ]
-    .into_iter()
-    .map(|(n, a_p, a_i, a_x)| Input {
-        n,
-        a_p,
-        a_i,
-        a_x,
-        trans,
-    })
-    .collect::<Vec<Input>>();
+/// Smallest power system case that is benchmarked.
+const MIN_BUSES: usize = 500;
+
+/// Reads the test matrices of the given kind with at least [`MIN_BUSES`]
+/// buses, skipping any that are not available.
+fn inputs(kind: Kind, trans: bool) -> Vec<Input> {
+    Dataset::of_kind(kind)
+        .filter(|d| d.buses >= MIN_BUSES)
+        .filter_map(|d| d.read(!trans).ok())
+        .map(|(n, a_p, a_i, a_x)| Input {
+            n,
+            a_p,
+            a_i,
+            a_x,
+            trans,
+        })
+        .collect()
+}
 
+pub fn bbus_solve_benchmark(c: &mut Criterion) {
+    let inputs = inputs(Kind::Bbus, false);
     benchmark_inputs(c, "solve(bbus,nrhs=1)", &inputs, 1);
 }
 
 pub fn jac_solve_benchmark(c: &mut Criterion) {
-    let trans = false;
-    let inputs = [
-        // matrix::activsg200_jac(!trans),
-        matrix::activsg500_jac(!trans),
-        matrix::activsg2000_jac(!trans),
-        matrix::activsg10k_jac(!trans),
-        matrix::activsg25k_jac(!trans),
-        // matrix::activsg70k_jac(!trans),
-    ]
-    .into_iter()
-    .map(|(n, a_p, a_i, a_x)| Input {
-        n,
-        a_p,
-        a_i,
-        a_x,
-        trans,
-    })
-    .collect::<Vec<Input>>();
-
+    let inputs = inputs(Kind::Jac, false);
     benchmark_inputs(c, "solve(jac,nrhs=1)", &inputs, 1);
 }
 
+pub fn laplacian_solve_benchmark(c: &mut Criterion) {
+    let inputs = [50, 100, 200]
+        .into_iter()
+        .map(|k| {
+            let (n, a_p, a_i, a_x) = gen::laplacian_2d(k, k);
+            Input {
+                n,
+                a_p,
+                a_i,
+                a_x,
+                trans: false,
+            }
+        })
+        .collect::<Vec<Input>>();
+
+    benchmark_inputs(c, "solve(laplacian2d,nrhs=1)", &inputs, 1);
+}
+
 criterion_group!(
     benches,
     // bbus_solve_benchmark,
-    jac_solve_benchmark
+    jac_solve_benchmark,
+    laplacian_solve_benchmark
 );
 criterion_main!(benches);
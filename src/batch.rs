@@ -0,0 +1,42 @@
+//! Helpers for batches of independent factorizations and solves, run in
+//! parallel if the `rayon` feature is enabled.
+
+use anyhow::Result;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Maps `f` over `items`, stopping at the first error.
+#[cfg(any(feature = "gplu", feature = "rlu", feature = "klu", feature = "snlu"))]
+pub(crate) fn try_map<T, U, F>(items: &[T], f: F) -> Result<Vec<U>>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> Result<U> + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.par_iter().map(f).collect();
+
+    #[cfg(not(feature = "rayon"))]
+    items.iter().map(f).collect()
+}
+
+/// Calls `f` for each pair of `items` and `out`, stopping at the first error.
+pub(crate) fn try_zip_mut<T, U, F>(items: &[T], out: &mut [U], f: F) -> Result<()>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T, &mut U) -> Result<()> + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items
+        .par_iter()
+        .zip(out.par_iter_mut())
+        .try_for_each(|(t, u)| f(t, u));
+
+    #[cfg(not(feature = "rayon"))]
+    items
+        .iter()
+        .zip(out.iter_mut())
+        .try_for_each(|(t, u)| f(t, u))
+}
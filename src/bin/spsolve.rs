@@ -1,28 +1,422 @@
+use std::process::ExitCode;
 use std::time::Instant;
 
 #[cfg(feature = "cpuprofiler")]
 use cpuprofiler::PROFILER;
 
+use anyhow::{format_err, Result};
+use spsolve::matrix::{read_csc, read_mtx, write_mtx_dense, Dataset, Mtx};
 use spsolve::Solver;
 
-fn main() {
-    let trans = false;
-    // let (n, a_p, a_i, a_x) = spsolve::matrix::activsg2000_bbus(!trans);
-    let (n, a_p, a_i, a_x) = spsolve::matrix::activsg10k_bbus(!trans);
-    // let (n, a_p, a_i, a_x) = spsolve::matrix::activsg25k_bbus(!trans);
-    let mut b: Vec<f64> = (0..n).map(|i| 1.0 + i as f64 / n as f64).collect();
+const USAGE: &str = "\
+Usage: spsolve solve (--matrix FILE | --dataset NAME) [OPTIONS]
+       spsolve backends
 
-    let solver = spsolve::gplu::GPLU::default();
+Solves A x = b, or A' x = b with --trans, and reports the residual.
 
+Options:
+    --matrix FILE         Matrix Market (.mtx, .mtx.gz, .mtx.zst) or .csc file
+    --dataset NAME        bundled test matrix, e.g. ACTIVSg10k_Bbus
+    --rhs FILE            right-hand-side(s) as a Matrix Market file
+                          (default: b = A * ones)
+    --out FILE            write the solution to a Matrix Market file
+    --backend NAME        solver backend (see `spsolve backends`)
+    --trans               solve with the transpose of A
+    --order N             fill-reducing ordering of the rsparse and csparse
+                          backends
+    --amd-dense X         AMD dense row threshold (AMD based backends)
+    --no-aggressive       disable AMD aggressive absorption
+    --tol X               pivot tolerance of the rsparse, csparse, ldlt and
+                          snlu backends
+    --max-residual X      fail if the relative residual exceeds X
+    -h, --help            print this message
+
+Exit status is 0 on success, 1 on failure and 2 on invalid usage.";
+
+/// Backends compiled into this binary, in order of preference.
+const BACKENDS: &[&str] = &[
+    #[cfg(feature = "gplu")]
+    "gplu",
+    #[cfg(feature = "rlu")]
+    "rlu",
+    #[cfg(feature = "klu")]
+    "klu",
+    #[cfg(feature = "snlu")]
+    "snlu",
+    #[cfg(feature = "ldlt")]
+    "ldlt",
+    #[cfg(feature = "rsparse")]
+    "rsparse",
+    #[cfg(feature = "lufact")]
+    "lufact",
+    #[cfg(feature = "csparse")]
+    "csparse",
+    #[cfg(feature = "basiclu")]
+    "basiclu",
+];
+
+/// Command-line error, reported with exit status 2.
+#[derive(Debug)]
+struct Usage(String);
+
+impl std::fmt::Display for Usage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Usage {}
+
+fn usage(msg: impl Into<String>) -> anyhow::Error {
+    Usage(msg.into()).into()
+}
+
+#[derive(Default)]
+struct Options {
+    matrix: Option<String>,
+    dataset: Option<String>,
+    rhs: Option<String>,
+    out: Option<String>,
+    backend: Option<String>,
+    trans: bool,
+    order: Option<i32>,
+    amd_dense: Option<f64>,
+    no_aggressive: bool,
+    tol: Option<f64>,
+    max_residual: Option<f64>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self> {
+        fn value<T: std::str::FromStr>(flag: &str, value: Option<&String>) -> Result<T> {
+            let value = value.ok_or_else(|| usage(format!("{} requires a value", flag)))?;
+            value
+                .parse()
+                .map_err(|_| usage(format!("invalid value {:?} for {}", value, flag)))
+        }
+
+        let mut opts = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--matrix" => opts.matrix = Some(value(arg, args.next())?),
+                "--dataset" => opts.dataset = Some(value(arg, args.next())?),
+                "--rhs" => opts.rhs = Some(value(arg, args.next())?),
+                "--out" => opts.out = Some(value(arg, args.next())?),
+                "--backend" => opts.backend = Some(value(arg, args.next())?),
+                "--trans" => opts.trans = true,
+                "--order" => opts.order = Some(value(arg, args.next())?),
+                "--amd-dense" => opts.amd_dense = Some(value(arg, args.next())?),
+                "--no-aggressive" => opts.no_aggressive = true,
+                "--tol" => opts.tol = Some(value(arg, args.next())?),
+                "--max-residual" => opts.max_residual = Some(value(arg, args.next())?),
+                _ => return Err(usage(format!("unknown option {:?}", arg))),
+            }
+        }
+        if opts.matrix.is_some() == opts.dataset.is_some() {
+            return Err(usage("expected one of --matrix or --dataset"));
+        }
+        Ok(opts)
+    }
+
+    #[cfg(any(feature = "gplu", feature = "rlu", feature = "snlu", feature = "ldlt"))]
+    fn amd(&self, control: &mut amd::Control) {
+        if let Some(dense) = self.amd_dense {
+            control.dense = dense;
+        }
+        if self.no_aggressive {
+            control.aggressive = false;
+        }
+    }
+
+    /// Fails if an option that `backend` does not support was given.
+    fn reject(&self, backend: &str, order: bool, amd: bool, tol: bool) -> Result<()> {
+        let unsupported = [
+            ("--order", self.order.is_some() && !order),
+            ("--amd-dense", self.amd_dense.is_some() && !amd),
+            ("--no-aggressive", self.no_aggressive && !amd),
+            ("--tol", self.tol.is_some() && !tol),
+        ];
+        match unsupported.iter().find(|(_, given)| *given) {
+            Some((flag, _)) => Err(usage(format!(
+                "{} is not supported by the {} backend",
+                flag, backend
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Constructs the backend called `name`, configured by `opts`.
+fn backend(name: &str, opts: &Options) -> Result<Box<dyn Solver<usize, f64>>> {
+    let solver: Box<dyn Solver<usize, f64>> = match name {
+        #[cfg(feature = "gplu")]
+        "gplu" => {
+            opts.reject(name, false, true, false)?;
+            let mut s = spsolve::gplu::GPLU::default();
+            opts.amd(&mut s.control);
+            Box::new(s)
+        }
+        #[cfg(feature = "rlu")]
+        "rlu" => {
+            opts.reject(name, false, true, false)?;
+            let mut s = spsolve::rlu::RLU::default();
+            opts.amd(&mut s.control);
+            Box::new(s)
+        }
+        #[cfg(feature = "klu")]
+        "klu" => {
+            opts.reject(name, false, false, false)?;
+            Box::new(spsolve::klu::KLU::default())
+        }
+        #[cfg(feature = "snlu")]
+        "snlu" => {
+            opts.reject(name, false, true, true)?;
+            let mut s = spsolve::snlu::SNLU::default();
+            opts.amd(&mut s.control);
+            if let Some(tol) = opts.tol {
+                s.pivot_tol = tol;
+            }
+            Box::new(s)
+        }
+        #[cfg(feature = "ldlt")]
+        "ldlt" => {
+            opts.reject(name, false, true, true)?;
+            let mut s = spsolve::ldlt::LDLT::default();
+            opts.amd(&mut s.control);
+            if let Some(tol) = opts.tol {
+                s.pivot_tol = tol;
+            }
+            Box::new(s)
+        }
+        #[cfg(feature = "rsparse")]
+        "rsparse" => {
+            opts.reject(name, true, false, true)?;
+            let mut s = spsolve::rsparse::RSparse::default();
+            if let Some(order) = opts.order {
+                s.order = i8::try_from(order).map_err(|_| usage("--order out of range"))?;
+            }
+            if let Some(tol) = opts.tol {
+                s.tol = tol;
+            }
+            Box::new(s)
+        }
+        #[cfg(feature = "lufact")]
+        "lufact" => {
+            opts.reject(name, false, false, false)?;
+            Box::new(spsolve::lufact::LUFact::default())
+        }
+        #[cfg(feature = "csparse")]
+        "csparse" => {
+            opts.reject(name, true, false, true)?;
+            let mut s = spsolve::csparse::CSparse::default();
+            if let Some(order) = opts.order {
+                s.order = order;
+            }
+            if let Some(tol) = opts.tol {
+                s.tol = tol;
+            }
+            Box::new(s)
+        }
+        #[cfg(feature = "basiclu")]
+        "basiclu" => {
+            opts.reject(name, false, false, false)?;
+            Box::new(spsolve::basiclu::BasicLU::default())
+        }
+        _ => {
+            return Err(usage(format!(
+                "unknown backend {:?}, expected one of: {}",
+                name,
+                BACKENDS.join(", ")
+            )))
+        }
+    };
+    Ok(solver)
+}
+
+/// Reads a matrix file in compressed-column form as `(m, n, a_p, a_i, a_x)`.
+fn read_matrix(path: &str) -> Result<Mtx<f64>> {
+    if path.ends_with(".csc") {
+        read_csc(path)
+    } else {
+        read_mtx(path)
+    }
+}
+
+/// Computes `op(A) x` for each column of `x`.
+fn multiply(
+    n: usize,
+    a_p: &[usize],
+    a_i: &[usize],
+    a_x: &[f64],
+    x: &[f64],
+    trans: bool,
+) -> Vec<f64> {
+    let mut y = vec![0.0; x.len()];
+    for (x, y) in x.chunks_exact(n).zip(y.chunks_exact_mut(n)) {
+        for j in 0..n {
+            for p in a_p[j]..a_p[j + 1] {
+                if trans {
+                    y[j] += a_x[p] * x[a_i[p]];
+                } else {
+                    y[a_i[p]] += a_x[p] * x[j];
+                }
+            }
+        }
+    }
+    y
+}
+
+fn norm_inf(x: &[f64]) -> f64 {
+    x.iter().fold(0.0, |m: f64, x| m.max(x.abs()))
+}
+
+fn solve(opts: &Options) -> Result<()> {
+    let (n, a_p, a_i, a_x) = match (&opts.matrix, &opts.dataset) {
+        (Some(path), _) => {
+            let (m, n, a_p, a_i, a_x) = read_matrix(path)?;
+            if m != n {
+                return Err(format_err!("{}: matrix is {}x{}, not square", path, m, n));
+            }
+            (n, a_p, a_i, a_x)
+        }
+        (_, Some(name)) => Dataset::get(name)?.read(true)?,
+        _ => unreachable!(),
+    };
+    if n == 0 {
+        return Err(format_err!("matrix is empty"));
+    }
+
+    let (b, x_true) = match &opts.rhs {
+        Some(path) => {
+            let (m, nrhs, b_p, b_i, b_x) = read_mtx::<f64>(path)?;
+            if m != n {
+                return Err(format_err!("{}: expected {} rows, found {}", path, n, m));
+            }
+            let mut b = vec![0.0; n * nrhs];
+            for j in 0..nrhs {
+                for p in b_p[j]..b_p[j + 1] {
+                    b[j * n + b_i[p]] = b_x[p];
+                }
+            }
+            (b, None)
+        }
+        None => {
+            let ones = vec![1.0; n];
+            (multiply(n, &a_p, &a_i, &a_x, &ones, opts.trans), Some(ones))
+        }
+    };
+    let nrhs = b.len() / n;
+
+    let name = match &opts.backend {
+        Some(name) => name.as_str(),
+        None => BACKENDS
+            .first()
+            .ok_or_else(|| format_err!("no backends compiled in"))?,
+    };
+    let solver = backend(name, opts)?;
+
+    println!("backend = {}", name);
+    println!("n = {}, nnz = {}, nrhs = {}", n, a_p[n], nrhs);
+
+    let mut x = b.clone();
     let t0 = Instant::now();
 
     #[cfg(feature = "cpuprofiler")]
     PROFILER.lock().unwrap().start("./spsolve.profile").unwrap();
 
-    solver.solve(n, &a_i, &a_p, &a_x, &mut b, trans).unwrap();
+    let result = solver.solve(n, &a_i, &a_p, &a_x, &mut x, opts.trans);
 
     #[cfg(feature = "cpuprofiler")]
     PROFILER.lock().unwrap().stop().unwrap();
 
+    result?;
     println!("t = {:#?}", t0.elapsed());
+
+    // Relative residual |b - op(A) x| / (|A| |x| + |b|) of the worst column.
+    let mut a_norm = vec![0.0; n];
+    for j in 0..n {
+        for p in a_p[j]..a_p[j + 1] {
+            a_norm[if opts.trans { j } else { a_i[p] }] += a_x[p].abs();
+        }
+    }
+    let a_norm = norm_inf(&a_norm);
+    let ax = multiply(n, &a_p, &a_i, &a_x, &x, opts.trans);
+    let mut residual: f64 = 0.0;
+    for ((x, b), ax) in x
+        .chunks_exact(n)
+        .zip(b.chunks_exact(n))
+        .zip(ax.chunks_exact(n))
+    {
+        let r: Vec<f64> = b.iter().zip(ax).map(|(b, ax)| b - ax).collect();
+        let scale = a_norm * norm_inf(x) + norm_inf(b);
+        let r = if scale > 0.0 {
+            norm_inf(&r) / scale
+        } else {
+            norm_inf(&r)
+        };
+        residual = residual.max(r);
+    }
+    println!("residual = {:e}", residual);
+    if let Some(x_true) = x_true {
+        let err = x
+            .iter()
+            .zip(&x_true)
+            .map(|(x, t)| x - t)
+            .collect::<Vec<_>>();
+        println!("error = {:e}", norm_inf(&err));
+    }
+
+    if let Some(path) = &opts.out {
+        write_mtx_dense(path, n, nrhs, &x)?;
+    }
+
+    if !residual.is_finite() {
+        return Err(format_err!("solution is not finite"));
+    }
+    if let Some(max) = opts.max_residual {
+        if residual > max {
+            return Err(format_err!("residual {:e} exceeds {:e}", residual, max));
+        }
+    }
+    Ok(())
+}
+
+fn run(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("solve") => {
+            if args[1..].iter().any(|a| a == "-h" || a == "--help") {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            solve(&Options::parse(&args[1..])?)
+        }
+        Some("backends") => {
+            for name in BACKENDS {
+                println!("{}", name);
+            }
+            Ok(())
+        }
+        Some("-h" | "--help" | "help") => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(cmd) => Err(usage(format!("unknown command {:?}", cmd))),
+        None => Err(usage("missing command")),
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) if err.is::<Usage>() => {
+            eprintln!("spsolve: {}\nRun `spsolve --help` for usage.", err);
+            ExitCode::from(2)
+        }
+        Err(err) => {
+            eprintln!("spsolve: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,100 @@
+//! Conversions of matrices in compressed-column form.
+
+use num_traits::ToPrimitive;
+use std::ops::Add;
+
+/// Returns the transpose of an `m x n` matrix in compressed-column form,
+/// which is also the matrix in compressed-row form. Row indices of the
+/// transpose are sorted.
+pub(crate) fn transpose<P, I, S>(
+    m: usize,
+    n: usize,
+    a_p: &[P],
+    a_i: &[I],
+    a_x: &[S],
+) -> (Vec<usize>, Vec<usize>, Vec<S>)
+where
+    P: ToPrimitive,
+    I: ToPrimitive,
+    S: Copy,
+{
+    let nnz = a_p[n].to_usize().unwrap();
+    let mut count = vec![0; m + 1];
+    for i in &a_i[..nnz] {
+        count[i.to_usize().unwrap() + 1] += 1;
+    }
+    for i in 0..m {
+        count[i + 1] += count[i];
+    }
+    let t_p = count.clone();
+    let mut t_i = vec![0; nnz];
+    let mut t_x = a_x[..nnz].to_vec();
+    for j in 0..n {
+        for p in a_p[j].to_usize().unwrap()..a_p[j + 1].to_usize().unwrap() {
+            let q = &mut count[a_i[p].to_usize().unwrap()];
+            t_i[*q] = j;
+            t_x[*q] = a_x[p];
+            *q += 1;
+        }
+    }
+    (t_p, t_i, t_x)
+}
+
+/// Converts triplets to compressed-column form with `n` columns, with
+/// sorted row indices and duplicates summed.
+pub(crate) fn compress<S>(
+    n: usize,
+    t_i: &[usize],
+    t_j: &[usize],
+    t_x: &[S],
+) -> (Vec<usize>, Vec<usize>, Vec<S>)
+where
+    S: Copy + Add<Output = S>,
+{
+    let mut order: Vec<usize> = (0..t_i.len()).collect();
+    order.sort_by_key(|&k| (t_j[k], t_i[k]));
+
+    let mut a_p = vec![0; n + 1];
+    let mut a_i: Vec<usize> = Vec::with_capacity(order.len());
+    let mut a_x: Vec<S> = Vec::with_capacity(order.len());
+    let mut last = None;
+    for k in order {
+        let (i, j) = (t_i[k], t_j[k]);
+        if last == Some((i, j)) {
+            let x = a_x.last_mut().unwrap();
+            *x = *x + t_x[k];
+        } else {
+            a_i.push(i);
+            a_x.push(t_x[k]);
+            a_p[j + 1] += 1;
+            last = Some((i, j));
+        }
+    }
+    for j in 0..n {
+        a_p[j + 1] += a_p[j];
+    }
+    (a_p, a_i, a_x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, transpose};
+
+    #[test]
+    fn test_transpose() {
+        // A = {1, 2, 0}
+        //     {0, 3, 4}
+        let (t_p, t_i, t_x) = transpose(2, 3, &[0, 1, 3, 4], &[0, 1, 0, 1], &[1, 3, 2, 4]);
+        assert_eq!(t_p, vec![0, 2, 4]);
+        assert_eq!(t_i, vec![0, 1, 1, 2]);
+        assert_eq!(t_x, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_compress() {
+        let (a_p, a_i, a_x) = compress(3, &[1, 0, 1, 2], &[2, 0, 2, 0], &[1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(a_p, vec![0, 2, 2, 3]);
+        assert_eq!(a_i, vec![0, 2, 1]);
+        assert_eq!(a_x, vec![2.0, 4.0, 4.0]);
+    }
+}
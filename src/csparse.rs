@@ -1,9 +1,9 @@
 use anyhow::{format_err, Result};
-use num_traits::ToPrimitive;
-use std::alloc::{alloc, Layout};
-use suitesparse_sys::{cs_di_lusol, cs_di_sparse};
+use std::mem::zeroed;
+use suitesparse_sys::{cs_di_lusol, cs_di_qrsol, cs_di_sparse, cs_dl_lusol, cs_dl_sparse};
 
-use crate::Solver;
+use crate::index::to_i32;
+use crate::{CIndex, LeastSquaresSolver, Solver};
 
 pub struct CSparse {
     /// Fill-reducing ordering
@@ -25,7 +25,7 @@ impl Default for CSparse {
 
 impl<I> Solver<I, f64> for CSparse
 where
-    I: ToPrimitive,
+    I: CIndex,
 {
     fn solve(
         &self,
@@ -36,26 +36,30 @@ where
         b: &mut [f64],
         _trans: bool,
     ) -> Result<()> {
-        let n = n as i32;
-        let mut a_i: Vec<i32> = a_i.iter().map(|i| i.to_i32().unwrap()).collect();
-        let mut a_p: Vec<i32> = a_p.iter().map(|i| i.to_i32().unwrap()).collect();
-        let mut a_x = a_x.to_vec();
-        let nzmax = a_x.len() as i32;
+        if n == 0 || b.len() != n {
+            return Err(format_err!("b length {} must be {}", b.len(), n));
+        }
+        if let (Some(a_i), Some(a_p)) = (I::as_i64_slice(a_i), I::as_i64_slice(a_p)) {
+            return self.solve_long(n, a_i, a_p, a_x, b);
+        }
+
+        let n = to_i32(n)?;
+        let a_i = I::to_i32_slice(a_i)?;
+        let a_p = I::to_i32_slice(a_p)?;
+        let nzmax = to_i32(a_x.len())?;
 
         unsafe {
-            let cs = alloc(Layout::new::<cs_di_sparse>()) as *mut cs_di_sparse;
-            if cs.is_null() {
-                return Err(format_err!("error allocating cs_di_sparse"));
-            }
-            (*cs).nzmax = i32::max(nzmax, 1);
-            (*cs).m = n;
-            (*cs).n = n;
-            (*cs).p = a_p.as_mut_ptr();
-            (*cs).i = a_i.as_mut_ptr();
-            (*cs).x = a_x.as_mut_ptr();
-            (*cs).nz = -1; // compressed column
-
-            let rv = cs_di_lusol(self.order, cs, b.as_mut_ptr(), self.tol);
+            // The matrix is only read by cs_di_lusol.
+            let mut cs = zeroed::<cs_di_sparse>();
+            cs.nzmax = i32::max(nzmax, 1);
+            cs.m = n;
+            cs.n = n;
+            cs.p = a_p.as_ptr() as *mut i32;
+            cs.i = a_i.as_ptr() as *mut i32;
+            cs.x = a_x.as_ptr() as *mut f64;
+            cs.nz = -1; // compressed column
+
+            let rv = cs_di_lusol(self.order, &cs, b.as_mut_ptr(), self.tol);
             if rv != 1 {
                 return Err(format_err!("error calling cs_di_lusol"));
             }
@@ -64,6 +68,82 @@ where
     }
 }
 
+impl CSparse {
+    /// Solves with the `SuiteSparse_long` routines, without copying the indices.
+    fn solve_long(
+        &self,
+        n: usize,
+        a_i: &[i64],
+        a_p: &[i64],
+        a_x: &[f64],
+        b: &mut [f64],
+    ) -> Result<()> {
+        let n = n as i64;
+        let nzmax = a_x.len() as i64;
+
+        unsafe {
+            // The matrix is only read by cs_dl_lusol.
+            let mut cs = zeroed::<cs_dl_sparse>();
+            cs.nzmax = i64::max(nzmax, 1);
+            cs.m = n;
+            cs.n = n;
+            cs.p = a_p.as_ptr() as *mut i64;
+            cs.i = a_i.as_ptr() as *mut i64;
+            cs.x = a_x.as_ptr() as *mut f64;
+            cs.nz = -1; // compressed column
+
+            let rv = cs_dl_lusol(self.order as i64, &cs, b.as_mut_ptr(), self.tol);
+            if rv != 1 {
+                return Err(format_err!("error calling cs_dl_lusol"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I> LeastSquaresSolver<I, f64> for CSparse
+where
+    I: CIndex,
+{
+    fn solve(
+        &self,
+        m: usize,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[f64],
+        b: &mut [f64],
+    ) -> Result<()> {
+        if self.order == 1 {
+            return Err(format_err!("order {} not supported for QR", self.order));
+        }
+        if b.len() != usize::max(m, n) {
+            return Err(format_err!("b must have length {}", usize::max(m, n)));
+        }
+        let a_i = I::to_i32_slice(a_i)?;
+        let a_p = I::to_i32_slice(a_p)?;
+        let nzmax = to_i32(a_x.len())?;
+
+        unsafe {
+            // The matrix is only read by cs_di_qrsol.
+            let mut cs = zeroed::<cs_di_sparse>();
+            cs.nzmax = i32::max(nzmax, 1);
+            cs.m = to_i32(m)?;
+            cs.n = to_i32(n)?;
+            cs.p = a_p.as_ptr() as *mut i32;
+            cs.i = a_i.as_ptr() as *mut i32;
+            cs.x = a_x.as_ptr() as *mut f64;
+            cs.nz = -1; // compressed column
+
+            let rv = cs_di_qrsol(self.order, &cs, b.as_mut_ptr());
+            if rv != 1 {
+                return Err(format_err!("error calling cs_di_qrsol"));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::CSparse;
@@ -76,6 +156,18 @@ mod tests {
         test::simple_solver_test::<i32, f64, CSparse>(solver)
     }
 
+    #[test]
+    fn test_csparse_i64() -> Result<()> {
+        let solver = CSparse::default();
+        test::simple_solver_test::<i64, f64, CSparse>(solver)
+    }
+
+    #[test]
+    fn test_csparse_least_squares() -> Result<()> {
+        let solver = CSparse::default();
+        test::simple_least_squares_test::<i32, f64, CSparse>(solver)
+    }
+
     #[test]
     #[cfg(feature = "matrix")]
     fn test_solver() -> Result<()> {
@@ -0,0 +1,39 @@
+use num_traits::ToPrimitive;
+
+/// Returns the sign of the permutation `p` (`1` if even, `-1` if odd).
+pub(crate) fn permutation_sign<I: ToPrimitive>(p: &[I]) -> f64 {
+    let n = p.len();
+    let mut visited = vec![false; n];
+    let mut sign = 1.0;
+    for k in 0..n {
+        if visited[k] {
+            continue;
+        }
+        // A cycle of length l is the product of l - 1 transpositions.
+        let mut j = k;
+        let mut len = 0;
+        while !visited[j] {
+            visited[j] = true;
+            j = p[j].to_usize().unwrap();
+            len += 1;
+        }
+        if len % 2 == 0 {
+            sign = -sign;
+        }
+    }
+    sign
+}
+
+#[cfg(test)]
+mod tests {
+    use super::permutation_sign;
+
+    #[test]
+    fn test_permutation_sign() {
+        assert_eq!(permutation_sign::<usize>(&[]), 1.0);
+        assert_eq!(permutation_sign::<usize>(&[0, 1, 2]), 1.0);
+        assert_eq!(permutation_sign::<usize>(&[1, 0, 2]), -1.0);
+        assert_eq!(permutation_sign::<usize>(&[1, 2, 0]), 1.0);
+        assert_eq!(permutation_sign::<isize>(&[3, 2, 1, 0]), 1.0);
+    }
+}
@@ -0,0 +1,448 @@
+//! Dulmage-Mendelsohn decomposition.
+//!
+//! Permutes an `m x n` matrix `A` to block upper triangular form `A(p, q)`.
+//! The coarse decomposition splits the rows and columns into an
+//! under-determined part, a square well-determined part and an
+//! over-determined part:
+//!
+//! ```txt
+//!          C0  C1  C2  C3
+//!     R1 [ A11 A12 A13 A14 ]
+//!     R2 [  0   0  A23 A24 ]
+//!     R3 [  0   0   0  A34 ]
+//!     R0 [  0   0   0  A44 ]
+//! ```
+//!
+//! The fine decomposition further splits `A(R2, C2)` into its strongly
+//! connected components, giving the block triangular form used for BTF
+//! preprocessing.
+
+use crate::csc::transpose;
+use crate::rank::max_matching;
+use num_traits::ToPrimitive;
+
+/// Dulmage-Mendelsohn decomposition from [`dmperm`].
+#[derive(Debug, Clone)]
+pub struct DMPerm {
+    /// Row permutation.
+    pub p: Vec<usize>,
+    /// Column permutation.
+    pub q: Vec<usize>,
+    /// Fine block `k` is rows `r[k]..r[k + 1]` of `A(p, q)`.
+    pub r: Vec<usize>,
+    /// Fine block `k` is columns `s[k]..s[k + 1]` of `A(p, q)`.
+    pub s: Vec<usize>,
+    /// Coarse row decomposition. `R1`, `R2`, `R3` and `R0` are rows
+    /// `rr[0]..rr[1]`, `rr[1]..rr[2]`, `rr[2]..rr[3]` and `rr[3]..rr[4]`.
+    pub rr: [usize; 5],
+    /// Coarse column decomposition. `C0`, `C1`, `C2` and `C3` are columns
+    /// `cc[0]..cc[1]`, `cc[1]..cc[2]`, `cc[2]..cc[3]` and `cc[3]..cc[4]`.
+    pub cc: [usize; 5],
+}
+
+impl DMPerm {
+    /// Number of fine blocks.
+    pub fn nb(&self) -> usize {
+        self.r.len() - 1
+    }
+
+    /// Structural rank of the matrix.
+    pub fn structural_rank(&self) -> usize {
+        self.rr[3]
+    }
+
+    /// Rows `R1` and columns `C0 C1` of the under-determined part, with
+    /// more unknowns than equations.
+    pub fn underdetermined(&self) -> (&[usize], &[usize]) {
+        (
+            &self.p[self.rr[0]..self.rr[1]],
+            &self.q[self.cc[0]..self.cc[2]],
+        )
+    }
+
+    /// Rows `R3 R0` and columns `C3` of the over-determined part, with
+    /// more equations than unknowns.
+    pub fn overdetermined(&self) -> (&[usize], &[usize]) {
+        (
+            &self.p[self.rr[2]..self.rr[4]],
+            &self.q[self.cc[3]..self.cc[4]],
+        )
+    }
+}
+
+/// Computes the Dulmage-Mendelsohn decomposition of an `m x n` matrix.
+pub fn dmperm<I: ToPrimitive>(m: usize, n: usize, a_i: &[I], a_p: &[I]) -> DMPerm {
+    let a_p: Vec<usize> = a_p.iter().map(|p| p.to_usize().unwrap()).collect();
+    let a_i: Vec<usize> = a_i[..a_p[n]]
+        .iter()
+        .map(|i| i.to_usize().unwrap())
+        .collect();
+
+    let (row_match, col_match) = max_matching(m, n, &a_i, &a_p);
+
+    // Coarse decomposition.
+    let mut p = vec![0; m];
+    let mut q = vec![0; n];
+    let mut rr = [0; 5];
+    let mut cc = [0; 5];
+    let mut wi = vec![Mark::None; m];
+    let mut wj = vec![Mark::None; n];
+
+    // Find C1 and R1 from C0.
+    bfs(
+        n,
+        &a_p,
+        &a_i,
+        &mut wi,
+        &mut wj,
+        &col_match,
+        &row_match,
+        Mark::One,
+    );
+    // Find R3 and C3 from R0.
+    let (at_p, at_i, _) = transpose(m, n, &a_p, &a_i, &vec![(); a_i.len()]);
+    bfs(
+        m,
+        &at_p,
+        &at_i,
+        &mut wj,
+        &mut wi,
+        &row_match,
+        &col_match,
+        Mark::Three,
+    );
+
+    unmatched(&wj, &mut q, &mut cc, 0);
+    matched(
+        &wj,
+        &col_match,
+        &mut p,
+        &mut q,
+        &mut cc,
+        &mut rr,
+        1,
+        Mark::One,
+    );
+    matched(
+        &wj,
+        &col_match,
+        &mut p,
+        &mut q,
+        &mut cc,
+        &mut rr,
+        2,
+        Mark::None,
+    );
+    matched(
+        &wj,
+        &col_match,
+        &mut p,
+        &mut q,
+        &mut cc,
+        &mut rr,
+        3,
+        Mark::Three,
+    );
+    unmatched(&wi, &mut p, &mut rr, 3);
+
+    // Fine decomposition of C = A(R2, C2).
+    let mut p_inv = vec![0; m];
+    for (k, &i) in p.iter().enumerate() {
+        p_inv[i] = k;
+    }
+    let nc = cc[3] - cc[2];
+    let mut c_p = vec![0; nc + 1];
+    let mut c_i = Vec::default();
+    for k in 0..nc {
+        let j = q[cc[2] + k];
+        for &i in &a_i[a_p[j]..a_p[j + 1]] {
+            let r = p_inv[i];
+            if r >= rr[1] && r < rr[2] {
+                c_i.push(r - rr[1]);
+            }
+        }
+        c_p[k + 1] = c_i.len();
+    }
+    let (ps, rs) = scc(nc, &c_p, &c_i);
+
+    let qs: Vec<usize> = ps.iter().map(|&k| q[k + cc[2]]).collect();
+    q[cc[2]..cc[2] + nc].copy_from_slice(&qs);
+    let ps: Vec<usize> = ps.iter().map(|&k| p[k + rr[1]]).collect();
+    p[rr[1]..rr[1] + nc].copy_from_slice(&ps);
+
+    // Fine block partitions.
+    let mut r = vec![0];
+    let mut s = vec![0];
+    if cc[2] > 0 {
+        // Leading coarse block A(R1, [C0 C1]).
+        r.push(0);
+        s.push(0);
+    }
+    for &k in &rs[..rs.len() - 1] {
+        *r.last_mut().unwrap() = k + rr[1];
+        *s.last_mut().unwrap() = k + cc[2];
+        r.push(0);
+        s.push(0);
+    }
+    if rr[2] < m {
+        // Trailing coarse block A([R3 R0], C3).
+        *r.last_mut().unwrap() = rr[2];
+        *s.last_mut().unwrap() = cc[3];
+        r.push(0);
+        s.push(0);
+    }
+    *r.last_mut().unwrap() = m;
+    *s.last_mut().unwrap() = n;
+
+    DMPerm { p, q, r, s, rr, cc }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Mark {
+    /// Not visited.
+    None,
+    /// Unmatched.
+    Zero,
+    One,
+    Three,
+}
+
+/// Breadth-first search along alternating paths from the unmatched
+/// columns of a matrix with `n` columns.
+#[allow(clippy::too_many_arguments)]
+fn bfs(
+    n: usize,
+    a_p: &[usize],
+    a_i: &[usize],
+    wi: &mut [Mark],
+    wj: &mut [Mark],
+    col_match: &[Option<usize>],
+    row_match: &[Option<usize>],
+    mark: Mark,
+) {
+    let mut queue = Vec::default();
+    for j in 0..n {
+        if col_match[j].is_none() {
+            wj[j] = Mark::Zero;
+            queue.push(j);
+        }
+    }
+    let mut head = 0;
+    while head < queue.len() {
+        let j = queue[head];
+        head += 1;
+        for &i in &a_i[a_p[j]..a_p[j + 1]] {
+            if wi[i] != Mark::None {
+                continue;
+            }
+            wi[i] = mark;
+            // Rows reachable from unmatched columns are always matched.
+            let j2 = row_match[i].unwrap();
+            if wj[j2] != Mark::None {
+                continue;
+            }
+            wj[j2] = mark;
+            queue.push(j2);
+        }
+    }
+}
+
+/// Collects the matched rows and columns of a coarse set into `p` and `q`.
+#[allow(clippy::too_many_arguments)]
+fn matched(
+    wj: &[Mark],
+    col_match: &[Option<usize>],
+    p: &mut [usize],
+    q: &mut [usize],
+    cc: &mut [usize; 5],
+    rr: &mut [usize; 5],
+    set: usize,
+    mark: Mark,
+) {
+    let mut kc = cc[set];
+    let mut kr = rr[set - 1];
+    for (j, &w) in wj.iter().enumerate() {
+        if w != mark {
+            continue;
+        }
+        p[kr] = col_match[j].unwrap();
+        kr += 1;
+        q[kc] = j;
+        kc += 1;
+    }
+    cc[set + 1] = kc;
+    rr[set] = kr;
+}
+
+/// Collects the unmatched rows or columns into the permutation `p`.
+fn unmatched(w: &[Mark], p: &mut [usize], rr: &mut [usize; 5], set: usize) {
+    let mut kr = rr[set];
+    for (i, &w) in w.iter().enumerate() {
+        if w == Mark::Zero {
+            p[kr] = i;
+            kr += 1;
+        }
+    }
+    rr[set + 1] = kr;
+}
+
+/// Depth-first search of the graph of a matrix from node `j`. Nodes are
+/// pushed onto `xi[..top]` in reverse order of finish time.
+fn dfs(
+    j: usize,
+    g_p: &[usize],
+    g_i: &[usize],
+    marked: &mut [bool],
+    top: usize,
+    xi: &mut [usize],
+    stack: &mut Vec<(usize, usize)>,
+) -> usize {
+    let mut top = top;
+    stack.clear();
+    stack.push((j, g_p[j]));
+    marked[j] = true;
+    while let Some(&(j, p)) = stack.last() {
+        if p < g_p[j + 1] {
+            let i = g_i[p];
+            stack.last_mut().unwrap().1 += 1;
+            if !marked[i] {
+                marked[i] = true;
+                stack.push((i, g_p[i]));
+            }
+        } else {
+            stack.pop();
+            top -= 1;
+            xi[top] = j;
+        }
+    }
+    top
+}
+
+/// Strongly connected components of the graph of a square matrix.
+///
+/// Returns the permutation `p` and block boundaries `r` such that
+/// `C(p, p)` is block upper triangular.
+fn scc(n: usize, c_p: &[usize], c_i: &[usize]) -> (Vec<usize>, Vec<usize>) {
+    let (ct_p, ct_i, _) = transpose(n, n, c_p, c_i, &vec![(); c_i.len()]);
+    let mut stack = Vec::default();
+
+    // First DFS of C to find finish times.
+    let mut xi = vec![0; n];
+    let mut marked = vec![false; n];
+    let mut top = n;
+    for i in 0..n {
+        if !marked[i] {
+            top = dfs(i, c_p, c_i, &mut marked, top, &mut xi, &mut stack);
+        }
+    }
+
+    // DFS of C' in reverse order of finish time.
+    let mut p = vec![0; n];
+    let mut r = vec![0; n + 1];
+    let mut marked = vec![false; n];
+    let mut top = n;
+    let mut nb = n;
+    for &i in &xi {
+        if marked[i] {
+            continue;
+        }
+        r[nb] = top;
+        nb -= 1;
+        top = dfs(i, &ct_p, &ct_i, &mut marked, top, &mut p, &mut stack);
+    }
+    r[nb] = 0;
+    let r: Vec<usize> = r[nb..].to_vec();
+    let nb = r.len() - 1;
+
+    // Sort each block in natural order.
+    let mut blk = vec![0; n];
+    for b in 0..nb {
+        for &i in &p[r[b]..r[b + 1]] {
+            blk[i] = b;
+        }
+    }
+    let mut next = r.clone();
+    for i in 0..n {
+        p[next[blk[i]]] = i;
+        next[blk[i]] += 1;
+    }
+    (p, r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dmperm, DMPerm};
+
+    /// Asserts that `A(p, q)` is block upper triangular.
+    fn assert_block_triangular(m: usize, n: usize, a_i: &[usize], a_p: &[usize], d: &DMPerm) {
+        let mut row_block = vec![0; m];
+        let mut col_block = vec![0; n];
+        for b in 0..d.nb() {
+            for k in d.r[b]..d.r[b + 1] {
+                row_block[d.p[k]] = b;
+            }
+            for k in d.s[b]..d.s[b + 1] {
+                col_block[d.q[k]] = b;
+            }
+        }
+        for j in 0..n {
+            for &i in &a_i[a_p[j]..a_p[j + 1]] {
+                assert!(row_block[i] <= col_block[j], "A({}, {})", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dmperm_reducible() {
+        //     {1, 1,  }
+        //     { , 1,  }
+        //     {1, 1, 1}
+        let a_i: Vec<usize> = vec![0, 2, 0, 1, 2, 2];
+        let a_p: Vec<usize> = vec![0, 2, 5, 6];
+
+        let d = dmperm(3, 3, &a_i, &a_p);
+        assert_eq!(d.structural_rank(), 3);
+        assert_eq!(d.nb(), 3);
+        assert_block_triangular(3, 3, &a_i, &a_p, &d);
+    }
+
+    #[test]
+    fn test_dmperm_singular() {
+        //     {1, , , 1}
+        //     { ,1,1,  }
+        //     { , , ,  }
+        //     {1, , , 1}
+        let a_i: Vec<usize> = vec![0, 3, 1, 1, 0, 3];
+        let a_p: Vec<usize> = vec![0, 2, 3, 4, 6];
+
+        let d = dmperm(4, 4, &a_i, &a_p);
+        assert_eq!(d.structural_rank(), 3);
+        assert_block_triangular(4, 4, &a_i, &a_p, &d);
+
+        let (rows, cols) = d.underdetermined();
+        assert_eq!(rows, &[1]);
+        assert_eq!(cols.len(), 2);
+        let (rows, cols) = d.overdetermined();
+        assert_eq!(rows, &[2]);
+        assert!(cols.is_empty());
+    }
+
+    #[test]
+    fn test_dmperm_rectangular() {
+        // Over-determined 3x2 matrix.
+        //
+        //     {1,  }
+        //     {1, 1}
+        //     {1,  }
+        let a_i: Vec<usize> = vec![0, 1, 2, 1];
+        let a_p: Vec<usize> = vec![0, 3, 4];
+
+        let d = dmperm(3, 2, &a_i, &a_p);
+        assert_eq!(d.structural_rank(), 2);
+        assert_block_triangular(3, 2, &a_i, &a_p, &d);
+
+        let (rows, cols) = d.overdetermined();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(cols, &[0]);
+    }
+}
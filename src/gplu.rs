@@ -1,4 +1,6 @@
-use crate::{FactorSolver, Solver};
+use crate::batch::try_map;
+use crate::rank::check_structural_rank;
+use crate::{assert_send_sync, BatchFactorSolver, FactorSolver, Solver};
 use anyhow::{format_err, Result};
 use gplu::{Scalar, LU};
 use num_traits::{NumAssignOps, PrimInt};
@@ -25,18 +27,12 @@ where
         b: &mut [S],
         trans: bool,
     ) -> Result<()> {
-        let (p, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), &a_p, &a_i, &self.control)
+        check_structural_rank(n, a_i, a_p)?;
+
+        let (p, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), a_p, a_i, &self.control)
             .map_err(|st| format_err!("amd status: {:?}", st))?;
 
-        let lu = gplu::factor(
-            I::from(n).unwrap(),
-            &a_i,
-            &a_p,
-            &a_x,
-            Some(&p),
-            &self.options,
-        )
-        .map_err(|err| format_err!("factor error: {}", err))?;
+        let lu = factor(n, a_i, a_p, a_x, &p, &self.options)?;
 
         gplu::solve(&lu, b, trans).map_err(|err| format_err!("solve error: {}", err))?;
 
@@ -50,18 +46,12 @@ where
     S: Scalar,
 {
     fn factor(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<LU<S>> {
-        let (p, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), &a_p, &a_i, &self.control)
+        check_structural_rank(n, a_i, a_p)?;
+
+        let (p, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), a_p, a_i, &self.control)
             .map_err(|st| format_err!("amd status: {:?}", st))?;
 
-        let lu = gplu::factor(
-            I::from(n).unwrap(),
-            &a_i,
-            &a_p,
-            &a_x,
-            Some(&p),
-            &self.options,
-        )
-        .map_err(|err| format_err!("factor error: {}", err))?;
+        let lu = factor(n, a_i, a_p, a_x, &p, &self.options)?;
         Ok(lu)
     }
 
@@ -71,10 +61,50 @@ where
     }
 }
 
+impl<I, S> BatchFactorSolver<I, S, LU<S>> for GPLU
+where
+    I: PrimInt + NumAssignOps + Display + Sync,
+    S: Scalar + Send + Sync,
+    LU<S>: Send,
+{
+    fn factor_batch(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[&[S]]) -> Result<Vec<LU<S>>> {
+        check_structural_rank(n, a_i, a_p)?;
+
+        let (p, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), a_p, a_i, &self.control)
+            .map_err(|st| format_err!("amd status: {:?}", st))?;
+
+        try_map(a_x, |a_x| factor(n, a_i, a_p, a_x, &p, &self.options))
+    }
+}
+
+/// Factorizes with the column ordering `p`. Callers check the structural
+/// rank first. gplu does not report the rank when it stops at a pivot
+/// that is exactly zero, so its errors are returned as they are.
+fn factor<I, S>(
+    n: usize,
+    a_i: &[I],
+    a_p: &[I],
+    a_x: &[S],
+    p: &[I],
+    options: &gplu::Options,
+) -> Result<LU<S>>
+where
+    I: PrimInt + NumAssignOps + Display,
+    S: Scalar,
+{
+    gplu::factor(I::from(n).unwrap(), a_i, a_p, a_x, Some(p), options)
+        .map_err(|err| format_err!("factor error: {}", err))
+}
+
+const _: () = assert_send_sync::<GPLU>();
+const _: () = assert_send_sync::<LU<f64>>();
+
 #[cfg(test)]
 mod tests {
     use super::GPLU;
+    use crate::rank::SingularMatrix;
     use crate::test;
+    use crate::FactorSolver;
     use anyhow::Result;
 
     #[test]
@@ -96,4 +126,43 @@ mod tests {
         let solver = GPLU::default();
         test::test_factor_solver_bbus(&solver, 1, 1e-11)
     }
+
+    #[test]
+    fn singular_test() -> Result<()> {
+        let solver = GPLU::default();
+        test::singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn numerically_singular_test() -> Result<()> {
+        // Columns 0 and 2 are equal. gplu stops at the zero pivot without
+        // a rank, so this is not a SingularMatrix error.
+        let solver = GPLU::default();
+        let a_i = vec![0, 1, 3, 0, 2, 0, 1, 3, 1, 3];
+        let a_p = vec![0, 3, 5, 8, 10];
+        let a_x = vec![1.0, 2.0, 4.0, 2.0, 1.0, 1.0, 2.0, 4.0, 1.0, 2.0];
+        let err = FactorSolver::<usize, f64, _>::factor(&solver, 4, &a_i, &a_p, &a_x).unwrap_err();
+        assert!(err.downcast_ref::<SingularMatrix>().is_none());
+        assert!(err.to_string().contains("zero"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn batch_test() -> Result<()> {
+        let solver = GPLU::default();
+        test::simple_batch_test(&solver)
+    }
+
+    #[test]
+    fn schur_test() -> Result<()> {
+        let solver = GPLU::default();
+        test::simple_schur_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn solve_par_test() -> Result<()> {
+        let solver = GPLU::default();
+        test::simple_solve_par_test(&solver)
+    }
 }
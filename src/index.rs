@@ -0,0 +1,96 @@
+//! Index arrays for the C backends.
+
+use anyhow::{format_err, Result};
+use num_traits::ToPrimitive;
+use std::borrow::Cow;
+
+/// Index types of matrices passed to the C backends. Arrays that already
+/// have the integer type of the C routine are passed without copying.
+pub trait CIndex: ToPrimitive + Sized {
+    /// Returns the indices as `i32`, converting unless `Self` is `i32`.
+    /// Fails if an index is out of range for `i32`.
+    fn to_i32_slice(a: &[Self]) -> Result<Cow<'_, [i32]>> {
+        let a = a
+            .iter()
+            .map(|i| {
+                i.to_i32()
+                    .ok_or_else(|| format_err!("index {:?} out of range for i32", i.to_i128()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Cow::Owned(a))
+    }
+
+    /// Returns the indices as `i64`, converting unless `Self` is `i64`.
+    /// Fails if an index is out of range for `i64`.
+    fn to_i64_slice(a: &[Self]) -> Result<Cow<'_, [i64]>> {
+        if let Some(a) = Self::as_i64_slice(a) {
+            return Ok(Cow::Borrowed(a));
+        }
+        let a = a
+            .iter()
+            .map(|i| {
+                i.to_i64()
+                    .ok_or_else(|| format_err!("index {:?} out of range for i64", i.to_i128()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Cow::Owned(a))
+    }
+
+    /// Returns the indices if `Self` is `i64`, for the `SuiteSparse_long`
+    /// versions of the C routines.
+    fn as_i64_slice(a: &[Self]) -> Option<&[i64]> {
+        let _ = a;
+        None
+    }
+}
+
+/// Converts a size to `i32` for the C routines.
+pub(crate) fn to_i32(n: usize) -> Result<i32> {
+    i32::try_from(n).map_err(|_| format_err!("{} out of range for i32", n))
+}
+
+impl CIndex for i32 {
+    fn to_i32_slice(a: &[Self]) -> Result<Cow<'_, [i32]>> {
+        Ok(Cow::Borrowed(a))
+    }
+}
+
+impl CIndex for i64 {
+    fn as_i64_slice(a: &[Self]) -> Option<&[i64]> {
+        Some(a)
+    }
+}
+
+impl CIndex for u8 {}
+impl CIndex for u16 {}
+impl CIndex for u32 {}
+impl CIndex for u64 {}
+impl CIndex for usize {}
+impl CIndex for i8 {}
+impl CIndex for i16 {}
+impl CIndex for isize {}
+
+#[cfg(test)]
+mod tests {
+    use super::CIndex;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_to_i32_slice() {
+        let a: Vec<i32> = vec![0, 2, 5];
+        assert!(matches!(i32::to_i32_slice(&a), Ok(Cow::Borrowed(_))));
+
+        let a: Vec<usize> = vec![0, 2, 5];
+        assert_eq!(usize::to_i32_slice(&a).unwrap().as_ref(), &[0, 2, 5]);
+
+        let a: Vec<i64> = vec![0, i64::from(i32::MAX) + 1];
+        assert!(i64::to_i32_slice(&a).is_err());
+        assert!(matches!(i64::to_i64_slice(&a), Ok(Cow::Borrowed(_))));
+
+        let a: Vec<i32> = vec![0, 2, 5];
+        assert_eq!(i32::to_i64_slice(&a).unwrap().as_ref(), &[0, 2, 5]);
+
+        let a: Vec<u64> = vec![0, u64::MAX];
+        assert!(u64::to_i64_slice(&a).is_err());
+    }
+}
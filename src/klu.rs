@@ -1,12 +1,17 @@
 use anyhow::{format_err, Result};
-use num_traits::ToPrimitive;
-use std::alloc::{alloc, Layout};
+use std::mem::zeroed;
+use std::sync::Arc;
 use suitesparse_sys::{
     klu_analyze, klu_common, klu_defaults, klu_factor, klu_free_numeric, klu_free_symbolic,
-    klu_numeric, klu_solve, klu_symbolic, klu_tsolve,
+    klu_l_analyze, klu_l_common, klu_l_defaults, klu_l_factor, klu_l_free_numeric,
+    klu_l_free_symbolic, klu_l_numeric, klu_l_solve, klu_l_symbolic, klu_l_tsolve, klu_numeric,
+    klu_solve, klu_symbolic, klu_tsolve,
 };
 
-use crate::Solver;
+use crate::batch::try_map;
+use crate::det::permutation_sign;
+use crate::index::to_i32;
+use crate::{assert_send_sync, BatchFactorSolver, CIndex, FactorSolver, LogDet, Solver, Workspace};
 
 pub struct KLU {}
 
@@ -18,7 +23,7 @@ impl Default for KLU {
 
 impl<I> Solver<I, f64> for KLU
 where
-    I: ToPrimitive,
+    I: CIndex,
 {
     fn solve(
         &self,
@@ -29,39 +34,46 @@ where
         b: &mut [f64],
         trans: bool,
     ) -> Result<()> {
-        let n = n as i32;
-        let a_i: Vec<i32> = a_i.iter().map(|i| i.to_i32().unwrap()).collect();
-        let a_p: Vec<i32> = a_p.iter().map(|i| i.to_i32().unwrap()).collect();
+        check_rhs(n, b)?;
+        if let (Some(a_i), Some(a_p)) = (I::as_i64_slice(a_i), I::as_i64_slice(a_p)) {
+            return solve_long(n, a_i, a_p, a_x, b, trans);
+        }
+
+        let nrhs = to_i32(b.len() / n)?;
+        let n = to_i32(n)?;
+        let a_i = I::to_i32_slice(a_i)?;
+        let a_p = I::to_i32_slice(a_p)?;
 
         unsafe {
-            let common = alloc(Layout::new::<klu_common>()) as *mut klu_common;
-            if common.is_null() {
-                return Err(format_err!("error allocating common"));
-            }
-            if klu_defaults(common) != 1 {
+            let mut common = zeroed::<klu_common>();
+            if klu_defaults(&mut common) != 1 {
                 return Err(format_err!("error calling klu_defaults"));
             }
 
-            let mut symbolic = klu_analyze(n, a_p.as_ptr(), a_i.as_ptr(), common);
+            let mut symbolic = klu_analyze(n, a_p.as_ptr(), a_i.as_ptr(), &mut common);
             if symbolic.is_null() {
                 return Err(format_err!("error calling klu_analyze"));
             }
 
-            let mut numeric =
-                klu_factor(a_p.as_ptr(), a_i.as_ptr(), a_x.as_ptr(), symbolic, common);
+            let mut numeric = klu_factor(
+                a_p.as_ptr(),
+                a_i.as_ptr(),
+                a_x.as_ptr(),
+                symbolic,
+                &mut common,
+            );
             if numeric.is_null() {
-                klu_free_symbolic(&mut symbolic as *mut *mut klu_symbolic, common);
+                klu_free_symbolic(&mut symbolic as *mut *mut klu_symbolic, &mut common);
                 return Err(format_err!("error calling klu_factor"));
             }
 
-            let nrhs = b.len() as i32 / n;
             let rv = if trans {
-                klu_tsolve(symbolic, numeric, n, nrhs, b.as_mut_ptr(), common)
+                klu_tsolve(symbolic, numeric, n, nrhs, b.as_mut_ptr(), &mut common)
             } else {
-                klu_solve(symbolic, numeric, n, nrhs, b.as_mut_ptr(), common)
+                klu_solve(symbolic, numeric, n, nrhs, b.as_mut_ptr(), &mut common)
             };
-            klu_free_numeric(&mut numeric as *mut *mut klu_numeric, common);
-            klu_free_symbolic(&mut symbolic as *mut *mut klu_symbolic, common);
+            klu_free_numeric(&mut numeric as *mut *mut klu_numeric, &mut common);
+            klu_free_symbolic(&mut symbolic as *mut *mut klu_symbolic, &mut common);
             if rv != 1 {
                 return Err(format_err!("error calling klu_solve"));
             }
@@ -70,10 +82,317 @@ where
     }
 }
 
+/// Returns an error unless `b` holds a whole number of right-hand-sides
+/// of length `n`.
+fn check_rhs(n: usize, b: &[f64]) -> Result<()> {
+    if n == 0 || !b.len().is_multiple_of(n) {
+        return Err(format_err!(
+            "b length {} is not a multiple of {}",
+            b.len(),
+            n
+        ));
+    }
+    Ok(())
+}
+
+/// Solves with the `SuiteSparse_long` routines, without copying the indices.
+/// `b.len()` must be a multiple of `n`, which must be nonzero.
+fn solve_long(
+    n: usize,
+    a_i: &[i64],
+    a_p: &[i64],
+    a_x: &[f64],
+    b: &mut [f64],
+    trans: bool,
+) -> Result<()> {
+    let nrhs = (b.len() / n) as i64;
+    let n = n as i64;
+    unsafe {
+        let mut common = zeroed::<klu_l_common>();
+        if klu_l_defaults(&mut common) != 1 {
+            return Err(format_err!("error calling klu_l_defaults"));
+        }
+
+        let mut symbolic = klu_l_analyze(n, a_p.as_ptr(), a_i.as_ptr(), &mut common);
+        if symbolic.is_null() {
+            return Err(format_err!("error calling klu_l_analyze"));
+        }
+
+        let mut numeric = klu_l_factor(
+            a_p.as_ptr(),
+            a_i.as_ptr(),
+            a_x.as_ptr(),
+            symbolic,
+            &mut common,
+        );
+        if numeric.is_null() {
+            klu_l_free_symbolic(&mut symbolic as *mut *mut klu_l_symbolic, &mut common);
+            return Err(format_err!("error calling klu_l_factor"));
+        }
+
+        let rv = if trans {
+            klu_l_tsolve(symbolic, numeric, n, nrhs, b.as_mut_ptr(), &mut common)
+        } else {
+            klu_l_solve(symbolic, numeric, n, nrhs, b.as_mut_ptr(), &mut common)
+        };
+        klu_l_free_numeric(&mut numeric as *mut *mut klu_l_numeric, &mut common);
+        klu_l_free_symbolic(&mut symbolic as *mut *mut klu_l_symbolic, &mut common);
+        if rv != 1 {
+            return Err(format_err!("error calling klu_l_solve"));
+        }
+    }
+    Ok(())
+}
+
+/// Symbolic analysis, shared by the factors of matrices with the same pattern.
+struct Symbolic(*mut klu_l_symbolic);
+
+impl Drop for Symbolic {
+    fn drop(&mut self) {
+        unsafe {
+            let mut common = zeroed::<klu_l_common>();
+            klu_l_defaults(&mut common);
+            klu_l_free_symbolic(&mut self.0, &mut common);
+        }
+    }
+}
+
+// The analysis is not modified after klu_l_analyze.
+unsafe impl Send for Symbolic {}
+unsafe impl Sync for Symbolic {}
+
+/// Symbolic and numeric factors from [`KLU`].
+///
+/// The factors are computed with the `SuiteSparse_long` routines, so `i64`
+/// index arrays are passed without copying and other index types are
+/// converted to `i64` once per factorization. Solves do not use the indices.
+pub struct KLUFactor {
+    n: usize,
+    symbolic: Arc<Symbolic>,
+    numeric: *mut klu_l_numeric,
+}
+
+// The factor owns the numeric object, which is not modified by solves
+// (see `FactorSolver::solve_with`).
+unsafe impl Send for KLUFactor {}
+unsafe impl Sync for KLUFactor {}
+
+impl Drop for KLUFactor {
+    fn drop(&mut self) {
+        unsafe {
+            let mut common = zeroed::<klu_l_common>();
+            klu_l_defaults(&mut common);
+            klu_l_free_numeric(&mut self.numeric, &mut common);
+        }
+    }
+}
+
+impl KLU {
+    fn analyze(&self, n: usize, a_i: &[i64], a_p: &[i64]) -> Result<Arc<Symbolic>> {
+        unsafe {
+            let mut common = zeroed::<klu_l_common>();
+            if klu_l_defaults(&mut common) != 1 {
+                return Err(format_err!("error calling klu_l_defaults"));
+            }
+
+            let symbolic = klu_l_analyze(n as i64, a_p.as_ptr(), a_i.as_ptr(), &mut common);
+            if symbolic.is_null() {
+                return Err(format_err!("error calling klu_l_analyze"));
+            }
+            Ok(Arc::new(Symbolic(symbolic)))
+        }
+    }
+
+    fn numeric(
+        &self,
+        n: usize,
+        a_i: &[i64],
+        a_p: &[i64],
+        a_x: &[f64],
+        symbolic: Arc<Symbolic>,
+    ) -> Result<KLUFactor> {
+        unsafe {
+            let mut common = zeroed::<klu_l_common>();
+            if klu_l_defaults(&mut common) != 1 {
+                return Err(format_err!("error calling klu_l_defaults"));
+            }
+
+            let numeric = klu_l_factor(
+                a_p.as_ptr(),
+                a_i.as_ptr(),
+                a_x.as_ptr(),
+                symbolic.0,
+                &mut common,
+            );
+            if numeric.is_null() {
+                return Err(format_err!("error calling klu_l_factor"));
+            }
+
+            Ok(KLUFactor {
+                n,
+                symbolic,
+                numeric,
+            })
+        }
+    }
+}
+
+impl<I> FactorSolver<I, f64, KLUFactor> for KLU
+where
+    I: CIndex,
+{
+    fn factor(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[f64]) -> Result<KLUFactor> {
+        let a_i = I::to_i64_slice(a_i)?;
+        let a_p = I::to_i64_slice(a_p)?;
+
+        let symbolic = self.analyze(n, &a_i, &a_p)?;
+        self.numeric(n, &a_i, &a_p, a_x, symbolic)
+    }
+
+    /// Reuses the symbolic analysis of `f`.
+    fn refactor(
+        &self,
+        f: &mut KLUFactor,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[f64],
+    ) -> Result<()> {
+        if n != f.n {
+            return Err(format_err!("expected order {}, found {}", f.n, n));
+        }
+        let a_i = I::to_i64_slice(a_i)?;
+        let a_p = I::to_i64_slice(a_p)?;
+        *f = self.numeric(n, &a_i, &a_p, a_x, f.symbolic.clone())?;
+        Ok(())
+    }
+
+    fn solve(&self, f: &KLUFactor, b: &mut [f64], trans: bool) -> Result<()> {
+        FactorSolver::<I, f64, KLUFactor>::solve_with(self, f, b, trans, &mut Workspace::new())
+    }
+
+    fn solve_with(
+        &self,
+        f: &KLUFactor,
+        b: &mut [f64],
+        trans: bool,
+        ws: &mut Workspace<f64>,
+    ) -> Result<()> {
+        check_rhs(f.n, b)?;
+        let nrhs = (b.len() / f.n) as i64;
+        let n = f.n as i64;
+
+        // klu_l_solve only writes to the numeric object's Xwork, of size 4n,
+        // so each solve uses a copy that points it at the workspace instead.
+        let x = ws.slice(4 * f.n);
+        // SAFETY: f.numeric is a valid object from klu_l_factor, owned by f.
+        // The bitwise copy shares its arrays, which klu_l_solve and
+        // klu_l_tsolve only read, and its Xwork is pointed at x, which has
+        // the 4n entries that they need. The copy is never freed, so the
+        // arrays are freed once, when f is dropped, and concurrent solves
+        // with the same factor only write to their own workspaces.
+        unsafe {
+            let mut numeric = std::ptr::read(f.numeric);
+            numeric.Xwork = x.as_mut_ptr().cast();
+
+            let mut common = zeroed::<klu_l_common>();
+            if klu_l_defaults(&mut common) != 1 {
+                return Err(format_err!("error calling klu_l_defaults"));
+            }
+            let rv = if trans {
+                klu_l_tsolve(
+                    f.symbolic.0,
+                    &mut numeric,
+                    n,
+                    nrhs,
+                    b.as_mut_ptr(),
+                    &mut common,
+                )
+            } else {
+                klu_l_solve(
+                    f.symbolic.0,
+                    &mut numeric,
+                    n,
+                    nrhs,
+                    b.as_mut_ptr(),
+                    &mut common,
+                )
+            };
+            if rv != 1 {
+                return Err(format_err!("error calling klu_l_solve"));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LogDet<f64> for KLUFactor {
+    fn log_det(&self) -> Result<(f64, f64)> {
+        let n = self.n;
+        if n == 0 {
+            return Ok((1.0, 0.0));
+        }
+        // P (R \ A) Q = L U, with row scale factors R.
+        let (u_diag, r_s, p, q) = unsafe {
+            let numeric = &*self.numeric;
+            let symbolic = &*self.symbolic.0;
+            (
+                std::slice::from_raw_parts(numeric.Udiag as *const f64, n),
+                if numeric.Rs.is_null() {
+                    None
+                } else {
+                    Some(std::slice::from_raw_parts(numeric.Rs, n))
+                },
+                std::slice::from_raw_parts(numeric.Pnum, n),
+                std::slice::from_raw_parts(symbolic.Q, n),
+            )
+        };
+
+        let mut sign = permutation_sign(p) * permutation_sign(q);
+        let mut ln = 0.0;
+        for &u in u_diag {
+            if u == 0.0 {
+                return Ok((0.0, f64::NEG_INFINITY));
+            }
+            sign *= u.signum();
+            ln += u.abs().ln();
+        }
+        if let Some(r_s) = r_s {
+            ln += r_s.iter().map(|r| r.ln()).sum::<f64>();
+        }
+        Ok((sign, ln))
+    }
+}
+
+impl<I> BatchFactorSolver<I, f64, KLUFactor> for KLU
+where
+    I: CIndex,
+{
+    fn factor_batch(
+        &self,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[&[f64]],
+    ) -> Result<Vec<KLUFactor>> {
+        let a_i = I::to_i64_slice(a_i)?;
+        let a_p = I::to_i64_slice(a_p)?;
+
+        let symbolic = self.analyze(n, &a_i, &a_p)?;
+        try_map(a_x, |a_x| {
+            self.numeric(n, &a_i, &a_p, a_x, symbolic.clone())
+        })
+    }
+}
+
+const _: () = assert_send_sync::<KLU>();
+const _: () = assert_send_sync::<KLUFactor>();
+
 #[cfg(test)]
 mod tests {
     use super::KLU;
     use crate::test;
+    use crate::Solver;
     use anyhow::Result;
 
     #[test]
@@ -82,10 +401,84 @@ mod tests {
         test::simple_solver_test::<usize, f64, KLU>(solver)
     }
 
+    #[test]
+    fn simple_test_i32() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_solver_test::<i32, f64, KLU>(solver)
+    }
+
+    #[test]
+    fn simple_test_i64() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_solver_test::<i64, f64, KLU>(solver)
+    }
+
+    #[test]
+    fn empty_test() {
+        // The SuiteSparse_long path is used for i64 indices.
+        let solver = KLU::default();
+        let mut b = vec![1.0];
+        let res = Solver::<i32, f64>::solve(&solver, 0, &[], &[0], &[], &mut b, false);
+        assert!(res.is_err());
+        let res = Solver::<i64, f64>::solve(&solver, 0, &[], &[0], &[], &mut b, false);
+        assert!(res.is_err());
+    }
+
     #[test]
     #[cfg(feature = "matrix")]
     fn test_solver() -> Result<()> {
         let solver = KLU::default();
         test::test_solver_bbus(&solver, 1, 1e-8)
     }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_solver_gen() -> Result<()> {
+        let solver = KLU::default();
+        test::test_solver_gen(&solver, 1e-9)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_factor_solver() -> Result<()> {
+        let solver = KLU::default();
+        test::test_factor_solver_bbus(&solver, 1, 1e-8)
+    }
+
+    #[test]
+    fn log_det_test() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_log_det_test(&solver)
+    }
+
+    #[test]
+    fn pivoting_log_det_test() -> Result<()> {
+        let solver = KLU::default();
+        test::pivoting_log_det_test(&solver)
+    }
+
+    #[test]
+    fn batch_test() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_batch_test(&solver)
+    }
+
+    #[test]
+    fn refactor_test() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_refactor_test(&solver)
+    }
+
+    #[test]
+    fn workspace_test() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_workspace_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn solve_par_test() -> Result<()> {
+        let solver = KLU::default();
+        test::simple_solve_par_test(&solver)
+    }
 }
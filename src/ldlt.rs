@@ -0,0 +1,676 @@
+use crate::csc::transpose;
+use crate::rank::check_structural_rank;
+use crate::selinv::Takahashi;
+use crate::triangular::{Columns, Diag, Triangular};
+use crate::{
+    assert_send_sync, FactorSolver, LogDet, Scalar, SelectedInversion, SelectedSolver, Solver,
+    Workspace,
+};
+use anyhow::{format_err, Result};
+use num_traits::{NumAssignOps, PrimInt};
+use std::fmt::Display;
+
+/// Solver based on [AMD](https://crates.io/crates/amd) and an up-looking
+/// sparse `LDL^T` factorization.
+///
+/// Supports real symmetric indefinite and complex symmetric (not Hermitian)
+/// matrices. Both triangles of the input matrix must be stored, and
+/// [`factor`](FactorSolver::factor) fails if they differ. No dynamic
+/// pivoting is performed, instead pivots with a modulus smaller than
+/// `pivot_tol * max|a_x|` are replaced by a value of that modulus
+/// (static pivoting). Solves with a perturbed factorization are improved
+/// by iterative refinement against the input matrix.
+pub struct LDLT {
+    pub control: amd::Control,
+
+    /// Static pivoting threshold, relative to the largest entry of the matrix.
+    pub pivot_tol: f64,
+
+    /// Maximum number of iterative refinement steps if any pivots
+    /// were perturbed.
+    pub refine: usize,
+}
+
+impl Default for LDLT {
+    fn default() -> Self {
+        Self {
+            control: amd::Control::default(),
+            pivot_tol: 1e-14,
+            refine: 10,
+        }
+    }
+}
+
+/// Factors `P A P' = L D L'` from [`LDLT`].
+pub struct LDLTFactor<S> {
+    n: usize,
+    /// Fill-reducing permutation.
+    p: Vec<usize>,
+    /// Strictly lower triangular part of the unit lower triangular factor `L`.
+    l_p: Vec<usize>,
+    l_i: Vec<usize>,
+    l_x: Vec<S>,
+    /// Diagonal of `D`.
+    d: Vec<S>,
+    perturbed: usize,
+    /// Input matrix, kept for iterative refinement if any pivots
+    /// were perturbed.
+    a_p: Vec<usize>,
+    a_i: Vec<usize>,
+    a_x: Vec<S>,
+}
+
+impl<S: Scalar> LDLTFactor<S> {
+    /// Order of the factorized matrix.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Number of entries in the strictly lower triangular part of `L`.
+    pub fn nnz(&self) -> usize {
+        self.l_p[self.n]
+    }
+
+    /// Number of pivots that were replaced by static pivoting. Solves
+    /// are refined if this is nonzero.
+    pub fn perturbed(&self) -> usize {
+        self.perturbed
+    }
+
+    /// Diagonal of `D`, in pivot order.
+    pub fn d(&self) -> &[S] {
+        &self.d
+    }
+
+    /// Fill-reducing permutation `P`.
+    pub fn perm(&self) -> &[usize] {
+        &self.p
+    }
+
+    fn solve_in_place(&self, x: &mut [S], b: &mut [S]) {
+        let n = self.n;
+        for k in 0..n {
+            x[k] = b[self.p[k]];
+        }
+        for j in 0..n {
+            let x_j = x[j];
+            for p in self.l_p[j]..self.l_p[j + 1] {
+                let x_i = x[self.l_i[p]];
+                x[self.l_i[p]] = x_i - self.l_x[p] * x_j;
+            }
+        }
+        for (x_j, &d_j) in x.iter_mut().zip(&self.d) {
+            *x_j /= d_j;
+        }
+        for j in (0..n).rev() {
+            let mut x_j = x[j];
+            for p in self.l_p[j]..self.l_p[j + 1] {
+                x_j -= self.l_x[p] * x[self.l_i[p]];
+            }
+            x[j] = x_j;
+        }
+        for k in 0..n {
+            b[self.p[k]] = x[k];
+        }
+    }
+
+    /// Solves for each right-hand-side of length `n` in `b`, with up to
+    /// `refine` steps of iterative refinement if any pivots were perturbed.
+    fn solve_all(&self, b: &mut [S], refine: usize, ws: &mut Workspace<S>) -> Result<()> {
+        let n = self.n;
+        if n == 0 || !b.len().is_multiple_of(n) {
+            return Err(format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                n
+            ));
+        }
+        if self.perturbed == 0 || refine == 0 {
+            let x = ws.slice(n);
+            for b in b.chunks_exact_mut(n) {
+                self.solve_in_place(x, b);
+            }
+            return Ok(());
+        }
+
+        let (x, rest) = ws.slice(3 * n).split_at_mut(n);
+        let (r, b0) = rest.split_at_mut(n);
+        for b in b.chunks_exact_mut(n) {
+            b0.copy_from_slice(b);
+            self.solve_in_place(x, b);
+            let mut r_last = f64::INFINITY;
+            for _ in 0..refine {
+                // r = b - A x
+                r.copy_from_slice(b0);
+                for (j, &x_j) in b.iter().enumerate() {
+                    for p in self.a_p[j]..self.a_p[j + 1] {
+                        let r_i = r[self.a_i[p]];
+                        r[self.a_i[p]] = r_i - self.a_x[p] * x_j;
+                    }
+                }
+                let r_max = r.iter().fold(0.0, |m: f64, r| m.max(r.norm()));
+                if r_max == 0.0 || r_max >= r_last {
+                    break;
+                }
+                r_last = r_max;
+                self.solve_in_place(x, r);
+                for (b, &r) in b.iter_mut().zip(r.iter()) {
+                    *b += r;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: Scalar> LDLTFactor<S> {
+    fn takahashi(&self) -> Takahashi<S> {
+        let l = Triangular {
+            p: &self.l_p,
+            i: &self.l_i,
+            x: &self.l_x,
+            diag: Diag::Unit,
+        };
+        // U = DL', so the transpose of U with a unit diagonal is L.
+        Takahashi::new(self.n, &l, &l, &self.d)
+    }
+}
+
+impl<S: Scalar> LogDet<S> for LDLTFactor<S> {
+    fn log_det(&self) -> Result<(S, f64)> {
+        // det(A) = det(P)^2 det(L)^2 det(D) = det(D)
+        let mut sign = S::one();
+        let mut ln = 0.0;
+        for &d in &self.d {
+            let d_abs = d.norm();
+            if d_abs == 0.0 {
+                return Ok((S::zero(), f64::NEG_INFINITY));
+            }
+            sign *= d / S::from_f64(d_abs);
+            ln += d_abs.ln();
+        }
+        Ok((sign, ln))
+    }
+}
+
+impl LDLT {
+    fn factorize<I, S>(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<LDLTFactor<S>>
+    where
+        I: PrimInt + NumAssignOps + Display,
+        S: Scalar,
+    {
+        check_structural_rank(n, a_i, a_p)?;
+        check_symmetric(n, a_i, a_p, a_x)?;
+
+        let (p, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), a_p, a_i, &self.control)
+            .map_err(|st| format_err!("amd status: {:?}", st))?;
+        let p: Vec<usize> = p.iter().map(|&k| k.to_usize().unwrap()).collect();
+        let mut p_inv = vec![0; n];
+        for (k, &i) in p.iter().enumerate() {
+            p_inv[i] = k;
+        }
+
+        // Upper triangular part of C = P A P'.
+        let (c_p, c_i, c_x) = permuted_upper(n, a_i, a_p, a_x, &p, &p_inv);
+
+        let a_max = a_x.iter().fold(0.0, |m: f64, &x| m.max(x.norm()));
+        let tol = self.pivot_tol * if a_max > 0.0 { a_max } else { 1.0 };
+
+        // Symbolic analysis: elimination tree and column counts of L.
+        let mut parent = vec![None; n];
+        let mut flag = vec![0; n];
+        let mut l_nz = vec![0; n];
+        for k in 0..n {
+            flag[k] = k;
+            for &i in &c_i[c_p[k]..c_p[k + 1]] {
+                let mut i = i;
+                if i < k {
+                    while flag[i] != k {
+                        if parent[i].is_none() {
+                            parent[i] = Some(k);
+                        }
+                        l_nz[i] += 1;
+                        flag[i] = k;
+                        i = parent[i].unwrap();
+                    }
+                }
+            }
+        }
+        let mut l_p = vec![0; n + 1];
+        for k in 0..n {
+            l_p[k + 1] = l_p[k] + l_nz[k];
+        }
+
+        // Numeric factorization.
+        let nnz = l_p[n];
+        let mut l_i = vec![0; nnz];
+        let mut l_x = vec![S::zero(); nnz];
+        let mut d = vec![S::zero(); n];
+        let mut y = vec![S::zero(); n];
+        let mut pattern = vec![0; n];
+        let mut perturbed = 0;
+        for k in 0..n {
+            // Nonzero pattern of row k of L, in topological order.
+            let mut top = n;
+            flag[k] = k;
+            l_nz[k] = 0;
+            for q in c_p[k]..c_p[k + 1] {
+                let mut i = c_i[q];
+                y[i] += c_x[q];
+                let mut len = 0;
+                while flag[i] != k {
+                    pattern[len] = i;
+                    len += 1;
+                    flag[i] = k;
+                    i = parent[i].unwrap();
+                }
+                while len > 0 {
+                    top -= 1;
+                    len -= 1;
+                    pattern[top] = pattern[len];
+                }
+            }
+
+            d[k] = y[k];
+            y[k] = S::zero();
+            for &i in &pattern[top..n] {
+                let y_i = y[i];
+                y[i] = S::zero();
+                let end = l_p[i] + l_nz[i];
+                for q in l_p[i]..end {
+                    y[l_i[q]] -= l_x[q] * y_i;
+                }
+                let l_ki = y_i / d[i];
+                d[k] -= l_ki * y_i;
+                l_i[end] = k;
+                l_x[end] = l_ki;
+                l_nz[i] += 1;
+            }
+
+            let d_k = d[k].norm();
+            if d_k < tol {
+                d[k] = if d_k == 0.0 {
+                    S::from_f64(tol)
+                } else {
+                    d[k] * S::from_f64(tol / d_k)
+                };
+                perturbed += 1;
+            }
+        }
+
+        let (a_p, a_i, a_x) = if perturbed > 0 {
+            let a_p: Vec<usize> = a_p.iter().map(|p| p.to_usize().unwrap()).collect();
+            let nnz = a_p[n];
+            let a_i = a_i[..nnz].iter().map(|i| i.to_usize().unwrap()).collect();
+            (a_p, a_i, a_x[..nnz].to_vec())
+        } else {
+            (Vec::default(), Vec::default(), Vec::default())
+        };
+
+        Ok(LDLTFactor {
+            n,
+            p,
+            l_p,
+            l_i,
+            l_x,
+            d,
+            perturbed,
+            a_p,
+            a_i,
+            a_x,
+        })
+    }
+}
+
+/// Fails unless the pattern and values of `A` are symmetric. Only the upper
+/// triangle of `P A P'` is factorized, so the lower triangle would otherwise
+/// be ignored.
+fn check_symmetric<I, S>(n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<()>
+where
+    I: PrimInt,
+    S: Scalar,
+{
+    // Transposing twice sorts the row indices of A.
+    let t = transpose(n, n, a_p, a_i, a_x);
+    if t != transpose(n, n, &t.0, &t.1, &t.2) {
+        return Err(format_err!("matrix is not symmetric"));
+    }
+    Ok(())
+}
+
+/// Returns the upper triangular part of `P A P'` in compressed-column form.
+fn permuted_upper<I, S>(
+    n: usize,
+    a_i: &[I],
+    a_p: &[I],
+    a_x: &[S],
+    p: &[usize],
+    p_inv: &[usize],
+) -> (Vec<usize>, Vec<usize>, Vec<S>)
+where
+    I: PrimInt,
+    S: Scalar,
+{
+    let mut count = vec![0; n + 1];
+    for j in 0..n {
+        let (start, end) = (a_p[j].to_usize().unwrap(), a_p[j + 1].to_usize().unwrap());
+        for i in &a_i[start..end] {
+            let (r, c) = (p_inv[i.to_usize().unwrap()], p_inv[j]);
+            if r <= c {
+                count[c + 1] += 1;
+            }
+        }
+    }
+    for k in 0..n {
+        count[k + 1] += count[k];
+    }
+    let c_p = count.clone();
+    let nnz = c_p[n];
+    let mut c_i = vec![0; nnz];
+    let mut c_x = vec![S::zero(); nnz];
+    for k in 0..n {
+        let j = p[k];
+        for q in a_p[j].to_usize().unwrap()..a_p[j + 1].to_usize().unwrap() {
+            let r = p_inv[a_i[q].to_usize().unwrap()];
+            if r <= k {
+                c_i[count[k]] = r;
+                c_x[count[k]] = a_x[q];
+                count[k] += 1;
+            }
+        }
+    }
+    (c_p, c_i, c_x)
+}
+
+impl<I, S> Solver<I, S> for LDLT
+where
+    I: PrimInt + NumAssignOps + Display,
+    S: Scalar,
+{
+    fn solve(
+        &self,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[S],
+        b: &mut [S],
+        _trans: bool,
+    ) -> Result<()> {
+        let f = self.factorize(n, a_i, a_p, a_x)?;
+        f.solve_all(b, self.refine, &mut Workspace::new())
+    }
+}
+
+impl<I, S> FactorSolver<I, S, LDLTFactor<S>> for LDLT
+where
+    I: PrimInt + NumAssignOps + Display,
+    S: Scalar,
+{
+    fn factor(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<LDLTFactor<S>> {
+        self.factorize(n, a_i, a_p, a_x)
+    }
+
+    fn solve(&self, f: &LDLTFactor<S>, b: &mut [S], trans: bool) -> Result<()> {
+        FactorSolver::<I, S, LDLTFactor<S>>::solve_with(self, f, b, trans, &mut Workspace::new())
+    }
+
+    fn solve_with(
+        &self,
+        f: &LDLTFactor<S>,
+        b: &mut [S],
+        _trans: bool,
+        ws: &mut Workspace<S>,
+    ) -> Result<()> {
+        f.solve_all(b, self.refine, ws)
+    }
+}
+
+impl<S: Scalar> SelectedSolver<S, LDLTFactor<S>> for LDLT {
+    fn solve_selected(&self, f: &LDLTFactor<S>, b: &[S], rows: &[usize]) -> Result<Vec<S>> {
+        let n = f.n;
+        if b.len() != n {
+            return Err(format_err!("b length {} must be {}", b.len(), n));
+        }
+        if let Some(r) = rows.iter().find(|&&r| r >= n) {
+            return Err(format_err!("row {} out of range", r));
+        }
+        let l = Triangular {
+            p: &f.l_p,
+            i: &f.l_i,
+            x: &f.l_x,
+            diag: Diag::Unit,
+        };
+        let mut pinv = vec![0; n];
+        for (k, &i) in f.p.iter().enumerate() {
+            pinv[i] = k;
+        }
+
+        let mut x: Vec<S> = f.p.iter().map(|&i| b[i]).collect();
+        l.solve(&(0..n).collect::<Vec<_>>(), &mut x); // x = L\x
+
+        // The entries of x at rows only depend on their ancestors
+        // in the elimination tree.
+        let wanted: Vec<usize> = rows.iter().map(|&r| pinv[r]).collect();
+        let pattern = l.reach(&wanted, &mut vec![false; n]);
+        for &j in &pattern {
+            x[j] /= f.d[j]; // x = D\x
+        }
+        l.solve_transposed(&pattern, &mut x); // x = L'\x
+
+        Ok(wanted.iter().map(|&k| x[k]).collect())
+    }
+}
+
+impl<S: Scalar> SelectedInversion<S, LDLTFactor<S>> for LDLT {
+    fn inverse_selected(&self, f: &LDLTFactor<S>) -> Result<(Vec<usize>, Vec<usize>, Vec<S>)> {
+        // P A P' = L D L', so inv(A)(p[a], p[b]) = inv(LDL')(a, b).
+        Ok(f.takahashi().to_csc(&f.p, &f.p))
+    }
+
+    fn inverse_diag(&self, f: &LDLTFactor<S>) -> Result<Vec<S>> {
+        let z = f.takahashi();
+        let mut d = vec![S::zero(); f.n];
+        for (k, &i) in f.p.iter().enumerate() {
+            d[i] = z.get(k, k).unwrap();
+        }
+        Ok(d)
+    }
+}
+
+const _: () = assert_send_sync::<LDLT>();
+const _: () = assert_send_sync::<LDLTFactor<f64>>();
+const _: () = assert_send_sync::<LDLTFactor<num_complex::Complex64>>();
+
+#[cfg(test)]
+mod tests {
+    use super::LDLT;
+    #[cfg(feature = "matrix")]
+    use crate::matrix::Dataset;
+    use crate::{test, FactorSolver, LogDet, Solver};
+    use anyhow::Result;
+    use num_complex::Complex64;
+
+    #[test]
+    fn simple_test() -> Result<()> {
+        let solver = LDLT::default();
+        test::simple_solver_test::<usize, f64, LDLT>(solver)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_factor_solver_ybus() -> Result<()> {
+        let solver = LDLT::default();
+        test::test_factor_solver_ybus(&solver, 2, 1e-9)
+    }
+
+    #[test]
+    fn singular_test() -> Result<()> {
+        let solver = LDLT::default();
+        test::singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn unsymmetric_test() {
+        let solver = LDLT::default();
+        // A = {2, 1}
+        //     {0, 2}
+        let f = FactorSolver::<usize, f64, _>::factor(
+            &solver,
+            2,
+            &[0, 0, 1],
+            &[0, 1, 3],
+            &[2.0, 1.0, 2.0],
+        );
+        assert!(f.is_err());
+        // A = {2, 1}
+        //     {3, 2}
+        let f = FactorSolver::<usize, f64, _>::factor(
+            &solver,
+            2,
+            &[0, 1, 0, 1],
+            &[0, 2, 4],
+            &[2.0, 3.0, 1.0, 2.0],
+        );
+        assert!(f.is_err());
+    }
+
+    #[test]
+    fn workspace_test() -> Result<()> {
+        let solver = LDLT::default();
+        test::simple_workspace_test(&solver)
+    }
+
+    #[test]
+    fn log_det_test() -> Result<()> {
+        let solver = LDLT::default();
+        test::simple_log_det_test(&solver)
+    }
+
+    #[test]
+    fn selected_solve_test() -> Result<()> {
+        let solver = LDLT::default();
+        test::simple_selected_solve_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_selected_inverse() -> Result<()> {
+        let solver = LDLT::default();
+        let (n, a_p, a_i, a_x) = Dataset::get("ACTIVSg200_Ybus")?.read::<Complex64>(true)?;
+        test::test_selected_inverse(&solver, n, &a_i, &a_p, &a_x, 1e-9)
+    }
+
+    #[test]
+    fn complex_log_det_test() -> Result<()> {
+        let (a, b, c) = (
+            Complex64::new(2.0, 1.0),
+            Complex64::new(0.5, -0.5),
+            Complex64::new(-1.0, 3.0),
+        );
+        let a_i: Vec<usize> = vec![0, 1, 0, 1];
+        let a_p: Vec<usize> = vec![0, 2, 4];
+        let a_x = vec![a, b, b, c];
+
+        let solver = LDLT::default();
+        let f = solver.factor(2, &a_i, &a_p, &a_x)?;
+        let (phase, ln) = f.log_det()?;
+
+        let det = a * c - b * b;
+        assert!((phase.norm() - 1.0).abs() < 1e-15);
+        assert!((phase * ln.exp() - det).norm() < 1e-12);
+        Ok(())
+    }
+
+    #[test]
+    fn kkt_test() -> Result<()> {
+        // Equality constrained QP, with H = diag(4, 3, 2) and the
+        // constraint x0 + x1 + x2 = 1.
+        //
+        //     K = {4.0,    ,    , 1.0}
+        //         {   , 3.0,    , 1.0}
+        //         {   ,    , 2.0, 1.0}
+        //         {1.0, 1.0, 1.0,    }
+        let a_i: Vec<usize> = vec![0, 3, 1, 3, 2, 3, 0, 1, 2];
+        let a_p: Vec<usize> = vec![0, 2, 4, 6, 9];
+        let a_x = vec![4.0, 1.0, 3.0, 1.0, 2.0, 1.0, 1.0, 1.0, 1.0];
+
+        let solver = LDLT::default();
+        let f = solver.factor(4, &a_i, &a_p, &a_x)?;
+        assert_eq!(f.perturbed(), 0);
+        // One negative eigenvalue for the single constraint.
+        assert_eq!(f.d().iter().filter(|&&d| d < 0.0).count(), 1);
+        assert_eq!(f.log_det()?.0, -1.0);
+
+        let mut b = vec![0.0, 0.0, 0.0, 1.0];
+        FactorSolver::<usize, f64, _>::solve(&solver, &f, &mut b, false)?;
+        // x = inv(H) 1 / (1' inv(H) 1) and y = -1 / (1' inv(H) 1).
+        let s = 1.0 / 4.0 + 1.0 / 3.0 + 1.0 / 2.0;
+        let expect = [0.25 / s, 1.0 / 3.0 / s, 0.5 / s, -1.0 / s];
+        for (i, (x, e)) in b.iter().zip(expect).enumerate() {
+            assert!((x - e).abs() < 1e-14, "x[{}] = {}, expected {}", i, x, e);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn perturbed_test() -> Result<()> {
+        // The leading zero pivot is replaced by static pivoting.
+        //
+        //     A = {   , 1.0,    }
+        //         {1.0, 2.0, 1.0}
+        //         {   , 1.0, 3.0}
+        let a_i: Vec<usize> = vec![1, 0, 1, 2, 1, 2];
+        let a_p: Vec<usize> = vec![0, 1, 4, 6];
+        let a_x = vec![1.0, 1.0, 2.0, 1.0, 1.0, 3.0];
+
+        let solver = LDLT {
+            pivot_tol: 1e-8,
+            ..Default::default()
+        };
+        let f = solver.factor(3, &a_i, &a_p, &a_x)?;
+        assert_eq!(f.perturbed(), 1);
+
+        // A x = b for x = {1, 2, 3}.
+        let b0 = vec![2.0, 8.0, 11.0];
+        let mut b = b0.clone();
+        FactorSolver::<usize, f64, _>::solve(&solver, &f, &mut b, false)?;
+        for (i, x) in b.iter().enumerate() {
+            let expect = (i + 1) as f64;
+            assert!((x - expect).abs() < 1e-12, "x[{}] = {}", i, x);
+        }
+
+        let mut b = b0.clone();
+        Solver::<usize, f64>::solve(&solver, 3, &a_i, &a_p, &a_x, &mut b, false)?;
+        assert!((b[0] - 1.0).abs() < 1e-12, "x[0] = {}", b[0]);
+
+        // Without refinement the solution is not accurate.
+        let solver = LDLT {
+            pivot_tol: 1e-8,
+            refine: 0,
+            ..Default::default()
+        };
+        let mut b = b0;
+        FactorSolver::<usize, f64, _>::solve(&solver, &f, &mut b, false)?;
+        assert!(b
+            .iter()
+            .enumerate()
+            .any(|(i, x)| (x - (i + 1) as f64).abs() > 1e-12));
+        Ok(())
+    }
+
+    #[test]
+    fn empty_rhs_test() -> Result<()> {
+        let solver = LDLT::default();
+        let f = solver.factor(0, &[] as &[usize], &[0], &[] as &[f64])?;
+        let mut b = vec![1.0];
+        assert!(FactorSolver::<usize, f64, _>::solve(&solver, &f, &mut b, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn solve_par_test() -> Result<()> {
+        let solver = LDLT::default();
+        test::simple_solve_par_test(&solver)
+    }
+}
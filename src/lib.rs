@@ -3,6 +3,28 @@
 
 mod traits;
 
+mod workspace;
+
+mod batch;
+
+#[cfg(feature = "num-traits")]
+mod scalar;
+
+#[cfg(any(feature = "klu", feature = "rlu", feature = "rsparse"))]
+mod det;
+
+#[cfg(any(feature = "klu", feature = "csparse", feature = "lufact"))]
+mod index;
+
+#[cfg(feature = "num-traits")]
+mod csc;
+
+#[cfg(any(feature = "rlu", feature = "rsparse", feature = "ldlt"))]
+mod triangular;
+
+#[cfg(any(feature = "rlu", feature = "rsparse", feature = "ldlt"))]
+mod selinv;
+
 #[cfg(feature = "gplu")]
 pub mod gplu;
 
@@ -24,10 +46,37 @@ pub mod basiclu;
 #[cfg(feature = "matrix")]
 pub mod matrix;
 
+#[cfg(feature = "powerflow")]
+pub mod powerflow;
+
 #[cfg(feature = "rlu")]
 pub mod rlu;
 
+#[cfg(feature = "ldlt")]
+pub mod ldlt;
+
+#[cfg(feature = "snlu")]
+pub mod snlu;
+
+#[cfg(feature = "num-traits")]
+pub mod rank;
+
+#[cfg(feature = "num-traits")]
+pub mod dmperm;
+
+#[cfg(feature = "num-traits")]
+pub mod lowrank;
+
+#[cfg(feature = "num-traits")]
+pub mod schur;
+
 #[cfg(test)]
 pub mod test;
 
+#[cfg(feature = "num-traits")]
+pub use scalar::*;
 pub use traits::*;
+pub use workspace::*;
+
+#[cfg(any(feature = "klu", feature = "csparse", feature = "lufact"))]
+pub use index::*;
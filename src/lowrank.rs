@@ -0,0 +1,370 @@
+//! Low-rank updates of factorized matrices.
+//!
+//! Solves with `A + U C V'` using the factors of `A` and the
+//! Sherman-Morrison-Woodbury formula
+//!
+//! ```txt
+//!     inv(A + U C V') = inv(A) - inv(A) U inv(I + C V' inv(A) U) C V' inv(A)
+//! ```
+//!
+//! which costs `k` solves with the factors of `A` for a rank `k` update.
+
+use crate::csc::compress;
+use crate::{FactorSolver, Scalar, SparseVec};
+use anyhow::{format_err, Result};
+use num_traits::PrimInt;
+
+/// Rank-`k` update `U C V'` of an `n x n` matrix.
+#[derive(Debug, Clone)]
+pub struct LowRank<S> {
+    /// The `k` columns of `U`.
+    pub u: Vec<SparseVec<S>>,
+    /// The `k x k` matrix `C` in column-major order.
+    pub c: Vec<S>,
+    /// The `k` columns of `V`.
+    pub v: Vec<SparseVec<S>>,
+}
+
+impl<S: Scalar> LowRank<S> {
+    /// Rank of the update.
+    pub fn k(&self) -> usize {
+        self.u.len()
+    }
+
+    /// Change of `delta` in the admittance of a branch between `i` and `j`,
+    /// i.e. `u = v = e_i - e_j` and `c = delta`.
+    pub fn branch(n: usize, i: usize, j: usize, delta: S) -> Self {
+        let e = SparseVec {
+            n,
+            indices: vec![i, j],
+            values: vec![S::one(), -S::one()],
+        };
+        Self {
+            u: vec![e.clone()],
+            c: vec![delta],
+            v: vec![e],
+        }
+    }
+}
+
+/// Factors of a matrix `A + U C V'`.
+///
+/// Updates with a rank up to `max_rank` are solved using the factors of
+/// `A`. Larger updates are applied to `A` and the result is refactorized.
+pub struct LowRankUpdated<F, S> {
+    n: usize,
+    factor: F,
+    woodbury: Option<Woodbury<S>>,
+}
+
+struct Woodbury<S> {
+    update: LowRank<S>,
+    /// `inv(A) U`, `n x k` column-major.
+    a_inv_u: Vec<S>,
+    /// `inv(A') V`, `n x k` column-major.
+    a_inv_t_v: Vec<S>,
+    /// `I + C V' inv(A) U`
+    m: DenseLU<S>,
+    /// `I + C' U' inv(A') V`
+    m_t: DenseLU<S>,
+}
+
+impl<F, S: Scalar> LowRankUpdated<F, S> {
+    /// Applies `update` to the matrix `A` with factors `f`. The
+    /// compressed-column arrays of `A` are only used if the rank of the
+    /// update exceeds `max_rank`, but the order `n` is taken from `a_p`,
+    /// so it must hold valid column pointers.
+    pub fn new<I, T>(
+        solver: &T,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[S],
+        f: F,
+        update: LowRank<S>,
+        max_rank: usize,
+    ) -> Result<Self>
+    where
+        I: PrimInt,
+        T: FactorSolver<I, S, F>,
+    {
+        let n = match a_p.len().checked_sub(1) {
+            Some(n) if a_p[0].is_zero() && a_p.windows(2).all(|p| p[0] <= p[1]) => n,
+            _ => return Err(format_err!("invalid column pointers")),
+        };
+        let nnz = a_p[n].to_usize().unwrap();
+        if a_i.len() < nnz || a_x.len() < nnz {
+            return Err(format_err!(
+                "a_i and a_x must have at least {} entries",
+                nnz
+            ));
+        }
+        let k = update.k();
+        if update.v.len() != k || update.c.len() != k * k {
+            return Err(format_err!(
+                "update must have {} columns of U and V and C must be {}x{}",
+                k,
+                k,
+                k
+            ));
+        }
+        if let Some(e) = update.u.iter().chain(&update.v).find(|e| e.n != n) {
+            return Err(format_err!("update column length {} must be {}", e.n, n));
+        }
+
+        if k > max_rank {
+            let (b_i, b_p, b_x) = add_low_rank(n, a_i, a_p, a_x, &update);
+            let factor = solver.factor(n, &b_i, &b_p, &b_x)?;
+            return Ok(Self {
+                n,
+                factor,
+                woodbury: None,
+            });
+        }
+
+        let a_inv_u = solve_columns(solver, &f, n, &update.u, false)?;
+        let a_inv_t_v = solve_columns(solver, &f, n, &update.v, true)?;
+
+        // m = I + C V' inv(A) U
+        let vt_a_inv_u = mul_transposed(&update.v, &a_inv_u, n);
+        let mut m = matmul(k, &update.c, &vt_a_inv_u, false);
+        // m_t = I + C' U' inv(A') V
+        let ut_a_inv_t_v = mul_transposed(&update.u, &a_inv_t_v, n);
+        let mut m_t = matmul(k, &update.c, &ut_a_inv_t_v, true);
+        for i in 0..k {
+            m[i * k + i] += S::one();
+            m_t[i * k + i] += S::one();
+        }
+
+        Ok(Self {
+            n,
+            factor: f,
+            woodbury: Some(Woodbury {
+                update,
+                a_inv_u,
+                a_inv_t_v,
+                m: DenseLU::new(k, m)?,
+                m_t: DenseLU::new(k, m_t)?,
+            }),
+        })
+    }
+
+    /// True if the update was applied by refactorization.
+    pub fn refactored(&self) -> bool {
+        self.woodbury.is_none()
+    }
+
+    /// Solve for one or more right-hand-sides.
+    pub fn solve<I, T>(&self, solver: &T, b: &mut [S], trans: bool) -> Result<()>
+    where
+        T: FactorSolver<I, S, F>,
+    {
+        let n = self.n;
+        if n == 0 || !b.len().is_multiple_of(n) {
+            return Err(format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                n
+            ));
+        }
+        for b in b.chunks_exact_mut(n) {
+            solver.solve(&self.factor, b, trans)?;
+
+            if let Some(w) = &self.woodbury {
+                let k = w.update.k();
+                let (v, m, a_inv_u) = if !trans {
+                    (&w.update.v, &w.m, &w.a_inv_u)
+                } else {
+                    (&w.update.u, &w.m_t, &w.a_inv_t_v)
+                };
+                // t = inv(M) C V' y
+                let vt_y: Vec<S> = v.iter().map(|v| dot(v, b)).collect();
+                let mut t = matmul(k, &w.update.c, &vt_y, trans);
+                m.solve(&mut t);
+                // x = y - inv(A) U t
+                for (j, &t_j) in t.iter().enumerate() {
+                    let col = &a_inv_u[j * n..(j + 1) * n];
+                    for (x, &a) in b.iter_mut().zip(col) {
+                        *x -= a * t_j;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Solves for each column of `u`, one at a time.
+fn solve_columns<I, S, F, T>(
+    solver: &T,
+    f: &F,
+    n: usize,
+    u: &[SparseVec<S>],
+    trans: bool,
+) -> Result<Vec<S>>
+where
+    S: Scalar,
+    T: FactorSolver<I, S, F>,
+{
+    let mut x = vec![S::zero(); n * u.len()];
+    for (u, x) in u.iter().zip(x.chunks_exact_mut(n)) {
+        for (&i, &v) in u.indices.iter().zip(&u.values) {
+            x[i] += v;
+        }
+        solver.solve(f, x, trans)?;
+    }
+    Ok(x)
+}
+
+fn dot<S: Scalar>(u: &SparseVec<S>, x: &[S]) -> S {
+    u.indices
+        .iter()
+        .zip(&u.values)
+        .fold(S::zero(), |s, (&i, &v)| s + v * x[i])
+}
+
+/// Returns `V' X` for sparse `V` and dense `X`, both with `k` columns.
+fn mul_transposed<S: Scalar>(v: &[SparseVec<S>], x: &[S], n: usize) -> Vec<S> {
+    let k = v.len();
+    let mut y = vec![S::zero(); k * k];
+    for j in 0..k {
+        for (i, v) in v.iter().enumerate() {
+            y[j * k + i] = dot(v, &x[j * n..(j + 1) * n]);
+        }
+    }
+    y
+}
+
+/// Returns `C X` or `C' X` for the `k x k` matrix `C` and `X` with `k` rows.
+fn matmul<S: Scalar>(k: usize, c: &[S], x: &[S], trans: bool) -> Vec<S> {
+    let mut y = vec![S::zero(); x.len()];
+    for (x, y) in x.chunks_exact(k).zip(y.chunks_exact_mut(k)) {
+        for j in 0..k {
+            for i in 0..k {
+                if !trans {
+                    y[i] += c[j * k + i] * x[j];
+                } else {
+                    y[j] += c[j * k + i] * x[i];
+                }
+            }
+        }
+    }
+    y
+}
+
+/// Returns the compressed-column arrays of `A + U C V'`.
+fn add_low_rank<I, S>(
+    n: usize,
+    a_i: &[I],
+    a_p: &[I],
+    a_x: &[S],
+    update: &LowRank<S>,
+) -> (Vec<I>, Vec<I>, Vec<S>)
+where
+    I: PrimInt,
+    S: Scalar,
+{
+    let k = update.k();
+    let (mut t_i, mut t_j, mut t_x) = (Vec::default(), Vec::default(), Vec::default());
+    for j in 0..n {
+        for p in a_p[j].to_usize().unwrap()..a_p[j + 1].to_usize().unwrap() {
+            t_i.push(a_i[p].to_usize().unwrap());
+            t_j.push(j);
+            t_x.push(a_x[p]);
+        }
+    }
+    for (l, v) in update.v.iter().enumerate() {
+        // Column l of U C.
+        for (m, u) in update.u.iter().enumerate() {
+            let c = update.c[l * k + m];
+            for (&i, &u_i) in u.indices.iter().zip(&u.values) {
+                for (&j, &v_j) in v.indices.iter().zip(&v.values) {
+                    t_i.push(i);
+                    t_j.push(j);
+                    t_x.push(u_i * c * v_j);
+                }
+            }
+        }
+    }
+    let (b_p, b_i, b_x) = compress(n, &t_i, &t_j, &t_x);
+
+    let b_i = b_i.iter().map(|&i| I::from(i).unwrap()).collect();
+    let b_p = b_p.iter().map(|&p| I::from(p).unwrap()).collect();
+    (b_i, b_p, b_x)
+}
+
+/// Dense LU factorization with partial pivoting.
+struct DenseLU<S> {
+    k: usize,
+    lu: Vec<S>,
+    piv: Vec<usize>,
+}
+
+impl<S: Scalar> DenseLU<S> {
+    fn new(k: usize, mut lu: Vec<S>) -> Result<Self> {
+        let mut piv = vec![0; k];
+        for j in 0..k {
+            let p = (j..k)
+                .max_by(|&a, &b| lu[j * k + a].norm().total_cmp(&lu[j * k + b].norm()))
+                .unwrap();
+            if lu[j * k + p].norm() == 0.0 {
+                return Err(format_err!("updated matrix is singular"));
+            }
+            piv[j] = p;
+            if p != j {
+                for c in 0..k {
+                    lu.swap(c * k + j, c * k + p);
+                }
+            }
+            let d = lu[j * k + j];
+            for i in j + 1..k {
+                lu[j * k + i] /= d;
+            }
+            for c in j + 1..k {
+                let x = lu[c * k + j];
+                for i in j + 1..k {
+                    let l = lu[j * k + i];
+                    lu[c * k + i] -= l * x;
+                }
+            }
+        }
+        Ok(Self { k, lu, piv })
+    }
+
+    fn solve(&self, x: &mut [S]) {
+        let k = self.k;
+        for j in 0..k {
+            x.swap(j, self.piv[j]);
+        }
+        for j in 0..k {
+            for i in j + 1..k {
+                let x_j = x[j];
+                x[i] -= self.lu[j * k + i] * x_j;
+            }
+        }
+        for j in (0..k).rev() {
+            x[j] /= self.lu[j * k + j];
+            for i in 0..j {
+                let x_j = x[j];
+                x[i] -= self.lu[j * k + i] * x_j;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DenseLU;
+
+    #[test]
+    fn test_dense_lu() {
+        // {1, 2}
+        // {3, 4}
+        let lu = DenseLU::new(2, vec![1.0, 3.0, 2.0, 4.0]).unwrap();
+        let mut x = vec![5.0, 11.0];
+        lu.solve(&mut x);
+        assert!(f64::abs(x[0] - 1.0) < 1e-14);
+        assert!(f64::abs(x[1] - 2.0) < 1e-14);
+
+        assert!(DenseLU::new(2, vec![1.0, 2.0, 2.0, 4.0]).is_err());
+    }
+}
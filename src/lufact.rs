@@ -1,7 +1,7 @@
-use crate::Solver;
+use crate::index::to_i32;
+use crate::{CIndex, Solver};
 use anyhow::{format_err, Result};
 use lufact::GP;
-use num_traits::ToPrimitive;
 use suitesparse_sys::{amd_defaults, amd_order, AMD_CONTROL, AMD_INFO, AMD_OK};
 
 /// Solver based on [AMD](https://crates.io/crates/amd_sys) and [LUFact](https://crates.io/crates/lufact).
@@ -25,7 +25,7 @@ impl Default for LUFact {
 
 impl<I> Solver<I, f64> for LUFact
 where
-    I: ToPrimitive,
+    I: CIndex,
 {
     fn solve(
         &self,
@@ -36,17 +36,26 @@ where
         b: &mut [f64],
         trans: bool,
     ) -> Result<()> {
-        let a_i: Vec<i32> = a_i.iter().map(|i| i.to_i32().unwrap()).collect();
-        let a_p: Vec<i32> = a_p.iter().map(|i| i.to_i32().unwrap()).collect();
+        if n == 0 || !b.len().is_multiple_of(n) {
+            return Err(format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                n
+            ));
+        }
+        let nrhs = to_i32(b.len() / n)?;
+        let n = to_i32(n)?;
+        let a_i = I::to_i32_slice(a_i)?;
+        let a_p = I::to_i32_slice(a_p)?;
 
         let mut gp = self.gp.clone();
         if gp.col_perm.is_none() {
-            let mut p = vec![0; n];
+            let mut p = vec![0; n as usize];
             let mut control = self.control.clone();
             let mut info = vec![0.0; AMD_INFO as usize];
             unsafe {
                 let rv = amd_order(
-                    n as i32,
+                    n,
                     a_p.as_ptr(),
                     a_i.as_ptr(),
                     p.as_mut_ptr(),
@@ -61,15 +70,16 @@ where
         }
 
         let mut a_desc = lufact::CSC {
-            m: n as i32,
-            n: n as i32,
-            nnz: a_x.len() as i32,
+            m: n,
+            n,
+            nnz: to_i32(a_x.len())?,
             base: 0,
-            colptr: a_p,
-            rowind: a_i,
+            // The descriptor owns its index arrays.
+            colptr: a_p.into_owned(),
+            rowind: a_i.into_owned(),
         };
 
-        let mut lu = match lufact::dgstrf(&gp, n as i32, n as i32, &a_x, &mut a_desc) {
+        let mut lu = match lufact::dgstrf(&gp, n, n, &a_x, &mut a_desc) {
             Ok(lu) => lu,
             Err(rv) => {
                 return Err(format_err!("dgstrf error: {}", rv));
@@ -79,8 +89,8 @@ where
         let rv = lufact::dgstrs(
             &gp,
             if trans { 'T' } else { 'N' },
-            n as i32,
-            (b.len() / n) as i32,
+            n,
+            nrhs,
             &mut lu,
             1,
             1,
@@ -0,0 +1,286 @@
+//! Binary compressed-column file format, used to cache matrices that are
+//! slow to parse from text.
+//!
+//! All values are little-endian. The file starts with a 32 byte header:
+//!
+//! | Bytes  | Contents                                     |
+//! |--------|----------------------------------------------|
+//! | 0..4   | Magic number `SPSC`                          |
+//! | 4      | Format version (1)                           |
+//! | 5      | Index width in bytes (4 or 8)                |
+//! | 6      | Scalar type (1: `f64`, 2: complex `f64`)     |
+//! | 7      | Reserved (0)                                 |
+//! | 8..32  | Rows, columns and non-zeros as `u64`         |
+//!
+//! It is followed by the column pointers, row indices and values, then
+//! a 64-bit FNV-1a checksum of all the preceding bytes.
+
+use super::mtx::{Field, Mtx, MtxScalar};
+use anyhow::{format_err, Result};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"SPSC";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 32;
+
+/// 64-bit FNV-1a hash.
+#[derive(Clone, Copy)]
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// Writer that hashes the bytes written.
+struct Hashed<W> {
+    w: W,
+    hash: Fnv,
+}
+
+impl<W: Write> Hashed<W> {
+    fn put(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.hash.update(bytes);
+        self.w.write_all(bytes)
+    }
+}
+
+fn scalar_type<S: MtxScalar>() -> u8 {
+    match S::FIELD {
+        Field::Complex => 2,
+        _ => 1,
+    }
+}
+
+/// Writes an `m x n` compressed-column matrix in binary format.
+pub fn write_csc<S: MtxScalar>(
+    path: impl AsRef<Path>,
+    m: usize,
+    n: usize,
+    a_p: &[usize],
+    a_i: &[usize],
+    a_x: &[S],
+) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    let mut w = BufWriter::new(file);
+    write_csc_to(&mut w, m, n, a_p, a_i, a_x)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes an `m x n` compressed-column matrix in binary format to `w`.
+pub fn write_csc_to<S: MtxScalar>(
+    w: impl Write,
+    m: usize,
+    n: usize,
+    a_p: &[usize],
+    a_i: &[usize],
+    a_x: &[S],
+) -> Result<()> {
+    if a_p.len() != n + 1 {
+        return Err(format_err!("a_p must have length {}", n + 1));
+    }
+    let nnz = a_p[n];
+    if a_i.len() < nnz || a_x.len() < nnz {
+        return Err(format_err!("a_i and a_x must have length {}", nnz));
+    }
+    let width: u8 = if usize::max(m, nnz) <= u32::MAX as usize {
+        4
+    } else {
+        8
+    };
+
+    let mut w = Hashed {
+        w,
+        hash: Fnv::new(),
+    };
+    w.put(MAGIC)?;
+    w.put(&[VERSION, width, scalar_type::<S>(), 0])?;
+    for d in [m, n, nnz] {
+        w.put(&(d as u64).to_le_bytes())?;
+    }
+    for &i in a_p.iter().chain(&a_i[..nnz]) {
+        if width == 4 {
+            w.put(&(i as u32).to_le_bytes())?;
+        } else {
+            w.put(&(i as u64).to_le_bytes())?;
+        }
+    }
+    for &x in &a_x[..nnz] {
+        let (re, im) = x.re_im();
+        w.put(&re.to_le_bytes())?;
+        if S::FIELD == Field::Complex {
+            w.put(&im.to_le_bytes())?;
+        }
+    }
+    let hash = w.hash.0;
+    w.w.write_all(&hash.to_le_bytes())?;
+    Ok(())
+}
+
+/// Reads a binary compressed-column matrix file.
+pub fn read_csc<S: MtxScalar>(path: impl AsRef<Path>) -> Result<Mtx<S>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    read_csc_from(file).map_err(|err| format_err!("{}: {}", path.display(), err))
+}
+
+/// Cursor over the bytes of a file.
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if len > self.0.len() {
+            return Err(format_err!("unexpected end of file"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn index(&mut self, width: u8) -> Result<usize> {
+        let i = if width == 4 {
+            u32::from_le_bytes(self.take(4)?.try_into().unwrap()) as u64
+        } else {
+            self.u64()?
+        };
+        usize::try_from(i).map_err(|_| format_err!("index {} out of range", i))
+    }
+}
+
+/// Reads a binary compressed-column matrix from `reader`.
+pub fn read_csc_from<S: MtxScalar>(mut reader: impl Read) -> Result<Mtx<S>> {
+    let mut data = Vec::default();
+    reader.read_to_end(&mut data)?;
+    if data.len() < HEADER_LEN + 8 || &data[..4] != MAGIC {
+        return Err(format_err!("not a binary CSC file"));
+    }
+    let (body, checksum) = data.split_at(data.len() - 8);
+    let mut hash = Fnv::new();
+    hash.update(body);
+    if hash.0.to_le_bytes() != checksum {
+        return Err(format_err!("checksum mismatch"));
+    }
+
+    let mut b = Bytes(&body[4..]);
+    let header = b.take(4)?;
+    let (version, width, scalar) = (header[0], header[1], header[2]);
+    if version != VERSION {
+        return Err(format_err!("unsupported version {}", version));
+    }
+    if width != 4 && width != 8 {
+        return Err(format_err!("invalid index width {}", width));
+    }
+    let field = match scalar {
+        1 => Field::Real,
+        2 => Field::Complex,
+        _ => return Err(format_err!("invalid scalar type {}", scalar)),
+    };
+    if field == Field::Complex && S::FIELD != Field::Complex {
+        return Err(format_err!("complex matrix read as real"));
+    }
+    let m = b.index(8)?;
+    let n = b.index(8)?;
+    let nnz = b.index(8)?;
+
+    let per = if field == Field::Complex { 16 } else { 8 };
+    let expected = n
+        .checked_add(1)
+        .and_then(|len| len.checked_add(nnz))
+        .and_then(|len| len.checked_mul(width as usize))
+        .and_then(|len| nnz.checked_mul(per).and_then(|x| len.checked_add(x)));
+    if expected != Some(b.0.len()) {
+        return Err(format_err!("file size does not match header"));
+    }
+
+    let a_p = (0..=n)
+        .map(|_| b.index(width))
+        .collect::<Result<Vec<_>>>()?;
+    if a_p[0] != 0 || a_p[n] != nnz || a_p.windows(2).any(|p| p[0] > p[1]) {
+        return Err(format_err!("invalid column pointers"));
+    }
+    let a_i = (0..nnz)
+        .map(|_| match b.index(width)? {
+            i if i < m => Ok(i),
+            i => Err(format_err!("row index {} out of range", i)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let a_x = (0..nnz)
+        .map(|_| {
+            let re = b.f64()?;
+            let im = if field == Field::Complex {
+                b.f64()?
+            } else {
+                0.0
+            };
+            Ok(S::from_re_im(re, im))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((m, n, a_p, a_i, a_x))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_csc_from, write_csc_to};
+    use anyhow::Result;
+    use num_complex::Complex64;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let a_p = vec![0, 2, 3, 3];
+        let a_i = vec![0, 1, 1];
+        let a_x = vec![1.5, -2.0, 1e-300];
+
+        let mut out = Vec::default();
+        write_csc_to(&mut out, 2, 3, &a_p, &a_i, &a_x)?;
+        assert_eq!(out.len(), 32 + 7 * 4 + 3 * 8 + 8);
+        assert_eq!(
+            read_csc_from::<f64>(out.as_slice())?,
+            (2, 3, a_p.clone(), a_i.clone(), a_x.clone())
+        );
+
+        // Real matrices can be read as complex, but not the reverse.
+        let (.., z_x) = read_csc_from::<Complex64>(out.as_slice())?;
+        assert_eq!(z_x[1], Complex64::new(-2.0, 0.0));
+
+        let z_x = vec![Complex64::new(1.0, 2.0); 3];
+        let mut out = Vec::default();
+        write_csc_to(&mut out, 2, 3, &a_p, &a_i, &z_x)?;
+        assert_eq!(read_csc_from::<Complex64>(out.as_slice())?.4, z_x);
+        assert!(read_csc_from::<f64>(out.as_slice()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt() -> Result<()> {
+        let mut out = Vec::default();
+        write_csc_to(&mut out, 1, 1, &[0, 1], &[0], &[2.0])?;
+        for k in [0, 5, 20, out.len() - 12, out.len() - 1] {
+            let mut bad = out.clone();
+            bad[k] ^= 1;
+            assert!(read_csc_from::<f64>(bad.as_slice()).is_err(), "byte {}", k);
+        }
+        assert!(read_csc_from::<f64>(&out[..out.len() - 1]).is_err());
+        Ok(())
+    }
+}
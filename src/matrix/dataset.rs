@@ -0,0 +1,291 @@
+use super::mtx::read_mtx_field;
+use super::{read_csc, read_mtx, write_csc, Field, Mtx, MtxScalar, Symmetry};
+use crate::csc::transpose;
+use anyhow::{format_err, Result};
+use num_complex::Complex64;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Square matrix `(n, a_p, a_i, a_x)` in compressed-column or
+/// compressed-row form.
+pub type Matrix<S> = (usize, Vec<usize>, Vec<usize>, Vec<S>);
+
+/// Kind of power system matrix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// DC power flow susceptance matrix, without the slack bus.
+    Bbus,
+    /// Bus admittance matrix.
+    Ybus,
+    /// AC power flow Jacobian.
+    Jac,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Bbus => "bbus",
+            Kind::Ybus => "ybus",
+            Kind::Jac => "jac",
+        })
+    }
+}
+
+/// Test matrix in the `matrix` directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dataset {
+    pub name: &'static str,
+    /// Subdirectory of `matrix` holding the data.
+    pub dir: &'static str,
+    pub kind: Kind,
+    /// Number of buses of the power system case.
+    pub buses: usize,
+    /// Number of rows and columns.
+    pub n: usize,
+    /// Number of entries, with symmetric storage expanded.
+    pub nnz: usize,
+    pub field: Field,
+    /// Storage scheme of the file.
+    pub symmetry: Symmetry,
+}
+
+const fn powers(
+    name: &'static str,
+    buses: usize,
+    kind: Kind,
+    n: usize,
+    nnz: usize,
+    symmetry: Symmetry,
+) -> Dataset {
+    let field = match kind {
+        Kind::Ybus => Field::Complex,
+        _ => Field::Real,
+    };
+    Dataset {
+        name,
+        dir: "powers",
+        kind,
+        buses,
+        n,
+        nnz,
+        field,
+        symmetry,
+    }
+}
+
+/// Registered test matrices, in order of increasing size.
+const DATASETS: &[Dataset] = {
+    use Kind::{Bbus, Jac, Ybus};
+    use Symmetry::{General, Symmetric};
+    &[
+        powers("ACTIVSg200_Bbus", 200, Bbus, 199, 683, General),
+        powers("ACTIVSg200_Ybus", 200, Ybus, 200, 690, Symmetric),
+        powers("ACTIVSg200_Jac", 200, Jac, 361, 2489, General),
+        powers("ACTIVSg500_Bbus", 500, Bbus, 499, 1662, General),
+        powers("ACTIVSg500_Ybus", 500, Ybus, 500, 1668, Symmetric),
+        powers("ACTIVSg500_Jac", 500, Jac, 943, 6275, General),
+        powers("ACTIVSg2000_Bbus", 2000, Bbus, 1999, 7329, General),
+        powers("ACTIVSg2000_Ybus", 2000, Ybus, 2000, 7334, Symmetric),
+        powers("ACTIVSg2000_Jac", 2000, Jac, 3607, 26345, General),
+        powers("ACTIVSg10k_Bbus", 10000, Bbus, 9999, 34429, General),
+        powers("ACTIVSg10k_Ybus", 10000, Ybus, 10000, 34434, General),
+        powers("ACTIVSg10k_Jac", 10000, Jac, 18544, 125174, General),
+        powers("ACTIVSg25k_Bbus", 25000, Bbus, 24999, 85213, General),
+        powers("ACTIVSg25k_Ybus", 25000, Ybus, 25000, 85220, Symmetric),
+    ]
+};
+
+impl Dataset {
+    /// Returns all registered test matrices.
+    pub fn all() -> &'static [Dataset] {
+        DATASETS
+    }
+
+    /// Returns the registered test matrices of the given kind.
+    pub fn of_kind(kind: Kind) -> impl Iterator<Item = &'static Dataset> {
+        DATASETS.iter().filter(move |d| d.kind == kind)
+    }
+
+    /// Looks up a test matrix by name, ignoring case.
+    pub fn get(name: &str) -> Result<&'static Dataset> {
+        DATASETS
+            .iter()
+            .find(|d| d.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| format_err!("unknown dataset {:?}", name))
+    }
+
+    /// Directory holding the data.
+    pub fn path(&self) -> PathBuf {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("matrix");
+        d.push(self.dir);
+        d
+    }
+
+    /// Reads the matrix in compressed-column (`csc`) or compressed-row
+    /// form as `(n, a_p, a_i, a_x)`.
+    pub fn read<S: MtxScalar>(&self, csc: bool) -> Result<Matrix<S>> {
+        let (m, n, a_p, a_i, a_x) = load(&self.path(), self.name)?;
+        if (m, n) != (self.n, self.n) {
+            return Err(format_err!(
+                "{}: expected {}x{} matrix, found {}x{}",
+                self.name,
+                self.n,
+                self.n,
+                m,
+                n
+            ));
+        }
+        if csc {
+            Ok((n, a_p, a_i, a_x))
+        } else {
+            let (a_p, a_i, a_x) = transpose(m, n, &a_p, &a_i, &a_x);
+            Ok((n, a_p, a_i, a_x))
+        }
+    }
+}
+
+impl fmt::Display for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// Loads `name` from `dir`, preferring a binary cache (`name.csc`) that is
+/// newer than the Matrix Market file (`name.mtx`, `name.mtx.zst` or
+/// `name.mtx.gz`). A cache that can't be read is ignored. The cache is
+/// written after parsing, if possible, in the field of the file rather
+/// than as `S`.
+fn load<S: MtxScalar>(dir: &Path, name: &str) -> Result<Mtx<S>> {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+
+    let cache = dir.join(name.to_string() + ".csc");
+    let source = ["mtx", "mtx.zst", "mtx.gz"]
+        .iter()
+        .map(|ext| dir.join(format!("{}.{}", name, ext)))
+        .find(|path| path.exists());
+
+    let cached = modified(&cache);
+    if cached.is_some() && cached >= source.as_deref().and_then(modified) {
+        if let Ok(a) = read_csc(&cache) {
+            return Ok(a);
+        }
+    }
+    let source =
+        source.ok_or_else(|| format_err!("{}: matrix not found", dir.join(name).display()))?;
+
+    match read_mtx_field(&source)? {
+        Field::Complex if S::FIELD != Field::Complex => Err(format_err!(
+            "{}: complex matrix read as real",
+            source.display()
+        )),
+        Field::Complex => parse::<Complex64>(&source, &cache).map(convert),
+        _ => parse::<f64>(&source, &cache).map(convert),
+    }
+}
+
+/// Parses `source` and writes it to `cache`.
+fn parse<S: MtxScalar>(source: &Path, cache: &Path) -> Result<Mtx<S>> {
+    let (m, n, a_p, a_i, a_x) = read_mtx(source)?;
+
+    // Write to a temporary file first, as other threads or processes may
+    // be reading the cache. A read-only source directory is not an error.
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let tmp = cache.with_extension(format!(
+        "csc.{}-{}.tmp",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    if write_csc(&tmp, m, n, &a_p, &a_i, &a_x).is_err() || fs::rename(&tmp, cache).is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok((m, n, a_p, a_i, a_x))
+}
+
+/// Converts the values of a real or complex matrix to `S`.
+fn convert<T: MtxScalar, S: MtxScalar>((m, n, a_p, a_i, a_x): Mtx<T>) -> Mtx<S> {
+    let a_x = a_x
+        .into_iter()
+        .map(|x| {
+            let (re, im) = x.re_im();
+            S::from_re_im(re, im)
+        })
+        .collect();
+    (m, n, a_p, a_i, a_x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load, Dataset, Kind};
+    use crate::matrix::read_mtx;
+    use anyhow::Result;
+    use num_complex::Complex64;
+    use std::fs;
+
+    #[test]
+    fn test_metadata() -> Result<()> {
+        for d in Dataset::all() {
+            let path = d.path().join(d.name.to_string() + ".mtx");
+            let (m, n, a_p, ..) = read_mtx::<Complex64>(&path)?;
+            assert_eq!((m, n, a_p[n]), (d.n, d.n, d.nnz), "{}", d);
+            match d.kind {
+                Kind::Bbus => assert_eq!(d.n, d.buses - 1, "{}", d),
+                Kind::Ybus => assert_eq!(d.n, d.buses, "{}", d),
+                Kind::Jac => assert!(d.n < 2 * d.buses, "{}", d),
+            }
+        }
+        assert_eq!(Dataset::of_kind(Kind::Ybus).count(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read() -> Result<()> {
+        let d = Dataset::get("activsg200_bbus")?;
+        let (n, a_p, a_i, a_x) = d.read::<f64>(true)?;
+        assert_eq!((n, a_p.len(), a_i.len(), a_x.len()), (199, 200, 683, 683));
+
+        let (_, r_p, ..) = d.read::<f64>(false)?;
+        assert_eq!(r_p[n], a_p[n]);
+
+        assert!(Dataset::get("ACTIVSg200_Ybus")?.read::<f64>(true).is_err());
+        assert!(Dataset::get("ACTIVSg70k_Bbus").is_err());
+
+        let missing = Dataset {
+            name: "ACTIVSg70k_Bbus",
+            ..*d
+        };
+        assert!(missing.read::<f64>(true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("spsolve-{}-cache", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let name = "ACTIVSg200_Bbus";
+        let d = Dataset::get(name)?;
+        fs::copy(
+            d.path().join(name.to_string() + ".mtx"),
+            dir.join(name.to_string() + ".mtx"),
+        )?;
+        let cache = dir.join(name.to_string() + ".csc");
+
+        // Reading as complex first must not stop the matrix being read as real.
+        let z = load::<Complex64>(&dir, name);
+        let x = load::<f64>(&dir, name);
+        let cached = cache.exists();
+
+        // A corrupt cache is parsed again.
+        fs::write(&cache, b"SPSC")?;
+        let y = load::<f64>(&dir, name);
+        fs::remove_dir_all(&dir)?;
+
+        let (z, x, y) = (z?, x?, y?);
+        assert!(cached);
+        assert!(z.4.iter().zip(&x.4).all(|(z, &x)| *z == Complex64::from(x)));
+        assert_eq!(x, y);
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::{format_err, Result};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Opens a file for reading, decompressing `.gz` and `.zst` files
+/// transparently.
+pub(super) fn open(path: &Path) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => gz(file),
+        Some("zst") => zst(file),
+        _ => Ok(Box::new(BufReader::new(file))),
+    }
+}
+
+#[cfg(feature = "gz")]
+fn gz(file: File) -> Result<Box<dyn BufRead>> {
+    let decoder = flate2::read::MultiGzDecoder::new(BufReader::new(file));
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+#[cfg(not(feature = "gz"))]
+fn gz(_file: File) -> Result<Box<dyn BufRead>> {
+    Err(format_err!("reading .gz files requires the \"gz\" feature"))
+}
+
+#[cfg(feature = "zst")]
+fn zst(file: File) -> Result<Box<dyn BufRead>> {
+    let decoder = zstd::stream::read::Decoder::new(file)?;
+    Ok(Box::new(BufReader::new(decoder)))
+}
+
+#[cfg(not(feature = "zst"))]
+fn zst(_file: File) -> Result<Box<dyn BufRead>> {
+    Err(format_err!(
+        "reading .zst files requires the \"zst\" feature"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::read_mtx;
+    use anyhow::Result;
+    use std::fs;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    const DATA: &str = "%%MatrixMarket matrix coordinate real general\n2 2 2\n1 1 1.5\n2 1 -2\n";
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("spsolve-{}-{}", std::process::id(), name))
+    }
+
+    #[cfg(any(feature = "gz", feature = "zst"))]
+    fn check(path: PathBuf) -> Result<()> {
+        let a = read_mtx::<f64>(&path);
+        fs::remove_file(&path)?;
+        assert_eq!(a?, (2, 2, vec![0, 2, 2], vec![0, 1], vec![1.5, -2.0]));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "gz")]
+    fn test_gz() -> Result<()> {
+        let path = temp_path("test.mtx.gz");
+        let mut w = flate2::write::GzEncoder::new(fs::File::create(&path)?, Default::default());
+        w.write_all(DATA.as_bytes())?;
+        w.finish()?;
+        check(path)
+    }
+
+    #[test]
+    #[cfg(feature = "zst")]
+    fn test_zst() -> Result<()> {
+        let path = temp_path("test.mtx.zst");
+        let mut w = zstd::stream::write::Encoder::new(fs::File::create(&path)?, 0)?;
+        w.write_all(DATA.as_bytes())?;
+        w.finish()?;
+        check(path)
+    }
+
+    #[test]
+    #[cfg(not(feature = "gz"))]
+    fn test_gz_disabled() -> Result<()> {
+        let path = temp_path("disabled.mtx.gz");
+        fs::File::create(&path)?.write_all(DATA.as_bytes())?;
+        let a = read_mtx::<f64>(&path);
+        fs::remove_file(&path)?;
+        assert!(a.is_err());
+        Ok(())
+    }
+}
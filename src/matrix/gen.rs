@@ -0,0 +1,365 @@
+//! Synthetic test matrices, parameterised by size and seed.
+//!
+//! All generators return a square matrix `(n, a_p, a_i, a_x)` in
+//! compressed-column form with sorted row indices. The same seed always
+//! produces the same matrix.
+
+use super::Matrix;
+use crate::csc::compress;
+use num_complex::Complex64;
+
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c) pseudo-random
+/// number generator.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `[lo, hi)`.
+    fn range(&mut self, lo: f64, hi: f64) -> f64 {
+        lo + (hi - lo) * self.next_f64()
+    }
+
+    /// Uniform in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_f64() * n as f64) as usize
+    }
+}
+
+/// Triplets of a matrix under construction.
+#[derive(Default)]
+struct Triplets<S> {
+    t_i: Vec<usize>,
+    t_j: Vec<usize>,
+    t_x: Vec<S>,
+}
+
+impl<S: super::MtxScalar> Triplets<S> {
+    fn push(&mut self, i: usize, j: usize, x: S) {
+        self.t_i.push(i);
+        self.t_j.push(j);
+        self.t_x.push(x);
+    }
+
+    fn compress(&self, n: usize) -> Matrix<S> {
+        let (a_p, a_i, a_x) = compress(n, &self.t_i, &self.t_j, &self.t_x);
+        (n, a_p, a_i, a_x)
+    }
+}
+
+/// Five-point finite difference Laplacian on an `nx x ny` grid, with
+/// Dirichlet boundaries. Symmetric positive definite of order `nx * ny`.
+pub fn laplacian_2d(nx: usize, ny: usize) -> Matrix<f64> {
+    laplacian_3d(nx, ny, 1)
+}
+
+/// Seven-point finite difference Laplacian on an `nx x ny x nz` grid,
+/// with Dirichlet boundaries. Symmetric positive definite of order
+/// `nx * ny * nz`.
+pub fn laplacian_3d(nx: usize, ny: usize, nz: usize) -> Matrix<f64> {
+    let n = nx * ny * nz;
+    let diag = if nz > 1 { 6.0 } else { 4.0 };
+    let index = |x: usize, y: usize, z: usize| x + nx * (y + ny * z);
+
+    let mut t = Triplets::default();
+    for z in 0..nz {
+        for y in 0..ny {
+            for x in 0..nx {
+                let k = index(x, y, z);
+                t.push(k, k, diag);
+                let mut link = |l: usize| {
+                    t.push(k, l, -1.0);
+                    t.push(l, k, -1.0);
+                };
+                if x + 1 < nx {
+                    link(index(x + 1, y, z));
+                }
+                if y + 1 < ny {
+                    link(index(x, y + 1, z));
+                }
+                if z + 1 < nz {
+                    link(index(x, y, z + 1));
+                }
+            }
+        }
+    }
+    t.compress(n)
+}
+
+/// Adds `per_col` random off-diagonal entries in `(-1, 1)` to each column
+/// and returns the sum of the magnitudes in each row.
+fn random_off_diagonal(t: &mut Triplets<f64>, n: usize, per_col: usize, rng: &mut Rng) -> Vec<f64> {
+    let mut row_sum = vec![0.0; n];
+    if n < 2 {
+        return row_sum;
+    }
+    for j in 0..n {
+        for _ in 0..per_col {
+            // Any row but j.
+            let i = (j + 1 + rng.below(n - 1)) % n;
+            let x = rng.range(-1.0, 1.0);
+            t.push(i, j, x);
+            row_sum[i] += f64::abs(x);
+        }
+    }
+    row_sum
+}
+
+/// Random unsymmetric matrix of order `n` with about `per_col`
+/// off-diagonal entries per column, that is strictly diagonally dominant
+/// by rows and has a positive diagonal.
+pub fn random_diagonally_dominant(n: usize, per_col: usize, seed: u64) -> Matrix<f64> {
+    let mut rng = Rng::new(seed);
+    let mut t = Triplets::default();
+    let row_sum = random_off_diagonal(&mut t, n, per_col, &mut rng);
+    for (i, s) in row_sum.into_iter().enumerate() {
+        t.push(i, i, s + rng.range(0.5, 1.5));
+    }
+    t.compress(n)
+}
+
+/// Random unsymmetric matrix of order `n` with about `per_col`
+/// off-diagonal entries per column, and an infinity-norm condition number
+/// between `cond / 3` and `3 * cond`.
+///
+/// The matrix is `D M`, where `M` has a unit diagonal and off-diagonal
+/// row sums of at most one half (so `cond(M) <= 3`) and `D` is a diagonal
+/// scaling with entries geometrically spaced between `1 / cond` and one.
+pub fn random_conditioned(n: usize, per_col: usize, cond: f64, seed: u64) -> Matrix<f64> {
+    assert!(cond >= 1.0, "condition number must be at least one");
+    let mut rng = Rng::new(seed);
+    let mut m = Triplets::default();
+    let row_sum = random_off_diagonal(&mut m, n, per_col, &mut rng);
+
+    // Random order of the row scale factors.
+    let mut d: Vec<f64> = (0..n)
+        .map(|k| match n {
+            1 => 1.0,
+            _ => cond.powf(-(k as f64) / (n - 1) as f64),
+        })
+        .collect();
+    for k in (1..n).rev() {
+        d.swap(k, rng.below(k + 1));
+    }
+
+    let mut t = Triplets::default();
+    for ((&i, &j), &x) in m.t_i.iter().zip(&m.t_j).zip(&m.t_x) {
+        t.push(i, j, d[i] * 0.5 * x / row_sum[i]);
+    }
+    for (i, &d_i) in d.iter().enumerate() {
+        t.push(i, i, d_i);
+    }
+    t.compress(n)
+}
+
+/// Branches of a synthetic meshed grid with `nx x ny` buses.
+///
+/// Buses are connected to their neighbours on the grid, with an extra
+/// `chords` random branches between any two buses. Each branch has a
+/// random resistance, reactance and line charging.
+struct Grid {
+    n: usize,
+    branches: Vec<(usize, usize, Complex64, f64)>,
+}
+
+impl Grid {
+    fn new(nx: usize, ny: usize, chords: usize, seed: u64) -> Self {
+        let n = nx * ny;
+        let mut rng = Rng::new(seed);
+        let mut branches = Vec::default();
+        for y in 0..ny {
+            for x in 0..nx {
+                let k = x + nx * y;
+                if x + 1 < nx {
+                    branches.push(Self::branch(&mut rng, k, k + 1));
+                }
+                if y + 1 < ny {
+                    branches.push(Self::branch(&mut rng, k, k + nx));
+                }
+            }
+        }
+        if n >= 2 {
+            for _ in 0..chords {
+                let f = rng.below(n);
+                let t = (f + 1 + rng.below(n - 1)) % n;
+                branches.push(Self::branch(&mut rng, f, t));
+            }
+        }
+        Self { n, branches }
+    }
+
+    /// Branch from bus `f` to bus `t` with random series admittance and
+    /// line charging susceptance.
+    fn branch(rng: &mut Rng, f: usize, t: usize) -> (usize, usize, Complex64, f64) {
+        let x = rng.range(0.01, 0.2);
+        let r = x * rng.range(0.05, 0.3);
+        let b = x * rng.range(0.0, 0.5);
+        (f, t, Complex64::new(r, x).inv(), b)
+    }
+}
+
+/// Bus admittance matrix of a synthetic meshed grid of `nx x ny` buses,
+/// with `chords` extra random branches. Complex symmetric of order
+/// `nx * ny`.
+pub fn grid_ybus(nx: usize, ny: usize, chords: usize, seed: u64) -> Matrix<Complex64> {
+    let grid = Grid::new(nx, ny, chords, seed);
+    let mut t = Triplets::default();
+    for &(f, to, y, b) in &grid.branches {
+        let y_sh = y + Complex64::new(0.0, b / 2.0);
+        t.push(f, f, y_sh);
+        t.push(to, to, y_sh);
+        t.push(f, to, -y);
+        t.push(to, f, -y);
+    }
+    t.compress(grid.n)
+}
+
+/// DC power flow susceptance matrix of the same grid as [`grid_ybus`],
+/// without the first (slack) bus. Symmetric positive definite of order
+/// `nx * ny - 1`.
+pub fn grid_bbus(nx: usize, ny: usize, chords: usize, seed: u64) -> Matrix<f64> {
+    let grid = Grid::new(nx, ny, chords, seed);
+    let n = grid.n.saturating_sub(1);
+    let mut t = Triplets::default();
+    for &(f, to, y, _) in &grid.branches {
+        let b = 1.0 / y.inv().im;
+        if f > 0 {
+            t.push(f - 1, f - 1, b);
+        }
+        if to > 0 {
+            t.push(to - 1, to - 1, b);
+        }
+        if f > 0 && to > 0 {
+            t.push(f - 1, to - 1, -b);
+            t.push(to - 1, f - 1, -b);
+        }
+    }
+    t.compress(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Dense copy of a compressed-column matrix.
+    fn dense(a: &Matrix<f64>) -> Vec<Vec<f64>> {
+        let (n, a_p, a_i, a_x) = a;
+        let mut d = vec![vec![0.0; *n]; *n];
+        for j in 0..*n {
+            for p in a_p[j]..a_p[j + 1] {
+                d[a_i[p]][j] += a_x[p];
+            }
+        }
+        d
+    }
+
+    fn norm_inf(a: &[Vec<f64>]) -> f64 {
+        a.iter()
+            .map(|r| r.iter().map(|x| x.abs()).sum())
+            .fold(0.0, f64::max)
+    }
+
+    /// Inverse by Gauss-Jordan elimination with partial pivoting.
+    fn inverse(mut a: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+        let n = a.len();
+        let mut z: Vec<Vec<f64>> = (0..n)
+            .map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect())
+            .collect();
+        for k in 0..n {
+            let p = (k..n)
+                .max_by(|&p, &q| a[p][k].abs().total_cmp(&a[q][k].abs()))
+                .unwrap();
+            a.swap(k, p);
+            z.swap(k, p);
+            for i in 0..n {
+                if i != k {
+                    let l = a[i][k] / a[k][k];
+                    for j in 0..n {
+                        a[i][j] -= l * a[k][j];
+                        z[i][j] -= l * z[k][j];
+                    }
+                }
+            }
+        }
+        for k in 0..n {
+            let d = a[k][k];
+            z[k].iter_mut().for_each(|z| *z /= d);
+        }
+        z
+    }
+
+    fn is_symmetric<S: PartialEq + Copy>(a: &Matrix<S>) -> bool {
+        let (n, a_p, a_i, a_x) = a;
+        (0..*n).all(|j| {
+            (a_p[j]..a_p[j + 1]).all(|p| {
+                let i = a_i[p];
+                let q = a_i[a_p[i]..a_p[i + 1]].binary_search(&j);
+                q.is_ok_and(|q| a_x[a_p[i] + q] == a_x[p])
+            })
+        })
+    }
+
+    #[test]
+    fn test_laplacian() {
+        let a = laplacian_2d(4, 3);
+        assert_eq!(a.0, 12);
+        assert_eq!(a.1[12], 5 * 12 - 2 * 4 - 2 * 3);
+        assert!(is_symmetric(&a));
+
+        let a = laplacian_3d(3, 3, 3);
+        assert_eq!(a.1[27], 7 * 27 - 6 * 9);
+        assert!(is_symmetric(&a));
+        // Row sums are zero away from the boundary.
+        let d = dense(&a);
+        assert_eq!(d[13].iter().sum::<f64>(), 0.0);
+    }
+
+    #[test]
+    fn test_random() {
+        let a = random_diagonally_dominant(50, 3, 7);
+        assert_eq!(a, random_diagonally_dominant(50, 3, 7));
+        assert_ne!(a, random_diagonally_dominant(50, 3, 8));
+        for (i, row) in dense(&a).iter().enumerate() {
+            let off: f64 = row.iter().map(|x| x.abs()).sum::<f64>() - row[i];
+            assert!(row[i] > off);
+        }
+
+        for cond in [1.0, 1e3, 1e8] {
+            let a = dense(&random_conditioned(40, 4, cond, 1));
+            let k = norm_inf(&a) * norm_inf(&inverse(a));
+            assert!(k >= cond / 3.0 && k <= 3.0 * cond, "{} {}", cond, k);
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let y = grid_ybus(5, 4, 3, 11);
+        assert_eq!(y.0, 20);
+        assert!(is_symmetric(&y));
+        // 31 grid branches and up to 3 chords.
+        assert!(y.1[20] >= 20 + 2 * 31);
+
+        let b = grid_bbus(5, 4, 3, 11);
+        assert_eq!(b.0, 19);
+        assert!(is_symmetric(&b));
+        for (i, row) in dense(&b).iter().enumerate() {
+            let off: f64 = row.iter().map(|x| x.abs()).sum::<f64>() - row[i];
+            assert!(row[i] >= off - 1e-12);
+        }
+    }
+}
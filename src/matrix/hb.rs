@@ -0,0 +1,587 @@
+//! Reading and writing of Harwell-Boeing and
+//! [Rutherford-Boeing](https://math.nist.gov/MatrixMarket/formats.html#hb)
+//! files.
+//!
+//! Both formats store the matrix in compressed-column form with Fortran
+//! fixed-width fields. Harwell-Boeing files may also hold dense
+//! right-hand-side vectors. Symmetric, skew-symmetric and Hermitian
+//! matrices are expanded to both triangles. Elemental matrices and sparse
+//! right-hand-sides are not supported.
+
+use super::file::open;
+use super::mtx::{Field, MtxScalar};
+use crate::csc::compress;
+use anyhow::{format_err, Result};
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::path::Path;
+
+/// Matrix read from or written to a Harwell-Boeing or Rutherford-Boeing file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HBMatrix<S> {
+    /// Title, up to 72 characters.
+    pub title: String,
+    /// Key, up to 8 characters.
+    pub key: String,
+    /// Number of rows.
+    pub m: usize,
+    /// Number of columns.
+    pub n: usize,
+    pub a_p: Vec<usize>,
+    pub a_i: Vec<usize>,
+    pub a_x: Vec<S>,
+    /// Dense right-hand-sides, `m x nrhs` column-major. Always empty for
+    /// Rutherford-Boeing files.
+    pub rhs: Vec<S>,
+}
+
+impl<S> HBMatrix<S> {
+    /// Number of right-hand-side vectors.
+    pub fn nrhs(&self) -> usize {
+        self.rhs.len().checked_div(self.m).unwrap_or(0)
+    }
+}
+
+/// Reads a Harwell-Boeing file (`.rua`, `.rsa`, `.cua`, ...), which may be
+/// compressed (`.gz` or `.zst`).
+pub fn read_hb<S: MtxScalar>(path: impl AsRef<Path>) -> Result<HBMatrix<S>> {
+    let path = path.as_ref();
+    read_hb_from(open(path)?).map_err(|err| format_err!("{}: {}", path.display(), err))
+}
+
+/// Reads a Harwell-Boeing file from `reader`.
+pub fn read_hb_from<S: MtxScalar>(reader: impl BufRead) -> Result<HBMatrix<S>> {
+    read(reader, false)
+}
+
+/// Reads a Rutherford-Boeing file (`.rb`), which may be compressed.
+pub fn read_rb<S: MtxScalar>(path: impl AsRef<Path>) -> Result<HBMatrix<S>> {
+    let path = path.as_ref();
+    read_rb_from(open(path)?).map_err(|err| format_err!("{}: {}", path.display(), err))
+}
+
+/// Reads a Rutherford-Boeing file from `reader`.
+pub fn read_rb_from<S: MtxScalar>(reader: impl BufRead) -> Result<HBMatrix<S>> {
+    read(reader, true)
+}
+
+/// Fortran edit descriptor, such as `(10I8)` or `(1P,4E20.12)`.
+#[derive(Debug, Clone, Copy)]
+struct Format {
+    /// Fields per line.
+    count: usize,
+    /// Characters per field.
+    width: usize,
+}
+
+impl Format {
+    fn parse(s: &str) -> Result<Self> {
+        let invalid = || format_err!("unsupported format {:?}", s);
+        let mut f = s.trim().trim_start_matches('(').trim_end_matches(')');
+        f = f.trim();
+        // Drop a scale factor, such as "1P,".
+        if let Some(p) = f.find(['P', 'p']) {
+            if f[..p].chars().all(|c| c.is_ascii_digit()) {
+                f = f[p + 1..].trim_start_matches(',').trim();
+            }
+        }
+        let letter = f
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or_else(invalid)?;
+        let count = if letter == 0 {
+            1
+        } else {
+            f[..letter].parse().map_err(|_| invalid())?
+        };
+        let rest = &f[letter + 1..];
+        let width = rest.split('.').next().unwrap_or_default();
+        let width = width.parse().map_err(|_| invalid())?;
+        if count == 0 || width == 0 {
+            return Err(invalid());
+        }
+        Ok(Self { count, width })
+    }
+}
+
+/// Reader of fixed-width fields, one section of the file at a time.
+struct Fields<L> {
+    lines: L,
+    line: usize,
+}
+
+impl<L: Iterator<Item = std::io::Result<String>>> Fields<L> {
+    fn next_line(&mut self) -> Result<String> {
+        self.line += 1;
+        match self.lines.next() {
+            Some(line) => Ok(line?),
+            None => Err(format_err!("line {}: unexpected end of file", self.line)),
+        }
+    }
+
+    /// Reads `len` fields, starting on a new line.
+    fn read(&mut self, len: usize, format: Format) -> Result<Vec<String>> {
+        let mut fields = Vec::with_capacity(len);
+        while fields.len() < len {
+            let line = self.next_line()?;
+            let line = line.trim_end();
+            if !line.is_ascii() {
+                return Err(format_err!("line {}: non-ASCII characters", self.line));
+            }
+            let before = fields.len();
+            for k in 0..format.count {
+                let start = k * format.width;
+                if start >= line.len() || fields.len() == len {
+                    break;
+                }
+                let end = usize::min(start + format.width, line.len());
+                let field = line[start..end].trim();
+                if !field.is_empty() {
+                    fields.push(field.to_string());
+                }
+            }
+            if fields.len() == before {
+                return Err(format_err!("line {}: expected data", self.line));
+            }
+        }
+        Ok(fields)
+    }
+
+    fn read_indices(&mut self, len: usize, format: Format, max: usize) -> Result<Vec<usize>> {
+        let line = self.line + 1;
+        self.read(len, format)?
+            .iter()
+            .map(|s| match s.parse::<usize>() {
+                Ok(i) if i >= 1 && i <= max => Ok(i - 1),
+                _ => Err(format_err!("line {}: invalid index {:?}", line, s)),
+            })
+            .collect()
+    }
+
+    fn read_values<S: MtxScalar>(
+        &mut self,
+        len: usize,
+        format: Format,
+        field: Field,
+    ) -> Result<Vec<S>> {
+        let line = self.line + 1;
+        let per = if field == Field::Complex { 2 } else { 1 };
+        let values: Vec<String> = self
+            .read(len * per, format)?
+            .iter()
+            .map(|s| fortran_float(s))
+            .collect();
+        values
+            .chunks_exact(per)
+            .map(|v| {
+                let v: Vec<&str> = v.iter().map(String::as_str).collect();
+                S::parse(field, &v).map_err(|err| format_err!("line {}: {}", line, err))
+            })
+            .collect()
+    }
+}
+
+/// Converts a Fortran real, such as `1.5D+03` or `1.5+003`, for parsing.
+fn fortran_float(s: &str) -> String {
+    let s = s.replace(['D', 'd'], "E");
+    // An exponent sign without a letter.
+    match s.rfind(['+', '-']) {
+        Some(k) if k > 0 && !s[..k].ends_with(['E', 'e']) => format!("{}E{}", &s[..k], &s[k..]),
+        _ => s,
+    }
+}
+
+fn header_ints(line: &str, len: usize, min: usize) -> Result<Vec<usize>> {
+    let ints = line
+        .split_whitespace()
+        .take(len)
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format_err!("invalid header line {:?}", line))?;
+    if ints.len() < min {
+        return Err(format_err!("invalid header line {:?}", line));
+    }
+    Ok(ints)
+}
+
+fn read<S: MtxScalar>(reader: impl BufRead, rb: bool) -> Result<HBMatrix<S>> {
+    let mut f = Fields {
+        lines: reader.lines(),
+        line: 0,
+    };
+
+    let line = f.next_line()?;
+    let (title, key) = line.split_at(usize::min(72, line.len()));
+    let (title, key) = (title.trim().to_string(), key.trim().to_string());
+
+    let cards = header_ints(&f.next_line()?, if rb { 4 } else { 5 }, 4)?;
+    let rhs_cards = if rb {
+        0
+    } else {
+        cards.get(4).copied().unwrap_or(0)
+    };
+
+    let line = f.next_line()?;
+    if line.len() < 3 {
+        return Err(format_err!("line 3: missing matrix type"));
+    }
+    let mxtype = line[..3].to_uppercase();
+    let size = header_ints(&line[3..], 4, 3)?;
+    let (m, n, nnz) = (size[0], size[1], size[2]);
+
+    let field = match &mxtype[..1] {
+        "R" | "I" => Field::Real,
+        "C" => Field::Complex,
+        "P" | "Q" => Field::Pattern,
+        _ => return Err(format_err!("unsupported matrix type {:?}", mxtype)),
+    };
+    let symmetry = &mxtype[1..2];
+    if !matches!(symmetry, "U" | "R" | "S" | "Z" | "H") {
+        return Err(format_err!("unsupported matrix type {:?}", mxtype));
+    }
+    if &mxtype[2..] != "A" {
+        return Err(format_err!("elemental matrices are not supported"));
+    }
+    if symmetry != "U" && symmetry != "R" && m != n {
+        return Err(format_err!("{} matrix must be square", mxtype));
+    }
+
+    let formats: Vec<String> = f
+        .next_line()?
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+    if formats.len() < 2 || (field != Field::Pattern && formats.len() < 3) {
+        return Err(format_err!("line 4: missing formats"));
+    }
+    let ptr_fmt = Format::parse(&formats[0])?;
+    let ind_fmt = Format::parse(&formats[1])?;
+
+    let mut nrhs = 0;
+    let mut rhs_fmt = None;
+    if rhs_cards > 0 {
+        let line = f.next_line()?;
+        if !line.to_uppercase().starts_with('F') {
+            return Err(format_err!("only full right-hand-sides are supported"));
+        }
+        nrhs = header_ints(line.get(3..).unwrap_or_default(), 1, 1)?[0];
+        let fmt = formats
+            .get(3)
+            .ok_or_else(|| format_err!("line 4: missing right-hand-side format"))?;
+        rhs_fmt = Some(Format::parse(fmt)?);
+    }
+
+    let a_p = f.read_indices(n + 1, ptr_fmt, nnz + 1)?;
+    if a_p[0] != 0 || a_p[n] != nnz || a_p.windows(2).any(|p| p[0] > p[1]) {
+        return Err(format_err!("invalid column pointers"));
+    }
+    let a_i = f.read_indices(nnz, ind_fmt, m)?;
+    let a_x: Vec<S> = if field == Field::Pattern {
+        (0..nnz)
+            .map(|_| S::parse(Field::Pattern, &[]))
+            .collect::<Result<_>>()?
+    } else {
+        f.read_values(nnz, Format::parse(&formats[2])?, field)?
+    };
+    let rhs = match rhs_fmt {
+        Some(fmt) => f.read_values(m * nrhs, fmt, field)?,
+        None => Vec::default(),
+    };
+
+    // Expand the stored triangle.
+    let (a_p, a_i, a_x) = if symmetry == "U" || symmetry == "R" {
+        (a_p, a_i, a_x)
+    } else {
+        let mut t_i = Vec::with_capacity(2 * nnz);
+        let mut t_j = Vec::with_capacity(2 * nnz);
+        let mut t_x = Vec::with_capacity(2 * nnz);
+        for j in 0..n {
+            for p in a_p[j]..a_p[j + 1] {
+                let (i, x) = (a_i[p], a_x[p]);
+                t_i.push(i);
+                t_j.push(j);
+                t_x.push(x);
+                if i != j {
+                    t_i.push(j);
+                    t_j.push(i);
+                    t_x.push(match symmetry {
+                        "S" => x,
+                        "Z" => -x,
+                        _ => x.conj(),
+                    });
+                }
+            }
+        }
+        compress(n, &t_i, &t_j, &t_x)
+    };
+
+    Ok(HBMatrix {
+        title,
+        key,
+        m,
+        n,
+        a_p,
+        a_i,
+        a_x,
+        rhs,
+    })
+}
+
+/// Writes a Harwell-Boeing file in unsymmetric (or rectangular) assembled
+/// format, including the right-hand-sides.
+pub fn write_hb<S: MtxScalar>(path: impl AsRef<Path>, a: &HBMatrix<S>) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    let mut w = BufWriter::new(file);
+    write(&mut w, a, false)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes a Harwell-Boeing file to `w`.
+pub fn write_hb_to<S: MtxScalar>(w: impl Write, a: &HBMatrix<S>) -> Result<()> {
+    write(w, a, false)
+}
+
+/// Writes a Rutherford-Boeing file in unsymmetric (or rectangular)
+/// assembled format. Right-hand-sides are not written.
+pub fn write_rb<S: MtxScalar>(path: impl AsRef<Path>, a: &HBMatrix<S>) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    let mut w = BufWriter::new(file);
+    write(&mut w, a, true)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes a Rutherford-Boeing file to `w`.
+pub fn write_rb_to<S: MtxScalar>(w: impl Write, a: &HBMatrix<S>) -> Result<()> {
+    write(w, a, true)
+}
+
+/// Values are written with 17 significant digits, so that they round-trip.
+const VALUE_FORMAT: Format = Format {
+    count: 3,
+    width: 25,
+};
+
+fn write_float(w: &mut impl Write, x: f64) -> std::io::Result<()> {
+    let s = format!("{:.16E}", x);
+    let s = match s.split_once('E') {
+        Some((mantissa, exp)) => {
+            let exp: i32 = exp.parse().unwrap();
+            format!(
+                "{}E{}{:02}",
+                mantissa,
+                if exp < 0 { '-' } else { '+' },
+                exp.abs()
+            )
+        }
+        None => s, // inf or NaN
+    };
+    write!(w, "{:>25}", s)
+}
+
+fn write_fields<T>(
+    w: &mut impl Write,
+    items: &[T],
+    format: Format,
+    mut f: impl FnMut(&mut dyn Write, &T) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    for line in items.chunks(format.count) {
+        for item in line {
+            f(w, item)?;
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn cards(len: usize, format: Format) -> usize {
+    len.div_ceil(format.count)
+}
+
+fn write<S: MtxScalar>(mut w: impl Write, a: &HBMatrix<S>, rb: bool) -> Result<()> {
+    let (m, n) = (a.m, a.n);
+    if a.a_p.len() != n + 1 {
+        return Err(format_err!("a_p must have length {}", n + 1));
+    }
+    let nnz = a.a_p[n];
+    if a.a_i.len() < nnz || a.a_x.len() < nnz {
+        return Err(format_err!("a_i and a_x must have length {}", nnz));
+    }
+    if let Some(i) = a.a_i[..nnz].iter().find(|&&i| i >= m) {
+        return Err(format_err!("row index {} out of range", i));
+    }
+    let nrhs = if rb { 0 } else { a.nrhs() };
+    if !rb && a.rhs.len() != m * nrhs {
+        return Err(format_err!("rhs length must be a multiple of {}", m));
+    }
+
+    let digits = (usize::max(nnz + 1, m).max(1) as f64).log10().floor() as usize + 1;
+    let int_format = Format {
+        count: 80 / (digits + 1),
+        width: digits + 1,
+    };
+    let (field, per) = match S::FIELD {
+        Field::Complex => ('C', 2),
+        _ => ('R', 1),
+    };
+    let values = |x: &[S]| -> Vec<f64> {
+        x.iter()
+            .flat_map(|x| {
+                let (re, im) = x.re_im();
+                [re, im].into_iter().take(per)
+            })
+            .collect()
+    };
+    let a_x = values(&a.a_x[..nnz]);
+    let rhs = values(&a.rhs[..m * nrhs]);
+
+    let ptr_cards = cards(n + 1, int_format);
+    let ind_cards = cards(nnz, int_format);
+    let val_cards = cards(a_x.len(), VALUE_FORMAT);
+    let rhs_cards = cards(rhs.len(), VALUE_FORMAT);
+    let int_fmt = format!("({}I{})", int_format.count, int_format.width);
+    let val_fmt = format!("({}E{}.16)", VALUE_FORMAT.count, VALUE_FORMAT.width);
+
+    let title: String = a.title.chars().take(72).collect();
+    let key: String = a.key.chars().take(8).collect();
+    writeln!(w, "{:<72}{:<8}", title, key)?;
+    let total = ptr_cards + ind_cards + val_cards + rhs_cards;
+    if rb {
+        writeln!(
+            w,
+            "{:14}{:14}{:14}{:14}",
+            total, ptr_cards, ind_cards, val_cards
+        )?;
+    } else {
+        writeln!(
+            w,
+            "{:14}{:14}{:14}{:14}{:14}",
+            total, ptr_cards, ind_cards, val_cards, rhs_cards
+        )?;
+    }
+    let shape = if m == n { 'U' } else { 'R' };
+    let mxtype = if rb {
+        format!(
+            "{}{}a",
+            field.to_ascii_lowercase(),
+            shape.to_ascii_lowercase()
+        )
+    } else {
+        format!("{}{}A", field, shape)
+    };
+    writeln!(w, "{}{:11}{:14}{:14}{:14}{:14}", mxtype, "", m, n, nnz, 0)?;
+    if rb {
+        writeln!(w, "{:<16}{:<16}{:<20}", int_fmt, int_fmt, val_fmt)?;
+    } else {
+        writeln!(
+            w,
+            "{:<16}{:<16}{:<20}{:<20}",
+            int_fmt, int_fmt, val_fmt, val_fmt
+        )?;
+    }
+    if nrhs > 0 {
+        writeln!(w, "{:<3}{:11}{:14}{:14}", "F", "", nrhs, 0)?;
+    }
+
+    let int = |w: &mut dyn Write, &i: &usize| write!(w, "{:>1$}", i + 1, int_format.width);
+    write_fields(&mut w, &a.a_p, int_format, int)?;
+    write_fields(&mut w, &a.a_i[..nnz], int_format, int)?;
+    let float = |mut w: &mut dyn Write, &x: &f64| write_float(&mut w, x);
+    write_fields(&mut w, &a_x, VALUE_FORMAT, float)?;
+    write_fields(&mut w, &rhs, VALUE_FORMAT, float)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_hb_from, read_rb_from, write_hb_to, write_rb_to, HBMatrix};
+    use anyhow::Result;
+    use num_complex::Complex64;
+
+    #[test]
+    fn test_read_hb() -> Result<()> {
+        // Symmetric 3x3 matrix with tightly packed fields and one
+        // right-hand-side.
+        //
+        //     {4, 1,  }
+        //     {1, 5, 2}
+        //     { , 2, 6}
+        let data = "\
+Test matrix                                                             TEST
+             7             1             1             2             1
+RSA                        3             3             5             0
+(4I3)           (5I2)           (3D8.1)             (3E10.2)
+F                          1             0
+  1  3  5  6
+ 1 2 2 3 3
+ 4.0D+00 1.0D+00 5.0D+00
+ 2.0D+00 6.0+000
+  1.00E+00  2.00E+00  3.00E+00
+";
+        let a = read_hb_from::<f64>(data.as_bytes())?;
+        assert_eq!((a.title.as_str(), a.key.as_str()), ("Test matrix", "TEST"));
+        assert_eq!((a.m, a.n, a.nrhs()), (3, 3, 1));
+        assert_eq!(a.a_p, vec![0, 2, 5, 7]);
+        assert_eq!(a.a_i, vec![0, 1, 0, 1, 2, 1, 2]);
+        assert_eq!(a.a_x, vec![4.0, 1.0, 1.0, 5.0, 2.0, 2.0, 6.0]);
+        assert_eq!(a.rhs, vec![1.0, 2.0, 3.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let a = HBMatrix {
+            title: "Round trip".to_string(),
+            key: "RT".to_string(),
+            m: 3,
+            n: 2,
+            a_p: vec![0, 2, 3],
+            a_i: vec![0, 2, 1],
+            a_x: vec![1.0 / 3.0, -2.5e-300, 7e100],
+            rhs: vec![0.1, 0.2, 0.3, -1.0, -2.0, -3.0],
+        };
+        let mut out = Vec::default();
+        write_hb_to(&mut out, &a)?;
+        assert_eq!(read_hb_from::<f64>(out.as_slice())?, a);
+
+        let mut out = Vec::default();
+        write_rb_to(&mut out, &a)?;
+        let b = read_rb_from::<f64>(out.as_slice())?;
+        assert_eq!(b.a_x, a.a_x);
+        assert!(b.rhs.is_empty());
+
+        let c = HBMatrix {
+            m: 2,
+            n: 2,
+            a_p: vec![0, 1, 2],
+            a_i: vec![1, 0],
+            a_x: vec![Complex64::new(1.0, -2.0), Complex64::new(0.5, 0.25)],
+            rhs: vec![Complex64::new(3.0, 4.0), Complex64::new(0.0, 1.0)],
+            ..Default::default()
+        };
+        let mut out = Vec::default();
+        write_hb_to(&mut out, &c)?;
+        assert_eq!(read_hb_from::<Complex64>(out.as_slice())?, c);
+        Ok(())
+    }
+
+    #[test]
+    fn test_hermitian_rb() -> Result<()> {
+        let data = "\
+Hermitian                                                               H
+             3             1             1             1
+cha                        2             2             2             0
+(3I4)           (2I4)           (4E12.4)
+   1   3   3
+   1   2
+  2.0000E+00  0.0000E+00  1.0000E+00 -1.0000E+00
+";
+        let a = read_rb_from::<Complex64>(data.as_bytes())?;
+        assert_eq!(a.a_i, vec![0, 1, 0]);
+        assert_eq!(a.a_x[2], Complex64::new(1.0, 1.0));
+        assert!(read_rb_from::<f64>(data.as_bytes()).is_err());
+        Ok(())
+    }
+}
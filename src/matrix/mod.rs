@@ -0,0 +1,14 @@
+//! Test matrices and Matrix Market, Harwell-Boeing, Rutherford-Boeing and
+//! binary compressed-column file I/O, and synthetic test matrices.
+
+mod csc;
+mod dataset;
+mod file;
+pub mod gen;
+mod hb;
+mod mtx;
+
+pub use csc::*;
+pub use dataset::*;
+pub use hb::*;
+pub use mtx::*;
@@ -0,0 +1,500 @@
+//! Reading and writing of [Matrix Market](https://math.nist.gov/MatrixMarket/formats.html)
+//! files.
+//!
+//! Matrices are returned as `(m, n, a_p, a_i, a_x)` in compressed-column
+//! form with sorted row indices and duplicate entries summed. Symmetric,
+//! skew-symmetric and Hermitian storage is expanded to both triangles.
+
+use super::file::open;
+use crate::csc::compress;
+use anyhow::{format_err, Result};
+use num_complex::Complex64;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::ops::{Add, Neg};
+use std::path::Path;
+
+/// Matrix `(m, n, a_p, a_i, a_x)` read from a Matrix Market file.
+pub type Mtx<S> = (usize, usize, Vec<usize>, Vec<usize>, Vec<S>);
+
+/// Type of the values in a Matrix Market file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Real,
+    Complex,
+    Integer,
+    /// Structure only, read as ones.
+    Pattern,
+}
+
+/// Storage scheme of a Matrix Market file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Symmetry {
+    General,
+    Symmetric,
+    SkewSymmetric,
+    Hermitian,
+}
+
+/// Scalar types that can be read from and written to Matrix Market files.
+pub trait MtxScalar: Copy + Add<Output = Self> + Neg<Output = Self> {
+    /// Field written for this type.
+    const FIELD: Field;
+
+    /// Parses a value from the fields of an entry after the indices.
+    fn parse(field: Field, values: &[&str]) -> Result<Self>;
+
+    /// Complex conjugate of the value (identity for real scalars).
+    fn conj(self) -> Self;
+
+    /// Writes the fields of the value.
+    fn write(self, w: &mut dyn Write) -> std::io::Result<()>;
+
+    /// Real and imaginary parts of the value.
+    fn re_im(self) -> (f64, f64);
+
+    /// Value from real and imaginary parts (the imaginary part is ignored
+    /// for real scalars).
+    fn from_re_im(re: f64, im: f64) -> Self;
+}
+
+fn parse_f64(s: &str) -> Result<f64> {
+    s.parse().map_err(|_| format_err!("invalid value {:?}", s))
+}
+
+fn expect_values(values: &[&str], len: usize) -> Result<()> {
+    if values.len() != len {
+        return Err(format_err!(
+            "expected {} value(s), found {}",
+            len,
+            values.len()
+        ));
+    }
+    Ok(())
+}
+
+impl MtxScalar for f64 {
+    const FIELD: Field = Field::Real;
+
+    fn parse(field: Field, values: &[&str]) -> Result<Self> {
+        match field {
+            Field::Real | Field::Integer => {
+                expect_values(values, 1)?;
+                parse_f64(values[0])
+            }
+            Field::Pattern => {
+                expect_values(values, 0)?;
+                Ok(1.0)
+            }
+            Field::Complex => Err(format_err!("complex matrix read as real")),
+        }
+    }
+
+    fn conj(self) -> Self {
+        self
+    }
+
+    fn write(self, w: &mut dyn Write) -> std::io::Result<()> {
+        write!(w, "{:e}", self)
+    }
+
+    fn re_im(self) -> (f64, f64) {
+        (self, 0.0)
+    }
+
+    fn from_re_im(re: f64, _im: f64) -> Self {
+        re
+    }
+}
+
+impl MtxScalar for Complex64 {
+    const FIELD: Field = Field::Complex;
+
+    fn parse(field: Field, values: &[&str]) -> Result<Self> {
+        match field {
+            Field::Complex => {
+                expect_values(values, 2)?;
+                Ok(Complex64::new(parse_f64(values[0])?, parse_f64(values[1])?))
+            }
+            _ => f64::parse(field, values).map(Complex64::from),
+        }
+    }
+
+    fn conj(self) -> Self {
+        Complex64::conj(&self)
+    }
+
+    fn write(self, w: &mut dyn Write) -> std::io::Result<()> {
+        write!(w, "{:e} {:e}", self.re, self.im)
+    }
+
+    fn re_im(self) -> (f64, f64) {
+        (self.re, self.im)
+    }
+
+    fn from_re_im(re: f64, im: f64) -> Self {
+        Complex64::new(re, im)
+    }
+}
+
+/// Reads a sparse or dense Matrix Market file, which may be compressed
+/// (`.mtx.gz` or `.mtx.zst`).
+pub fn read_mtx<S: MtxScalar>(path: impl AsRef<Path>) -> Result<Mtx<S>> {
+    let path = path.as_ref();
+    read_mtx_from(open(path)?).map_err(|err| format_err!("{}: {}", path.display(), err))
+}
+
+/// Reads the field from the header line of a Matrix Market file.
+pub(super) fn read_mtx_field(path: &Path) -> Result<Field> {
+    let mut header = String::default();
+    open(path)?.read_line(&mut header)?;
+    let (_, field, _) =
+        parse_header(&header).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    Ok(field)
+}
+
+/// Reads a Matrix Market file from `reader`.
+pub fn read_mtx_from<S: MtxScalar>(reader: impl BufRead) -> Result<Mtx<S>> {
+    let mut lines = reader.lines().enumerate();
+
+    let header = match lines.next() {
+        Some((_, line)) => line?,
+        None => return Err(format_err!("empty file")),
+    };
+    let (dense, field, symmetry) = parse_header(&header)?;
+    if field == Field::Pattern && dense {
+        return Err(format_err!("pattern matrices must be in coordinate format"));
+    }
+    if symmetry == Symmetry::Hermitian && field != Field::Complex {
+        return Err(format_err!("hermitian matrices must be complex"));
+    }
+
+    // Data lines, skipping comments and blank lines.
+    let mut data = lines.filter_map(|(k, line)| match line {
+        Ok(line) if line.trim().is_empty() || line.starts_with('%') => None,
+        Ok(line) => Some(Ok((k + 1, line))),
+        Err(err) => Some(Err(err)),
+    });
+
+    let (k, size) = data
+        .next()
+        .ok_or_else(|| format_err!("missing size line"))??;
+    let size = size
+        .split_whitespace()
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format_err!("line {}: invalid size line", k))?;
+    let (m, n) = match (dense, size.as_slice()) {
+        (false, &[m, n, _]) | (true, &[m, n]) => (m, n),
+        _ => return Err(format_err!("line {}: invalid size line", k)),
+    };
+    if symmetry != Symmetry::General && m != n {
+        return Err(format_err!("{:?} matrix must be square", symmetry));
+    }
+
+    // Coordinates of the stored entries.
+    let stored: Vec<(usize, usize)> = if dense {
+        (0..n)
+            .flat_map(|j| {
+                let i0 = match symmetry {
+                    Symmetry::General => 0,
+                    Symmetry::SkewSymmetric => j + 1,
+                    _ => j,
+                };
+                (i0..m).map(move |i| (i, j))
+            })
+            .collect()
+    } else {
+        Vec::default()
+    };
+    let nnz = if dense { stored.len() } else { size[2] };
+
+    let mut t_i = Vec::with_capacity(2 * nnz);
+    let mut t_j = Vec::with_capacity(2 * nnz);
+    let mut t_x = Vec::with_capacity(2 * nnz);
+    let mut coords = stored.iter();
+    for e in 0..nnz {
+        let (k, line) = data
+            .next()
+            .ok_or_else(|| format_err!("expected {} entries, found {}", nnz, e))??;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let (i, j, values) = if dense {
+            let (i, j) = *coords.next().unwrap();
+            (i, j, &parts[..])
+        } else {
+            if parts.len() < 2 {
+                return Err(format_err!("line {}: missing indices", k));
+            }
+            let index = |s: &str, len: usize| match s.parse::<usize>() {
+                Ok(i) if i >= 1 && i <= len => Ok(i - 1),
+                _ => Err(format_err!("line {}: invalid index {:?}", k, s)),
+            };
+            (index(parts[0], m)?, index(parts[1], n)?, &parts[2..])
+        };
+        let x = S::parse(field, values).map_err(|err| format_err!("line {}: {}", k, err))?;
+
+        t_i.push(i);
+        t_j.push(j);
+        t_x.push(x);
+        if i != j {
+            let y = match symmetry {
+                Symmetry::General => continue,
+                Symmetry::Symmetric => x,
+                Symmetry::SkewSymmetric => -x,
+                Symmetry::Hermitian => x.conj(),
+            };
+            t_i.push(j);
+            t_j.push(i);
+            t_x.push(y);
+        }
+    }
+    if let Some((k, _)) = data.next().transpose()? {
+        return Err(format_err!("line {}: more than {} entries", k, nnz));
+    }
+
+    let (a_p, a_i, a_x) = compress(n, &t_i, &t_j, &t_x);
+    Ok((m, n, a_p, a_i, a_x))
+}
+
+fn parse_header(header: &str) -> Result<(bool, Field, Symmetry)> {
+    let words: Vec<String> = header
+        .split_whitespace()
+        .map(|w| w.to_lowercase())
+        .collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let (format, field, symmetry) = match words.as_slice() {
+        &["%%matrixmarket", "matrix", format, field, symmetry] => (format, field, symmetry),
+        _ => return Err(format_err!("invalid header {:?}", header)),
+    };
+    let dense = match format {
+        "coordinate" => false,
+        "array" => true,
+        _ => return Err(format_err!("unsupported format {:?}", format)),
+    };
+    let field = match field {
+        "real" => Field::Real,
+        "complex" => Field::Complex,
+        "integer" => Field::Integer,
+        "pattern" => Field::Pattern,
+        _ => return Err(format_err!("unsupported field {:?}", field)),
+    };
+    let symmetry = match symmetry {
+        "general" => Symmetry::General,
+        "symmetric" => Symmetry::Symmetric,
+        "skew-symmetric" => Symmetry::SkewSymmetric,
+        "hermitian" => Symmetry::Hermitian,
+        _ => return Err(format_err!("unsupported symmetry {:?}", symmetry)),
+    };
+    Ok((dense, field, symmetry))
+}
+
+/// Writes an `m x n` matrix in compressed-column form to a Matrix Market
+/// file in general coordinate format.
+pub fn write_mtx<S: MtxScalar>(
+    path: impl AsRef<Path>,
+    m: usize,
+    n: usize,
+    a_p: &[usize],
+    a_i: &[usize],
+    a_x: &[S],
+) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    let mut w = BufWriter::new(file);
+    write_mtx_to(&mut w, m, n, a_p, a_i, a_x)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes a matrix in general coordinate format to `w`.
+pub fn write_mtx_to<S: MtxScalar>(
+    mut w: impl Write,
+    m: usize,
+    n: usize,
+    a_p: &[usize],
+    a_i: &[usize],
+    a_x: &[S],
+) -> Result<()> {
+    if a_p.len() != n + 1 {
+        return Err(format_err!("a_p must have length {}", n + 1));
+    }
+    let nnz = a_p[n];
+    if a_i.len() < nnz || a_x.len() < nnz {
+        return Err(format_err!("a_i and a_x must have length {}", nnz));
+    }
+
+    writeln!(
+        w,
+        "%%MatrixMarket matrix coordinate {} general",
+        field_name(S::FIELD)
+    )?;
+    writeln!(w, "{} {} {}", m, n, nnz)?;
+    for j in 0..n {
+        for p in a_p[j]..a_p[j + 1] {
+            if a_i[p] >= m {
+                return Err(format_err!("row index {} out of range", a_i[p]));
+            }
+            write!(w, "{} {} ", a_i[p] + 1, j + 1)?;
+            a_x[p].write(&mut w)?;
+            writeln!(w)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes an `m x n` dense matrix, stored column-major in `x`, to a Matrix
+/// Market file in general array format. Right-hand-side vectors are
+/// written with `n = 1`.
+pub fn write_mtx_dense<S: MtxScalar>(
+    path: impl AsRef<Path>,
+    m: usize,
+    n: usize,
+    x: &[S],
+) -> Result<()> {
+    let path = path.as_ref();
+    let file = File::create(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    let mut w = BufWriter::new(file);
+    write_mtx_dense_to(&mut w, m, n, x)?;
+    w.flush()?;
+    Ok(())
+}
+
+/// Writes a dense matrix in general array format to `w`.
+pub fn write_mtx_dense_to<S: MtxScalar>(
+    mut w: impl Write,
+    m: usize,
+    n: usize,
+    x: &[S],
+) -> Result<()> {
+    if x.len() != m * n {
+        return Err(format_err!("x must have length {}", m * n));
+    }
+    writeln!(
+        w,
+        "%%MatrixMarket matrix array {} general",
+        field_name(S::FIELD)
+    )?;
+    writeln!(w, "{} {}", m, n)?;
+    for &x in x {
+        x.write(&mut w)?;
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn field_name(field: Field) -> &'static str {
+    match field {
+        Field::Real => "real",
+        Field::Complex => "complex",
+        Field::Integer => "integer",
+        Field::Pattern => "pattern",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_mtx, read_mtx_from, write_mtx_dense_to, write_mtx_to};
+    use anyhow::Result;
+    use num_complex::Complex64;
+
+    #[test]
+    fn test_general() -> Result<()> {
+        let data = "%%MatrixMarket matrix coordinate real general
+% comment
+3 2 4
+1 1 1.5
+3 1 -2
+2 2 3e-1
+3 1 1
+";
+        let (m, n, a_p, a_i, a_x) = read_mtx_from::<f64>(data.as_bytes())?;
+        assert_eq!((m, n), (3, 2));
+        assert_eq!(a_p, vec![0, 2, 3]);
+        assert_eq!(a_i, vec![0, 2, 1]);
+        assert_eq!(a_x, vec![1.5, -1.0, 0.3]);
+
+        let mut out = Vec::default();
+        write_mtx_to(&mut out, m, n, &a_p, &a_i, &a_x)?;
+        let b = read_mtx_from::<f64>(out.as_slice())?;
+        assert_eq!(b, (m, n, a_p, a_i, a_x));
+        Ok(())
+    }
+
+    #[test]
+    fn test_symmetry() -> Result<()> {
+        let data = "%%MatrixMarket matrix coordinate integer symmetric
+2 2 2
+1 1 4
+2 1 -1
+";
+        let (_, _, a_p, a_i, a_x) = read_mtx_from::<f64>(data.as_bytes())?;
+        assert_eq!(
+            (a_p, a_i, a_x),
+            (vec![0, 2, 3], vec![0, 1, 0], vec![4.0, -1.0, -1.0])
+        );
+
+        let data = "%%MatrixMarket matrix coordinate real skew-symmetric
+2 2 1
+2 1 3
+";
+        let (_, _, _, _, a_x) = read_mtx_from::<f64>(data.as_bytes())?;
+        assert_eq!(a_x, vec![3.0, -3.0]);
+
+        let data = "%%MatrixMarket matrix coordinate complex hermitian
+2 2 2
+1 1 2 0
+2 1 1 -1
+";
+        let (_, _, _, a_i, a_x) = read_mtx_from::<Complex64>(data.as_bytes())?;
+        assert_eq!(a_i, vec![0, 1, 0]);
+        assert_eq!(a_x[2], Complex64::new(1.0, 1.0));
+
+        let data = "%%MatrixMarket matrix coordinate pattern general
+2 2 2
+1 2
+2 1
+";
+        let (_, _, a_p, a_i, a_x) = read_mtx_from::<Complex64>(data.as_bytes())?;
+        assert_eq!((a_p, a_i), (vec![0, 1, 2], vec![1, 0]));
+        assert_eq!(a_x, vec![Complex64::new(1.0, 0.0); 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_array() -> Result<()> {
+        let data = "%%MatrixMarket matrix array real symmetric
+2 2
+1
+2
+3
+";
+        let (_, _, a_p, a_i, a_x) = read_mtx_from::<f64>(data.as_bytes())?;
+        assert_eq!(a_p, vec![0, 2, 4]);
+        assert_eq!(a_i, vec![0, 1, 0, 1]);
+        assert_eq!(a_x, vec![1.0, 2.0, 2.0, 3.0]);
+
+        let x = vec![Complex64::new(1.0, -1.0), Complex64::new(0.5, 2.0)];
+        let mut out = Vec::default();
+        write_mtx_dense_to(&mut out, 2, 1, &x)?;
+        let (m, n, _, _, a_x) = read_mtx_from::<Complex64>(out.as_slice())?;
+        assert_eq!((m, n, a_x), (2, 1, x));
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() {
+        for data in [
+            "",
+            "%%MatrixMarket matrix coordinate real general\n",
+            "%%MatrixMarket matrix coordinate real general\n2 2 1\n3 1 1.0\n",
+            "%%MatrixMarket matrix coordinate real general\n2 2 2\n1 1 1.0\n",
+            "%%MatrixMarket matrix coordinate real general\n2 2 1\n1 1 x\n",
+            "%%MatrixMarket matrix coordinate complex general\n2 2 1\n1 1 1 0\n",
+            "%%MatrixMarket matrix coordinate real symmetric\n2 3 0\n",
+            "%%MatrixMarket vector coordinate real general\n2 2 0\n",
+        ] {
+            assert!(read_mtx_from::<f64>(data.as_bytes()).is_err(), "{:?}", data);
+        }
+        assert!(read_mtx::<f64>("does/not/exist.mtx").is_err());
+    }
+}
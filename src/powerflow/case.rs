@@ -0,0 +1,365 @@
+use anyhow::{format_err, Result};
+use num_complex::Complex64;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Type of a bus, as in the `BUS_TYPE` column of a MATPOWER case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BusType {
+    PQ,
+    PV,
+    Ref,
+    Isolated,
+}
+
+/// Row of the MATPOWER bus table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bus {
+    /// External bus number.
+    pub id: usize,
+    pub kind: BusType,
+    /// Real power demand (MW).
+    pub pd: f64,
+    /// Reactive power demand (MVAr).
+    pub qd: f64,
+    /// Shunt conductance (MW demanded at V = 1.0 p.u.).
+    pub gs: f64,
+    /// Shunt susceptance (MVAr injected at V = 1.0 p.u.).
+    pub bs: f64,
+    /// Voltage magnitude (p.u.).
+    pub vm: f64,
+    /// Voltage angle (degrees).
+    pub va: f64,
+    pub base_kv: f64,
+}
+
+/// Row of the MATPOWER generator table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gen {
+    /// External number of the bus the generator is connected to.
+    pub bus: usize,
+    /// Real power output (MW).
+    pub pg: f64,
+    /// Reactive power output (MVAr).
+    pub qg: f64,
+    pub qmax: f64,
+    pub qmin: f64,
+    /// Voltage magnitude setpoint (p.u.).
+    pub vg: f64,
+    pub status: bool,
+}
+
+/// Row of the MATPOWER branch table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    /// External number of the "from" bus.
+    pub from: usize,
+    /// External number of the "to" bus.
+    pub to: usize,
+    /// Resistance (p.u.).
+    pub r: f64,
+    /// Reactance (p.u.).
+    pub x: f64,
+    /// Total line charging susceptance (p.u.).
+    pub b: f64,
+    /// Transformer off-nominal turns ratio (zero for lines).
+    pub ratio: f64,
+    /// Transformer phase shift angle (degrees).
+    pub angle: f64,
+    pub status: bool,
+}
+
+impl Branch {
+    /// Complex tap ratio, with a zero ratio taken as one.
+    pub fn tap(&self) -> Complex64 {
+        let ratio = if self.ratio == 0.0 { 1.0 } else { self.ratio };
+        Complex64::from_polar(ratio, self.angle.to_radians())
+    }
+}
+
+/// Power system case, as defined by a MATPOWER case file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    /// System MVA base.
+    pub base_mva: f64,
+    pub bus: Vec<Bus>,
+    pub gen: Vec<Gen>,
+    pub branch: Vec<Branch>,
+}
+
+impl Case {
+    /// Map from external bus numbers to bus table rows.
+    pub fn bus_index(&self) -> Result<HashMap<usize, usize>> {
+        let mut index = HashMap::with_capacity(self.bus.len());
+        for (i, bus) in self.bus.iter().enumerate() {
+            if index.insert(bus.id, i).is_some() {
+                return Err(format_err!("duplicate bus {}", bus.id));
+            }
+        }
+        Ok(index)
+    }
+
+    /// Bus table rows of the "from" and "to" buses of each branch.
+    pub(crate) fn branch_buses(&self) -> Result<Vec<(usize, usize)>> {
+        let index = self.bus_index()?;
+        let lookup = |id: usize| {
+            index
+                .get(&id)
+                .copied()
+                .ok_or_else(|| format_err!("unknown bus {}", id))
+        };
+        self.branch
+            .iter()
+            .map(|br| Ok((lookup(br.from)?, lookup(br.to)?)))
+            .collect()
+    }
+
+    /// Returns the reference, PV and PQ buses. PV and reference buses
+    /// without an in-service generator are treated as PQ buses.
+    pub fn bus_types(&self) -> Result<(Vec<usize>, Vec<usize>, Vec<usize>)> {
+        let index = self.bus_index()?;
+        let mut has_gen = vec![false; self.bus.len()];
+        for gen in self.gen.iter().filter(|g| g.status) {
+            let i = index
+                .get(&gen.bus)
+                .ok_or_else(|| format_err!("unknown generator bus {}", gen.bus))?;
+            has_gen[*i] = true;
+        }
+
+        let (mut ref_, mut pv, mut pq) = (Vec::new(), Vec::new(), Vec::new());
+        for (i, bus) in self.bus.iter().enumerate() {
+            match bus.kind {
+                BusType::Ref if has_gen[i] => ref_.push(i),
+                BusType::PV if has_gen[i] => pv.push(i),
+                BusType::Isolated => {}
+                _ => pq.push(i),
+            }
+        }
+        if ref_.is_empty() {
+            return Err(format_err!("no reference bus with an in-service generator"));
+        }
+        Ok((ref_, pv, pq))
+    }
+}
+
+/// Reads a MATPOWER case file (version 2).
+pub fn read_case(path: impl AsRef<Path>) -> Result<Case> {
+    let path = path.as_ref();
+    let text =
+        fs::read_to_string(path).map_err(|err| format_err!("{}: {}", path.display(), err))?;
+    parse_case(&text).map_err(|err| format_err!("{}: {}", path.display(), err))
+}
+
+/// Parses the text of a MATPOWER case file. Only the `baseMVA`, `bus`,
+/// `gen` and `branch` fields are read.
+pub fn parse_case(text: &str) -> Result<Case> {
+    // Strip comments and join continued lines.
+    let mut joined = String::with_capacity(text.len());
+    for line in text.lines() {
+        let line = line.split('%').next().unwrap_or_default();
+        match line.split_once("...") {
+            Some((line, _)) => {
+                joined.push_str(line);
+                joined.push(' ');
+            }
+            None => {
+                joined.push_str(line);
+                joined.push('\n');
+            }
+        }
+    }
+    let text = joined;
+
+    let base_mva = field(&text, "baseMVA")?;
+    let base_mva: f64 = base_mva
+        .trim()
+        .parse()
+        .map_err(|_| format_err!("invalid baseMVA {:?}", base_mva))?;
+
+    let bus = table(&text, "bus", 10)?
+        .iter()
+        .map(|row| {
+            Ok(Bus {
+                id: int(row[0])?,
+                kind: match row[1] as i64 {
+                    1 => BusType::PQ,
+                    2 => BusType::PV,
+                    3 => BusType::Ref,
+                    4 => BusType::Isolated,
+                    t => return Err(format_err!("invalid type {} of bus {}", t, row[0])),
+                },
+                pd: row[2],
+                qd: row[3],
+                gs: row[4],
+                bs: row[5],
+                vm: row[7],
+                va: row[8],
+                base_kv: row[9],
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let gen = table(&text, "gen", 8)?
+        .iter()
+        .map(|row| {
+            Ok(Gen {
+                bus: int(row[0])?,
+                pg: row[1],
+                qg: row[2],
+                qmax: row[3],
+                qmin: row[4],
+                vg: row[5],
+                status: row[7] > 0.0,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let branch = table(&text, "branch", 11)?
+        .iter()
+        .map(|row| {
+            Ok(Branch {
+                from: int(row[0])?,
+                to: int(row[1])?,
+                r: row[2],
+                x: row[3],
+                b: row[4],
+                ratio: row[8],
+                angle: row[9],
+                status: row[10] > 0.0,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Case {
+        base_mva,
+        bus,
+        gen,
+        branch,
+    })
+}
+
+fn int(x: f64) -> Result<usize> {
+    if x >= 0.0 && x.fract() == 0.0 {
+        Ok(x as usize)
+    } else {
+        Err(format_err!("invalid bus number {}", x))
+    }
+}
+
+/// Returns the right-hand side of the assignment `mpc.<name> = ...;`.
+fn field<'a>(text: &'a str, name: &str) -> Result<&'a str> {
+    let pattern = format!("mpc.{}", name);
+    let mut rest = text;
+    while let Some(k) = rest.find(&pattern) {
+        rest = &rest[k + pattern.len()..];
+        let value = rest.trim_start();
+        if let Some(value) = value.strip_prefix('=') {
+            // Matrices may span lines, so end at "];" or the line end.
+            let value = value.trim_start();
+            let end = if value.starts_with('[') {
+                value.find(']').map(|k| k + 1)
+            } else {
+                value.find([';', '\n'])
+            };
+            return end
+                .map(|end| &value[..end])
+                .ok_or_else(|| format_err!("unterminated mpc.{}", name));
+        }
+    }
+    Err(format_err!("missing mpc.{}", name))
+}
+
+/// Parses the matrix `mpc.<name> = [...]`, with at least `cols` columns.
+fn table(text: &str, name: &str, cols: usize) -> Result<Vec<Vec<f64>>> {
+    let value = field(text, name)?;
+    let value = value
+        .strip_prefix('[')
+        .and_then(|value| value.strip_suffix(']'))
+        .ok_or_else(|| format_err!("mpc.{} is not a matrix", name))?;
+
+    value
+        .split([';', '\n'])
+        .map(|row| row.trim())
+        .filter(|row| !row.is_empty())
+        .map(|row| {
+            let row = row
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.parse::<f64>()
+                        .map_err(|_| format_err!("mpc.{}: invalid value {:?}", name, s))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            if row.len() < cols {
+                return Err(format_err!(
+                    "mpc.{}: expected {} columns, found {}",
+                    name,
+                    cols,
+                    row.len()
+                ));
+            }
+            Ok(row)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_case, BusType};
+    use crate::powerflow::case9;
+    use anyhow::Result;
+
+    #[test]
+    fn test_case9() -> Result<()> {
+        let case = case9()?;
+        assert_eq!(case.base_mva, 100.0);
+        assert_eq!(
+            (case.bus.len(), case.gen.len(), case.branch.len()),
+            (9, 3, 9)
+        );
+        assert_eq!(case.bus[0].kind, BusType::Ref);
+        assert_eq!((case.bus[6].pd, case.bus[6].qd), (100.0, 35.0));
+        assert_eq!((case.gen[1].bus, case.gen[1].vg), (2, 1.025));
+        assert_eq!(case.branch[2].b, 0.358);
+
+        let (ref_, pv, pq) = case.bus_types()?;
+        assert_eq!(
+            (ref_, pv, pq),
+            (vec![0], vec![1, 2], vec![3, 4, 5, 6, 7, 8])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<()> {
+        let text = "
+function mpc = case2
+mpc.version = '2';
+mpc.baseMVA = 10;
+mpc.bus = [ % bus_i type ...
+    1, 3, 0, 0, 0, 0, 1, 1, 0, 230;
+    2  1  5  1  0  0  1  1  0  230 ...
+        1.1 0.9
+];
+mpc.gen = [1 0 0 Inf -Inf 1.0 100 1];
+mpc.branch = [
+    1 2 0.01 0.1 0 0 0 0 0.95 -3 1
+];
+mpc.bus_name = {'a'; 'b'};
+";
+        let case = parse_case(text)?;
+        assert_eq!(case.base_mva, 10.0);
+        assert_eq!(case.bus[1].pd, 5.0);
+        assert_eq!(case.gen[0].qmax, f64::INFINITY);
+        assert_eq!((case.branch[0].ratio, case.branch[0].angle), (0.95, -3.0));
+
+        assert!(parse_case(&text.replace("1 2 0.01", "1 3 0.01"))?
+            .branch_buses()
+            .is_err());
+        assert!(parse_case(&text.replace("mpc.baseMVA", "baseMVA")).is_err());
+        assert!(parse_case(&text.replace(", 3, 0,", ", 7, 0,")).is_err());
+        assert!(parse_case(&text.replace("-Inf 1.0 100 1", "")).is_err());
+        Ok(())
+    }
+}
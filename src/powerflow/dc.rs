@@ -0,0 +1,441 @@
+use crate::powerflow::network::submatrix;
+use crate::powerflow::Case;
+use crate::{FactorSolver, SparseSolver, Workspace};
+use anyhow::{format_err, Result};
+
+/// DC power flow, with the reduced Bbus factorized once for any number of
+/// injection patterns and sensitivity solves.
+///
+/// The reference bus angle is fixed and isolated buses are excluded, so
+/// every other bus must be connected to the reference bus. The distribution
+/// factors need a [`SparseSolver`], as each is found from a right-hand-side
+/// with at most two nonzeros.
+pub struct DCPowerFlow<'a, T: ?Sized, F> {
+    solver: &'a T,
+    /// Factors of the reduced Bbus.
+    f: F,
+    /// Position of each bus in the reduced Bbus.
+    red: Vec<Option<usize>>,
+    n_red: usize,
+    /// Reference bus angle (radians).
+    va_ref: f64,
+    /// In-service branches, as `(branch, from, to, b, shift)`.
+    branches: Vec<(usize, usize, usize, f64, f64)>,
+    n_branch: usize,
+
+    /// Scheduled net bus injections (p.u.), less the shunt demand and the
+    /// equivalent injections of phase shifters.
+    pub p: Vec<f64>,
+}
+
+/// DC power flow solution.
+#[derive(Debug, Clone)]
+pub struct DCSolution {
+    /// Bus voltage angles (radians).
+    pub va: Vec<f64>,
+    /// Branch flows (p.u.), zero for out-of-service branches.
+    pub pf: Vec<f64>,
+}
+
+impl<'a, T, F> DCPowerFlow<'a, T, F>
+where
+    T: FactorSolver<usize, f64, F> + ?Sized,
+{
+    /// Builds and factorizes the reduced Bbus of `case` with `solver`.
+    pub fn new(solver: &'a T, case: &Case) -> Result<Self> {
+        let n = case.bus.len();
+        let (ref_, pv, pq) = case.bus_types()?;
+        let mut red = vec![None; n];
+        let mut n_red = 0;
+        for (i, r) in red.iter_mut().enumerate() {
+            if pv.contains(&i) || pq.contains(&i) {
+                *r = Some(n_red);
+                n_red += 1;
+            }
+        }
+        if n_red == 0 {
+            return Err(format_err!("no buses other than the reference bus"));
+        }
+
+        let keep: Vec<usize> = (0..n).filter(|&i| red[i].is_some()).collect();
+        let (_, a_p, a_i, a_x) = submatrix(&case.bbus()?, &keep);
+        let f = solver.factor(n_red, &a_i, &a_p, &a_x)?;
+
+        let mut p: Vec<f64> = case.sbus()?.iter().map(|s| s.re).collect();
+        for (p, bus) in p.iter_mut().zip(&case.bus) {
+            *p -= bus.gs / case.base_mva;
+        }
+        let mut branches = Vec::new();
+        for (k, (br, &(f, t))) in case.branch.iter().zip(&case.branch_buses()?).enumerate() {
+            if !br.status {
+                continue;
+            }
+            let ratio = if br.ratio == 0.0 { 1.0 } else { br.ratio };
+            let b = 1.0 / (br.x * ratio);
+            let shift = br.angle.to_radians();
+            p[f] += b * shift;
+            p[t] -= b * shift;
+            branches.push((k, f, t, b, shift));
+        }
+
+        Ok(Self {
+            solver,
+            f,
+            red,
+            n_red,
+            va_ref: case.bus[ref_[0]].va.to_radians(),
+            branches,
+            n_branch: case.branch.len(),
+            p,
+        })
+    }
+
+    /// Solves for the bus angles and branch flows at the scheduled
+    /// injections [`p`](Self::p).
+    pub fn solve(&self) -> Result<DCSolution> {
+        let va = self.angles(&self.p)?;
+        let pf = self.flows(&va);
+        Ok(DCSolution { va, pf })
+    }
+
+    /// Bus voltage angles (radians) for the net bus injections `p` (p.u.).
+    /// The injection at the reference bus is ignored.
+    pub fn angles(&self, p: &[f64]) -> Result<Vec<f64>> {
+        if p.len() != self.red.len() {
+            return Err(format_err!(
+                "expected {} injections, found {}",
+                self.red.len(),
+                p.len()
+            ));
+        }
+        let mut x = vec![0.0; self.n_red];
+        for (i, &r) in self.red.iter().enumerate() {
+            if let Some(r) = r {
+                x[r] = p[i];
+            }
+        }
+        self.solver.solve(&self.f, &mut x, false)?;
+
+        Ok(self
+            .red
+            .iter()
+            .map(|r| self.va_ref + r.map_or(0.0, |r| x[r]))
+            .collect())
+    }
+
+    /// Branch flows (p.u.) for the bus angles `va`.
+    pub fn flows(&self, va: &[f64]) -> Vec<f64> {
+        let mut pf = vec![0.0; self.n_branch];
+        for &(k, f, t, b, shift) in &self.branches {
+            pf[k] = b * (va[f] - va[t] - shift);
+        }
+        pf
+    }
+}
+
+impl<T, F> DCPowerFlow<'_, T, F>
+where
+    T: FactorSolver<usize, f64, F> + SparseSolver<f64, F> + ?Sized,
+{
+    /// Power transfer distribution factors: the change in the flow of each
+    /// branch per unit injection at each bus, withdrawn at the reference
+    /// bus. Rows are branches and columns are buses.
+    pub fn ptdf(&self) -> Result<Vec<Vec<f64>>> {
+        let branches: Vec<usize> = (0..self.n_branch).collect();
+        self.ptdf_rows(&branches)
+    }
+
+    /// Rows of the [PTDF](Self::ptdf) matrix for `branches` only. Row `k`
+    /// solves `Bbus' x = b_k`, where `b_k` holds the two nonzeros of branch
+    /// `k`, and Bbus is symmetric so no transposed solve is needed.
+    pub fn ptdf_rows(&self, branches: &[usize]) -> Result<Vec<Vec<f64>>> {
+        let mut index = vec![None; self.n_branch];
+        for (j, &(k, ..)) in self.branches.iter().enumerate() {
+            index[k] = Some(j);
+        }
+        let buses: Vec<usize> = (0..self.red.len())
+            .filter(|&i| self.red[i].is_some())
+            .collect();
+
+        let mut ws = Workspace::new();
+        let mut rows = Vec::with_capacity(branches.len());
+        for &k in branches {
+            let j = *index
+                .get(k)
+                .ok_or_else(|| format_err!("unknown branch {}", k))?;
+            let mut row = vec![0.0; self.red.len()];
+            if let Some(j) = j {
+                let (_, f, t, y, _) = self.branches[j];
+                let (b_i, b_x): (Vec<usize>, Vec<f64>) = [(self.red[f], y), (self.red[t], -y)]
+                    .into_iter()
+                    .filter_map(|(r, y)| r.map(|r| (r, y)))
+                    .unzip();
+                let x = self
+                    .solver
+                    .solve_sparse_with(&self.f, &b_i, &b_x, &mut ws)?;
+                for (&r, &x) in x.indices.iter().zip(&x.values) {
+                    row[buses[r]] = x;
+                }
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Line outage distribution factors: the change in the flow of each
+    /// branch (rows) per unit of pre-outage flow on each outaged branch
+    /// (columns). Diagonal entries are -1 and outages that island the
+    /// network, or of out-of-service branches, give NaN columns.
+    pub fn lodf(&self) -> Result<Vec<Vec<f64>>> {
+        let ptdf = self.ptdf()?;
+        let mut lodf = vec![vec![f64::NAN; self.n_branch]; self.n_branch];
+        for &(k, f, t, ..) in &self.branches {
+            let denom = 1.0 - (ptdf[k][f] - ptdf[k][t]);
+            if denom.abs() < 1e-10 {
+                continue;
+            }
+            for (l, row) in lodf.iter_mut().enumerate() {
+                row[k] = if l == k {
+                    -1.0
+                } else {
+                    (ptdf[l][f] - ptdf[l][t]) / denom
+                };
+            }
+        }
+        Ok(lodf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DCPowerFlow;
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    use crate::matrix::Dataset;
+    use crate::powerflow::case9;
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    use crate::powerflow::{Branch, Bus, BusType, Case, Gen};
+    use crate::FactorSolver;
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    use crate::SparseSolver;
+    use anyhow::Result;
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    use std::collections::BTreeMap;
+
+    fn case9_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+        let case = case9()?;
+        let dc = DCPowerFlow::new(solver, &case)?;
+        let sol = dc.solve()?;
+        assert_eq!(sol.va[0], 0.0);
+
+        // Flows balance the injections at every bus.
+        let buses = case.branch_buses()?;
+        let mut net = dc.p.clone();
+        for (&(f, t), pf) in buses.iter().zip(&sol.pf) {
+            net[f] -= pf;
+            net[t] += pf;
+        }
+        let total: f64 = dc.p.iter().sum();
+        assert!((net[0] - total).abs() < 1e-12);
+        assert!(net[1..].iter().all(|x| x.abs() < 1e-12), "{:?}", net);
+        Ok(())
+    }
+
+    /// DC model of ACTIVSg200 from its Bbus test matrix, which has the rows
+    /// of all buses but the reference bus 189 and the columns of all buses
+    /// but bus 1. The missing entries follow from symmetry. Each bus other
+    /// than the reference bus has a load of 10 to 16 MW.
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    fn activsg200() -> Result<Case> {
+        let (n, a_p, a_i, a_x) = Dataset::get("ACTIVSg200_Bbus")?.read::<f64>(true)?;
+        let ref_ = 188;
+        let row = |i: usize| i + (i >= ref_) as usize;
+
+        let mut b = BTreeMap::new();
+        for j in 0..n {
+            for p in a_p[j]..a_p[j + 1] {
+                let (i, j) = (row(a_i[p]), j + 1);
+                if i != j {
+                    b.insert((i.min(j), i.max(j)), -a_x[p]);
+                }
+            }
+        }
+
+        let bus: Vec<Bus> = (0..=n)
+            .map(|i| Bus {
+                id: i + 1,
+                kind: if i == ref_ { BusType::Ref } else { BusType::PQ },
+                pd: if i == ref_ {
+                    0.0
+                } else {
+                    10.0 + (i % 7) as f64
+                },
+                qd: 0.0,
+                gs: 0.0,
+                bs: 0.0,
+                vm: 1.0,
+                va: 0.0,
+                base_kv: 0.0,
+            })
+            .collect();
+        let gen = vec![Gen {
+            bus: ref_ + 1,
+            pg: bus.iter().map(|b| b.pd).sum(),
+            qg: 0.0,
+            qmax: 0.0,
+            qmin: 0.0,
+            vg: 1.0,
+            status: true,
+        }];
+        let branch = b
+            .iter()
+            .map(|(&(f, t), &b)| Branch {
+                from: f + 1,
+                to: t + 1,
+                r: 0.0,
+                x: 1.0 / b,
+                b: 0.0,
+                ratio: 0.0,
+                angle: 0.0,
+                status: true,
+            })
+            .collect();
+        let case = Case {
+            base_mva: 100.0,
+            bus,
+            gen,
+            branch,
+        };
+
+        // The diagonal checks that no branch is missing.
+        let (_, b_p, b_i, b_x) = case.bbus()?;
+        for j in 0..n {
+            for p in a_p[j]..a_p[j + 1] {
+                let i = row(a_i[p]);
+                let x = (b_p[j + 1]..b_p[j + 2])
+                    .find(|&q| b_i[q] == i)
+                    .map_or(0.0, |q| b_x[q]);
+                assert!((x - a_x[p]).abs() < 1e-9, "B[{},{}] = {}", i, j + 1, x);
+            }
+        }
+        Ok(case)
+    }
+
+    /// Whether taking branch `k` out of service separates a bus from the
+    /// reference bus.
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    fn islands(case: &Case, k: usize) -> Result<bool> {
+        let (ref_, ..) = case.bus_types()?;
+        let buses = case.branch_buses()?;
+        let mut seen = vec![false; case.bus.len()];
+        let mut stack = vec![ref_[0]];
+        seen[ref_[0]] = true;
+        while let Some(i) = stack.pop() {
+            for (l, &(f, t)) in buses.iter().enumerate() {
+                let j = if f == i {
+                    t
+                } else if t == i {
+                    f
+                } else {
+                    continue;
+                };
+                if l != k && case.branch[l].status && !seen[j] {
+                    seen[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+        Ok(seen.contains(&false))
+    }
+
+    /// Checks the PTDF columns against the flow change for a unit injection,
+    /// and the LODF columns against the flow change after each outage.
+    #[cfg(any(feature = "rlu", feature = "rsparse"))]
+    fn sensitivity_test<T, F>(solver: &T, case: &Case) -> Result<()>
+    where
+        T: FactorSolver<usize, f64, F> + SparseSolver<f64, F>,
+    {
+        let (ref_, ..) = case.bus_types()?;
+        let dc = DCPowerFlow::new(solver, case)?;
+        let sol = dc.solve()?;
+        let (n, n_branch) = (case.bus.len(), case.branch.len());
+
+        let ptdf = dc.ptdf()?;
+        for i in 0..n {
+            let mut p = dc.p.clone();
+            p[i] += 1.0;
+            let pf = dc.flows(&dc.angles(&p)?);
+            for l in 0..n_branch {
+                let expected = pf[l] - sol.pf[l];
+                assert!(
+                    (ptdf[l][i] - expected).abs() < 1e-10,
+                    "PTDF[{}][{}] = {} expected {}",
+                    l,
+                    i,
+                    ptdf[l][i],
+                    expected
+                );
+            }
+        }
+        assert!(ptdf.iter().all(|row| row[ref_[0]] == 0.0));
+        assert_eq!(
+            dc.ptdf_rows(&[4, 2])?,
+            vec![ptdf[4].clone(), ptdf[2].clone()]
+        );
+        assert!(dc.ptdf_rows(&[n_branch]).is_err());
+
+        let lodf = dc.lodf()?;
+        for k in 0..n_branch {
+            if islands(case, k)? {
+                assert!(lodf.iter().all(|row| row[k].is_nan()), "LODF column {}", k);
+                continue;
+            }
+            let mut outage = case.clone();
+            outage.branch[k].status = false;
+            let post = DCPowerFlow::new(solver, &outage)?.solve()?;
+            for (l, row) in lodf.iter().enumerate() {
+                let expected = post.pf[l] - sol.pf[l];
+                assert!(
+                    (row[k] * sol.pf[k] - expected).abs() < 1e-10,
+                    "LODF[{}][{}]",
+                    l,
+                    k
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "snlu")]
+    fn test_case9_snlu() -> Result<()> {
+        case9_test(&crate::snlu::SNLU::default())
+    }
+
+    #[test]
+    #[cfg(feature = "rlu")]
+    fn test_case9_rlu() -> Result<()> {
+        let solver = crate::rlu::RLU::default();
+        case9_test(&solver)?;
+        sensitivity_test(&solver, &case9()?)
+    }
+
+    #[test]
+    #[cfg(feature = "rsparse")]
+    fn test_case9_rsparse() -> Result<()> {
+        let solver = crate::rsparse::RSparse::default();
+        case9_test(&solver)?;
+        sensitivity_test(&solver, &case9()?)
+    }
+
+    #[test]
+    #[cfg(feature = "rlu")]
+    fn test_activsg200_rlu() -> Result<()> {
+        sensitivity_test(&crate::rlu::RLU::default(), &activsg200()?)
+    }
+
+    #[test]
+    #[cfg(feature = "rsparse")]
+    fn test_activsg200_rsparse() -> Result<()> {
+        sensitivity_test(&crate::rsparse::RSparse::default(), &activsg200()?)
+    }
+}
@@ -0,0 +1,227 @@
+use crate::matrix::Matrix;
+use crate::powerflow::network::submatrix;
+use crate::powerflow::newton::max_abs;
+use crate::powerflow::{injections, Case, Solution};
+use crate::FactorSolver;
+use anyhow::Result;
+use num_complex::Complex64;
+
+/// Approximation used to build the fast-decoupled `B'` and `B''` matrices.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FDMethod {
+    /// Branch resistance is ignored in `B'`.
+    XB,
+    /// Branch resistance is ignored in `B''`.
+    BX,
+}
+
+/// Fast-decoupled AC power flow.
+///
+/// `B'` and `B''` are factorized once and each iteration solves with both,
+/// updating the angles from the real power mismatch and then the PQ bus
+/// magnitudes from the reactive power mismatch.
+pub struct FastDecoupled {
+    pub method: FDMethod,
+
+    /// Convergence tolerance on the largest power mismatch (p.u.).
+    pub tol: f64,
+
+    /// Maximum number of iterations.
+    pub max_it: usize,
+}
+
+impl Default for FastDecoupled {
+    fn default() -> Self {
+        Self {
+            method: FDMethod::XB,
+            tol: 1e-8,
+            max_it: 30,
+        }
+    }
+}
+
+impl FastDecoupled {
+    /// Builds `B'` and `B''` for all buses of `case`, as in MATPOWER.
+    /// `B'` ignores line charging, bus shunts and off-nominal tap ratios
+    /// and `B''` ignores phase shifts.
+    pub fn b_matrices(&self, case: &Case) -> Result<(Matrix<f64>, Matrix<f64>)> {
+        let mut bp = case.clone();
+        for bus in &mut bp.bus {
+            bus.bs = 0.0;
+        }
+        for br in &mut bp.branch {
+            br.b = 0.0;
+            br.ratio = 1.0;
+            if self.method == FDMethod::XB {
+                br.r = 0.0;
+            }
+        }
+
+        let mut bpp = case.clone();
+        for br in &mut bpp.branch {
+            br.angle = 0.0;
+            if self.method == FDMethod::BX {
+                br.r = 0.0;
+            }
+        }
+
+        Ok((neg_imag(bp.ybus()?), neg_imag(bpp.ybus()?)))
+    }
+
+    /// Solves the power flow of `case`, starting from [`Case::voltages`].
+    pub fn solve<F>(
+        &self,
+        solver: &dyn FactorSolver<usize, f64, F>,
+        case: &Case,
+    ) -> Result<Solution> {
+        let ybus = case.ybus()?;
+        let sbus = case.sbus()?;
+        let (_, pv, pq) = case.bus_types()?;
+        let pvpq: Vec<usize> = pv.iter().chain(&pq).copied().collect();
+
+        let (bp, bpp) = self.b_matrices(case)?;
+        let factor = |b: &Matrix<f64>, buses: &[usize]| -> Result<Option<F>> {
+            if buses.is_empty() {
+                return Ok(None);
+            }
+            let (n, a_p, a_i, a_x) = submatrix(b, buses);
+            Ok(Some(solver.factor(n, &a_i, &a_p, &a_x)?))
+        };
+        let fp = factor(&bp, &pvpq)?;
+        let fpp = factor(&bpp, &pq)?;
+
+        let mut v = case.voltages()?;
+        let mut va: Vec<f64> = v.iter().map(|v| v.arg()).collect();
+        let mut vm: Vec<f64> = v.iter().map(|v| v.norm()).collect();
+
+        let mut ds = power_mismatch(&ybus, &sbus, &v);
+        let mut norm = max_mismatch(&ds, &pvpq, &pq);
+        let mut history = vec![norm];
+        let mut iterations = 0;
+        while norm >= self.tol && iterations < self.max_it {
+            iterations += 1;
+
+            if let Some(fp) = &fp {
+                let mut dp: Vec<f64> = pvpq.iter().map(|&i| ds[i].re / vm[i]).collect();
+                solver.solve(fp, &mut dp, false)?;
+                for (&i, dp) in pvpq.iter().zip(&dp) {
+                    va[i] -= dp;
+                    v[i] = Complex64::from_polar(vm[i], va[i]);
+                }
+                ds = power_mismatch(&ybus, &sbus, &v);
+                norm = max_mismatch(&ds, &pvpq, &pq);
+                if norm < self.tol {
+                    history.push(norm);
+                    break;
+                }
+            }
+
+            if let Some(fpp) = &fpp {
+                let mut dq: Vec<f64> = pq.iter().map(|&i| ds[i].im / vm[i]).collect();
+                solver.solve(fpp, &mut dq, false)?;
+                for (&i, dq) in pq.iter().zip(&dq) {
+                    vm[i] -= dq;
+                    v[i] = Complex64::from_polar(vm[i], va[i]);
+                }
+                ds = power_mismatch(&ybus, &sbus, &v);
+                norm = max_mismatch(&ds, &pvpq, &pq);
+            }
+            history.push(norm);
+        }
+
+        Ok(Solution {
+            v,
+            converged: norm < self.tol,
+            iterations,
+            mismatch: norm,
+            history,
+        })
+    }
+}
+
+/// Returns the matrix with values `-Im(y)`.
+fn neg_imag((n, a_p, a_i, a_x): Matrix<Complex64>) -> Matrix<f64> {
+    (n, a_p, a_i, a_x.iter().map(|y| -y.im).collect())
+}
+
+/// Bus power mismatches `V conj(Ybus V) - Sbus`.
+fn power_mismatch(ybus: &Matrix<Complex64>, sbus: &[Complex64], v: &[Complex64]) -> Vec<Complex64> {
+    injections(ybus, v)
+        .iter()
+        .zip(sbus)
+        .map(|(s, s0)| s - s0)
+        .collect()
+}
+
+/// Largest real power mismatch at `pvpq` and reactive power mismatch at
+/// `pq`.
+fn max_mismatch(ds: &[Complex64], pvpq: &[usize], pq: &[usize]) -> f64 {
+    let p = pvpq.iter().map(|&i| ds[i].re);
+    let q = pq.iter().map(|&i| ds[i].im);
+    max_abs(&p.chain(q).collect::<Vec<f64>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FDMethod, FastDecoupled};
+    use crate::powerflow::{case9, CASE9_V};
+    use crate::FactorSolver;
+    use anyhow::Result;
+
+    fn case9_test<F>(solver: &dyn FactorSolver<usize, f64, F>, method: FDMethod) -> Result<()> {
+        let case = case9()?;
+        let fd = FastDecoupled {
+            method,
+            ..Default::default()
+        };
+        let sol = fd.solve(solver, &case)?;
+        assert!(sol.converged, "mismatch {}", sol.mismatch);
+        assert!(sol.iterations <= 15, "{} iterations", sol.iterations);
+        assert_eq!(sol.history.len(), sol.iterations + 1);
+        assert_eq!(sol.history.last(), Some(&sol.mismatch));
+
+        for (i, (v, &(vm, va))) in sol.v.iter().zip(&CASE9_V).enumerate() {
+            assert!((v.norm() - vm).abs() < 1e-4, "|V{}| = {}", i + 1, v.norm());
+            assert!(
+                (v.arg().to_degrees() - va).abs() < 1e-4,
+                "angle V{} = {}",
+                i + 1,
+                v.arg().to_degrees()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_b_matrices() -> Result<()> {
+        let case = case9()?;
+        let (bp, bpp) = FastDecoupled::default().b_matrices(&case)?;
+        let (bbus, ybus) = (case.bbus()?, case.ybus()?);
+        // Without resistance or taps, B' is Bbus.
+        assert_eq!((&bp.1, &bp.2), (&bbus.1, &bbus.2));
+        for (x, y) in bp.3.iter().zip(&bbus.3) {
+            assert!((x - y).abs() < 1e-12);
+        }
+        // case9 has no phase shifters, so B'' is -Im(Ybus).
+        for (x, y) in bpp.3.iter().zip(&ybus.3) {
+            assert_eq!(*x, -y.im);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "snlu")]
+    fn test_case9_snlu() -> Result<()> {
+        let solver = crate::snlu::SNLU::default();
+        case9_test(&solver, FDMethod::XB)?;
+        case9_test(&solver, FDMethod::BX)
+    }
+
+    #[test]
+    #[cfg(feature = "rlu")]
+    fn test_case9_rlu() -> Result<()> {
+        let solver = crate::rlu::RLU::default();
+        case9_test(&solver, FDMethod::XB)?;
+        case9_test(&solver, FDMethod::BX)
+    }
+}
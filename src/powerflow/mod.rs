@@ -0,0 +1,64 @@
+//! Power flow network models, built from [MATPOWER](https://matpower.org)
+//! case files.
+//!
+//! Buses are numbered by their row in the case bus table and matrices are
+//! returned as `(n, a_p, a_i, a_x)` in compressed-column form, as consumed
+//! by the [`Solver`](crate::Solver) traits.
+
+mod case;
+mod dc;
+mod decoupled;
+mod network;
+mod newton;
+
+pub use case::*;
+pub use dc::*;
+pub use decoupled::*;
+pub use network::*;
+pub use newton::*;
+
+/// Reads the bundled MATPOWER `case9`.
+#[cfg(test)]
+pub(crate) fn case9() -> anyhow::Result<Case> {
+    read_case(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/matrix/matpower/case9.m"
+    ))
+}
+
+/// MATPOWER `runpf(case9)` bus voltages (p.u., degrees).
+#[cfg(test)]
+pub(crate) const CASE9_V: [(f64, f64); 9] = [
+    (1.0400, 0.0000),
+    (1.0250, 9.2800),
+    (1.0250, 4.6648),
+    (1.0258, -2.2168),
+    (1.0127, -3.6874),
+    (1.0324, 1.9667),
+    (1.0159, 0.7275),
+    (1.0258, 3.7197),
+    (0.9956, -3.9888),
+];
+
+/// Reads the bus types and solved voltages of the ACTIVSg200 case, at
+/// which the `ACTIVSg200_Jac` test matrix was exported, as `(pv, pq, v)`.
+#[cfg(test)]
+pub(crate) fn activsg200() -> anyhow::Result<(Vec<usize>, Vec<usize>, Vec<num_complex::Complex64>)>
+{
+    let text = std::fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/matrix/matpower/ACTIVSg200_V.csv"
+    ))?;
+    let (mut pv, mut pq, mut v) = (Vec::new(), Vec::new(), Vec::new());
+    for (i, line) in text.lines().skip(1).enumerate() {
+        let cols: Vec<&str> = line.split(',').collect();
+        match cols[1] {
+            "1" => pq.push(i),
+            "2" => pv.push(i),
+            _ => {}
+        }
+        let (vm, va): (f64, f64) = (cols[2].parse()?, cols[3].parse()?);
+        v.push(num_complex::Complex64::from_polar(vm, va.to_radians()));
+    }
+    Ok((pv, pq, v))
+}
@@ -0,0 +1,395 @@
+use crate::csc::compress;
+use crate::matrix::Matrix;
+use crate::powerflow::Case;
+use anyhow::{format_err, Result};
+use num_complex::Complex64;
+
+impl Case {
+    /// Builds the bus admittance matrix (p.u.) from the in-service
+    /// branches and the bus shunts.
+    pub fn ybus(&self) -> Result<Matrix<Complex64>> {
+        let n = self.bus.len();
+        let buses = self.branch_buses()?;
+
+        let mut t_i = Vec::with_capacity(4 * buses.len() + n);
+        let mut t_j = Vec::with_capacity(4 * buses.len() + n);
+        let mut t_x = Vec::with_capacity(4 * buses.len() + n);
+        let mut push = |i: usize, j: usize, x: Complex64| {
+            t_i.push(i);
+            t_j.push(j);
+            t_x.push(x);
+        };
+
+        for (br, &(f, t)) in self.branch.iter().zip(&buses).filter(|(br, _)| br.status) {
+            if br.r == 0.0 && br.x == 0.0 {
+                return Err(format_err!(
+                    "branch {}-{} has zero impedance",
+                    br.from,
+                    br.to
+                ));
+            }
+            let y_s = Complex64::new(br.r, br.x).inv();
+            let y_tt = y_s + Complex64::new(0.0, br.b / 2.0);
+            let tap = br.tap();
+            push(f, f, y_tt / tap.norm_sqr());
+            push(f, t, -y_s / tap.conj());
+            push(t, f, -y_s / tap);
+            push(t, t, y_tt);
+        }
+        for (i, bus) in self.bus.iter().enumerate() {
+            push(i, i, Complex64::new(bus.gs, bus.bs) / self.base_mva);
+        }
+
+        let (a_p, a_i, a_x) = compress(n, &t_i, &t_j, &t_x);
+        Ok((n, a_p, a_i, a_x))
+    }
+
+    /// Builds the DC power flow susceptance matrix (p.u.), ignoring
+    /// resistance, line charging and shunts. The matrix includes all
+    /// buses and is singular.
+    pub fn bbus(&self) -> Result<Matrix<f64>> {
+        let n = self.bus.len();
+        let buses = self.branch_buses()?;
+
+        let mut t_i = Vec::with_capacity(4 * buses.len());
+        let mut t_j = Vec::with_capacity(4 * buses.len());
+        let mut t_x = Vec::with_capacity(4 * buses.len());
+        for (br, &(f, t)) in self.branch.iter().zip(&buses).filter(|(br, _)| br.status) {
+            if br.x == 0.0 {
+                return Err(format_err!(
+                    "branch {}-{} has zero reactance",
+                    br.from,
+                    br.to
+                ));
+            }
+            let ratio = if br.ratio == 0.0 { 1.0 } else { br.ratio };
+            let b = 1.0 / (br.x * ratio);
+            for (i, j, x) in [(f, f, b), (f, t, -b), (t, f, -b), (t, t, b)] {
+                t_i.push(i);
+                t_j.push(j);
+                t_x.push(x);
+            }
+        }
+
+        let (a_p, a_i, a_x) = compress(n, &t_i, &t_j, &t_x);
+        Ok((n, a_p, a_i, a_x))
+    }
+
+    /// Scheduled bus power injections (p.u.), from the in-service
+    /// generators less the bus demand.
+    pub fn sbus(&self) -> Result<Vec<Complex64>> {
+        let index = self.bus_index()?;
+        let mut s: Vec<Complex64> = self
+            .bus
+            .iter()
+            .map(|bus| -Complex64::new(bus.pd, bus.qd))
+            .collect();
+        for gen in self.gen.iter().filter(|g| g.status) {
+            let i = index
+                .get(&gen.bus)
+                .ok_or_else(|| format_err!("unknown generator bus {}", gen.bus))?;
+            s[*i] += Complex64::new(gen.pg, gen.qg);
+        }
+        Ok(s.into_iter().map(|s| s / self.base_mva).collect())
+    }
+
+    /// Initial voltage profile, from the bus table with generator voltage
+    /// setpoints applied at PV and reference buses.
+    pub fn voltages(&self) -> Result<Vec<Complex64>> {
+        let index = self.bus_index()?;
+        let (ref_, pv, _) = self.bus_types()?;
+        let mut vm: Vec<f64> = self.bus.iter().map(|b| b.vm).collect();
+        for gen in self.gen.iter().filter(|g| g.status) {
+            let i = index[&gen.bus];
+            if ref_.contains(&i) || pv.contains(&i) {
+                vm[i] = gen.vg;
+            }
+        }
+        Ok(self
+            .bus
+            .iter()
+            .zip(vm)
+            .map(|(bus, vm)| Complex64::from_polar(vm, bus.va.to_radians()))
+            .collect())
+    }
+}
+
+/// Returns `A(keep, keep)`, with row and column `k` of the result taken
+/// from row and column `keep[k]` of `a`.
+pub(super) fn submatrix(a: &Matrix<f64>, keep: &[usize]) -> Matrix<f64> {
+    let (n, a_p, a_i, a_x) = a;
+    let mut index = vec![None; *n];
+    for (k, &i) in keep.iter().enumerate() {
+        index[i] = Some(k);
+    }
+    let (mut t_i, mut t_j, mut t_x) = (Vec::new(), Vec::new(), Vec::new());
+    for (j, jj) in index
+        .iter()
+        .enumerate()
+        .filter_map(|(j, jj)| Some((j, (*jj)?)))
+    {
+        for p in a_p[j]..a_p[j + 1] {
+            if let Some(ii) = index[a_i[p]] {
+                t_i.push(ii);
+                t_j.push(jj);
+                t_x.push(a_x[p]);
+            }
+        }
+    }
+    let (s_p, s_i, s_x) = compress(keep.len(), &t_i, &t_j, &t_x);
+    (keep.len(), s_p, s_i, s_x)
+}
+
+/// Bus complex power injections `V conj(Ybus V)` (p.u.).
+pub fn injections(ybus: &Matrix<Complex64>, v: &[Complex64]) -> Vec<Complex64> {
+    let (n, y_p, y_i, y_x) = ybus;
+    let mut i_bus = vec![Complex64::default(); *n];
+    for j in 0..*n {
+        for p in y_p[j]..y_p[j + 1] {
+            i_bus[y_i[p]] += y_x[p] * v[j];
+        }
+    }
+    v.iter().zip(i_bus).map(|(v, i)| v * i.conj()).collect()
+}
+
+/// Builds the polar power flow Jacobian
+///
+/// ```txt
+///     J = {dP/dVa, dP/dVm}
+///         {dQ/dVa, dQ/dVm}
+/// ```
+///
+/// at voltages `v`, with rows and columns ordered as the PV then PQ bus
+/// angles followed by the PQ bus magnitudes.
+pub fn jacobian(
+    ybus: &Matrix<Complex64>,
+    v: &[Complex64],
+    pv: &[usize],
+    pq: &[usize],
+) -> Matrix<f64> {
+    let mut jac = Jacobian::new(ybus, pv, pq);
+    jac.update(ybus, v);
+    (jac.n, jac.a_p, jac.a_i, jac.a_x)
+}
+
+/// Polar power flow Jacobian, as built by [`jacobian`], with a pattern
+/// that is fixed by `Ybus` so that only the values change between
+/// iterations.
+pub struct Jacobian {
+    /// Order of the matrix.
+    pub n: usize,
+    pub a_p: Vec<usize>,
+    pub a_i: Vec<usize>,
+    pub a_x: Vec<f64>,
+
+    /// Positions of the `dP/dVa`, `dP/dVm`, `dQ/dVa` and `dQ/dVm` terms of
+    /// each `Ybus` entry in `a_x`.
+    entries: Vec<[Option<usize>; 4]>,
+    /// Positions of the same terms on the diagonal of each bus.
+    diag: Vec<[Option<usize>; 4]>,
+}
+
+impl Jacobian {
+    /// Builds the pattern of the Jacobian, with zero values.
+    pub fn new(ybus: &Matrix<Complex64>, pv: &[usize], pq: &[usize]) -> Self {
+        let (n, y_p, y_i, _) = ybus;
+        let n = *n;
+
+        // Positions of the buses in the angle and magnitude blocks.
+        let mut va = vec![None; n];
+        let mut vm = vec![None; n];
+        for (k, &i) in pv.iter().chain(pq).enumerate() {
+            va[i] = Some(k);
+        }
+        for (k, &i) in pq.iter().enumerate() {
+            vm[i] = Some(pv.len() + pq.len() + k);
+        }
+        let dim = pv.len() + 2 * pq.len();
+        let blocks = |i: usize, j: usize| {
+            [
+                (va[i], va[j]),
+                (va[i], vm[j]),
+                (vm[i], va[j]),
+                (vm[i], vm[j]),
+            ]
+        };
+
+        let mut t_i = Vec::with_capacity(4 * y_i.len() + 4 * n);
+        let mut t_j = Vec::with_capacity(4 * y_i.len() + 4 * n);
+        let ybus_entries = (0..n).flat_map(|j| (y_p[j]..y_p[j + 1]).map(move |p| (y_i[p], j)));
+        for (i, j) in ybus_entries.chain((0..n).map(|i| (i, i))) {
+            for (r, c) in blocks(i, j) {
+                if let (Some(r), Some(c)) = (r, c) {
+                    t_i.push(r);
+                    t_j.push(c);
+                }
+            }
+        }
+        let (a_p, a_i, a_x) = compress(dim, &t_i, &t_j, &vec![0.0; t_i.len()]);
+
+        let find = |(r, c): (Option<usize>, Option<usize>)| {
+            let (r, c) = (r?, c?);
+            let k = a_i[a_p[c]..a_p[c + 1]].binary_search(&r).ok()?;
+            Some(a_p[c] + k)
+        };
+        let entries = (0..n)
+            .flat_map(|j| (y_p[j]..y_p[j + 1]).map(move |p| (y_i[p], j)))
+            .map(|(i, j)| blocks(i, j).map(find))
+            .collect();
+        let diag = (0..n).map(|i| blocks(i, i).map(find)).collect();
+
+        Self {
+            n: dim,
+            a_p,
+            a_i,
+            a_x,
+            entries,
+            diag,
+        }
+    }
+
+    /// Sets the values of the Jacobian at voltages `v`.
+    pub fn update(&mut self, ybus: &Matrix<Complex64>, v: &[Complex64]) {
+        let (n, y_p, y_i, y_x) = ybus;
+        self.a_x.fill(0.0);
+
+        // Bus current injections.
+        let mut i_bus = vec![Complex64::default(); *n];
+        for j in 0..*n {
+            for p in y_p[j]..y_p[j + 1] {
+                i_bus[y_i[p]] += y_x[p] * v[j];
+            }
+        }
+
+        let a_x = &mut self.a_x;
+        let mut add = |pos: &[Option<usize>; 4], ds_va: Complex64, ds_vm: Complex64| {
+            for (k, x) in pos.iter().zip([ds_va.re, ds_vm.re, ds_va.im, ds_vm.im]) {
+                if let Some(k) = k {
+                    a_x[*k] += x;
+                }
+            }
+        };
+
+        let j1 = Complex64::i();
+        for j in 0..*n {
+            let v_n = v[j] / v[j].norm();
+            for p in y_p[j]..y_p[j + 1] {
+                // dS_i/dVa_j and dS_i/dVm_j
+                let i = y_i[p];
+                let ds_va = -j1 * v[i] * (y_x[p] * v[j]).conj();
+                let ds_vm = v[i] * (y_x[p] * v_n).conj();
+                add(&self.entries[p], ds_va, ds_vm);
+            }
+            add(
+                &self.diag[j],
+                j1 * v[j] * i_bus[j].conj(),
+                i_bus[j].conj() * v_n,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{injections, jacobian};
+    use crate::matrix::{Dataset, Matrix};
+    use crate::powerflow::{activsg200, case9};
+    use anyhow::Result;
+    use num_complex::Complex64;
+
+    fn get<S: Copy + Default>((_, a_p, a_i, a_x): &Matrix<S>, i: usize, j: usize) -> S {
+        (a_p[j]..a_p[j + 1])
+            .find(|&p| a_i[p] == i)
+            .map(|p| a_x[p])
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_ybus() -> Result<()> {
+        let case = case9()?;
+        let y = case.ybus()?;
+        assert_eq!((y.0, y.1[9]), (9, 9 + 2 * 9));
+        assert!((get(&y, 0, 0) - Complex64::new(0.0, -17.3611)).norm() < 1e-4);
+        assert!((get(&y, 3, 3) - Complex64::new(3.3074, -39.3089)).norm() < 1e-4);
+        assert!((get(&y, 3, 4) - Complex64::new(-1.9422, 10.5107)).norm() < 1e-4);
+
+        let b = case.bbus()?;
+        assert!((get(&b, 0, 0) - 17.3611).abs() < 1e-4);
+        assert!((get(&b, 3, 3) - (1.0 / 0.0576 + 1.0 / 0.092 + 1.0 / 0.085)).abs() < 1e-12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jacobian() -> Result<()> {
+        let case = case9()?;
+        let y = case.ybus()?;
+        let (_, pv, pq) = case.bus_types()?;
+        let v: Vec<Complex64> = (0..9)
+            .map(|i| Complex64::from_polar(1.0 + 0.01 * i as f64, 0.02 * i as f64 - 0.05))
+            .collect();
+        let jac = jacobian(&y, &v, &pv, &pq);
+        assert_eq!(jac.0, 2 + 2 * 6);
+
+        // Compare with central differences of the injections.
+        let h = 1e-6;
+        let vars: Vec<(usize, bool)> = pv
+            .iter()
+            .chain(&pq)
+            .map(|&i| (i, true))
+            .chain(pq.iter().map(|&i| (i, false)))
+            .collect();
+        for (col, &(j, angle)) in vars.iter().enumerate() {
+            let step = |sign: f64| {
+                let mut v = v.clone();
+                let (vm, va) = v[j].to_polar();
+                v[j] = if angle {
+                    Complex64::from_polar(vm, va + sign * h)
+                } else {
+                    Complex64::from_polar(vm + sign * h, va)
+                };
+                injections(&y, &v)
+            };
+            let (s1, s0) = (step(1.0), step(-1.0));
+            for (row, &(i, p)) in vars.iter().enumerate() {
+                let ds = (s1[i] - s0[i]) / (2.0 * h);
+                let expected = if p { ds.re } else { ds.im };
+                assert!(
+                    (get(&jac, row, col) - expected).abs() < 1e-6,
+                    "J[{},{}] = {} expected {}",
+                    row,
+                    col,
+                    get(&jac, row, col),
+                    expected
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_jacobian_activsg200() -> Result<()> {
+        let ybus = Dataset::get("ACTIVSg200_Ybus")?.read::<Complex64>(true)?;
+        let (pv, pq, v) = activsg200()?;
+        let jac = jacobian(&ybus, &v, &pv, &pq);
+
+        let expected = Dataset::get("ACTIVSg200_Jac")?.read::<f64>(true)?;
+        assert_eq!(jac.0, expected.0);
+        for j in 0..jac.0 {
+            for p in expected.1[j]..expected.1[j + 1] {
+                let i = expected.2[p];
+                let x = get(&jac, i, j);
+                assert!(
+                    (x - expected.3[p]).abs() < 1e-9 * (1.0 + x.abs()),
+                    "J[{},{}] = {} expected {}",
+                    i,
+                    j,
+                    x,
+                    expected.3[p]
+                );
+            }
+            let nnz = |a: &Matrix<f64>| (a.1[j]..a.1[j + 1]).filter(|&p| a.3[p] != 0.0).count();
+            assert_eq!(nnz(&jac), nnz(&expected), "column {}", j);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::matrix::Matrix;
+use crate::powerflow::{injections, Case, Jacobian};
+use crate::FactorSolver;
+use anyhow::Result;
+use num_complex::Complex64;
+
+/// Newton-Raphson AC power flow in polar coordinates.
+///
+/// The Jacobian is factorized once and then refactorized with
+/// [`FactorSolver::refactor`] at each iteration, as its pattern does not
+/// change.
+pub struct NewtonRaphson {
+    /// Convergence tolerance on the largest power mismatch (p.u.).
+    pub tol: f64,
+
+    /// Maximum number of iterations.
+    pub max_it: usize,
+}
+
+impl Default for NewtonRaphson {
+    fn default() -> Self {
+        Self {
+            tol: 1e-8,
+            max_it: 10,
+        }
+    }
+}
+
+/// Power flow solution.
+#[derive(Debug, Clone)]
+pub struct Solution {
+    /// Bus voltages (p.u.).
+    pub v: Vec<Complex64>,
+    pub converged: bool,
+    /// Number of iterations taken.
+    pub iterations: usize,
+    /// Largest power mismatch at `v` (p.u.).
+    pub mismatch: f64,
+    /// Largest power mismatch at the initial voltages and after each
+    /// iteration.
+    pub history: Vec<f64>,
+}
+
+impl NewtonRaphson {
+    /// Solves the power flow of `case`, starting from [`Case::voltages`].
+    pub fn solve<F>(
+        &self,
+        solver: &dyn FactorSolver<usize, f64, F>,
+        case: &Case,
+    ) -> Result<Solution> {
+        let ybus = case.ybus()?;
+        let sbus = case.sbus()?;
+        let (_, pv, pq) = case.bus_types()?;
+        self.solve_ybus(solver, &ybus, &sbus, case.voltages()?, &pv, &pq)
+    }
+
+    /// Solves `V conj(Ybus V) = Sbus` at the PV and PQ buses, from the
+    /// initial voltages `v`.
+    pub fn solve_ybus<F>(
+        &self,
+        solver: &dyn FactorSolver<usize, f64, F>,
+        ybus: &Matrix<Complex64>,
+        sbus: &[Complex64],
+        mut v: Vec<Complex64>,
+        pv: &[usize],
+        pq: &[usize],
+    ) -> Result<Solution> {
+        let npvpq = pv.len() + pq.len();
+        let mut va: Vec<f64> = v.iter().map(|v| v.arg()).collect();
+        let mut vm: Vec<f64> = v.iter().map(|v| v.norm()).collect();
+
+        let mut dx = mismatch(ybus, sbus, &v, pv, pq);
+        let mut norm = max_abs(&dx);
+        let mut history = vec![norm];
+
+        let mut jac = Jacobian::new(ybus, pv, pq);
+        let mut f = None;
+        let mut iterations = 0;
+        while norm >= self.tol && iterations < self.max_it {
+            iterations += 1;
+
+            jac.update(ybus, &v);
+            match f.as_mut() {
+                None => f = Some(solver.factor(jac.n, &jac.a_i, &jac.a_p, &jac.a_x)?),
+                Some(f) => solver.refactor(f, jac.n, &jac.a_i, &jac.a_p, &jac.a_x)?,
+            }
+            solver.solve(f.as_ref().unwrap(), &mut dx, false)?;
+
+            for (&i, dx) in pv.iter().chain(pq).zip(&dx) {
+                va[i] -= dx;
+            }
+            for (&i, dx) in pq.iter().zip(&dx[npvpq..]) {
+                vm[i] -= dx;
+            }
+            for (v, (&vm, &va)) in v.iter_mut().zip(vm.iter().zip(&va)) {
+                *v = Complex64::from_polar(vm, va);
+            }
+
+            dx = mismatch(ybus, sbus, &v, pv, pq);
+            norm = max_abs(&dx);
+            history.push(norm);
+        }
+
+        Ok(Solution {
+            v,
+            converged: norm < self.tol,
+            iterations,
+            mismatch: norm,
+            history,
+        })
+    }
+}
+
+/// Real power mismatches at the PV and PQ buses followed by the reactive
+/// power mismatches at the PQ buses.
+pub(super) fn mismatch(
+    ybus: &Matrix<Complex64>,
+    sbus: &[Complex64],
+    v: &[Complex64],
+    pv: &[usize],
+    pq: &[usize],
+) -> Vec<f64> {
+    let ds: Vec<Complex64> = injections(ybus, v)
+        .iter()
+        .zip(sbus)
+        .map(|(s, s0)| s - s0)
+        .collect();
+    pv.iter()
+        .chain(pq)
+        .map(|&i| ds[i].re)
+        .chain(pq.iter().map(|&i| ds[i].im))
+        .collect()
+}
+
+pub(super) fn max_abs(x: &[f64]) -> f64 {
+    x.iter().fold(0.0, |m: f64, x| m.max(x.abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NewtonRaphson;
+    use crate::matrix::Dataset;
+    use crate::powerflow::{activsg200, case9, injections, CASE9_V};
+    use crate::FactorSolver;
+    use anyhow::Result;
+    use num_complex::Complex64;
+
+    fn case9_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+        let case = case9()?;
+        let sol = NewtonRaphson::default().solve(solver, &case)?;
+        assert!(sol.converged, "mismatch {}", sol.mismatch);
+        assert!(sol.iterations <= 5, "{} iterations", sol.iterations);
+        assert_eq!(sol.history.len(), sol.iterations + 1);
+
+        for (i, (v, &(vm, va))) in sol.v.iter().zip(&CASE9_V).enumerate() {
+            assert!((v.norm() - vm).abs() < 1e-4, "|V{}| = {}", i + 1, v.norm());
+            assert!(
+                (v.arg().to_degrees() - va).abs() < 1e-4,
+                "angle V{} = {}",
+                i + 1,
+                v.arg().to_degrees()
+            );
+        }
+        Ok(())
+    }
+
+    /// Solves ACTIVSg200 from a flat start, with the injections of its
+    /// solved voltages, and compares with the solved voltages.
+    fn activsg200_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+        let ybus = Dataset::get("ACTIVSg200_Ybus")?.read::<Complex64>(true)?;
+        let (pv, pq, v) = activsg200()?;
+        let sbus = injections(&ybus, &v);
+        let v0 = v
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                if pq.contains(&i) {
+                    Complex64::new(1.0, 0.0)
+                } else {
+                    Complex64::new(v.norm(), 0.0)
+                }
+            })
+            .collect();
+
+        let sol = NewtonRaphson::default().solve_ybus(solver, &ybus, &sbus, v0, &pv, &pq)?;
+        assert!(sol.converged, "mismatch {}", sol.mismatch);
+        assert!(sol.iterations <= 6, "{} iterations", sol.iterations);
+        for (i, (x, v)) in sol.v.iter().zip(&v).enumerate() {
+            assert!((x - v).norm() < 1e-8, "V{} = {} expected {}", i + 1, x, v);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "snlu")]
+    fn test_case9_snlu() -> Result<()> {
+        case9_test(&crate::snlu::SNLU::default())
+    }
+
+    #[test]
+    #[cfg(feature = "snlu")]
+    fn test_activsg200_snlu() -> Result<()> {
+        activsg200_test(&crate::snlu::SNLU::default())
+    }
+
+    #[test]
+    #[cfg(feature = "rlu")]
+    fn test_case9_rlu() -> Result<()> {
+        case9_test(&crate::rlu::RLU::default())
+    }
+
+    #[test]
+    #[cfg(feature = "rlu")]
+    fn test_activsg200_rlu() -> Result<()> {
+        activsg200_test(&crate::rlu::RLU::default())
+    }
+
+    #[test]
+    #[cfg(feature = "klu")]
+    fn test_case9_klu() -> Result<()> {
+        case9_test(&crate::klu::KLU::default())
+    }
+}
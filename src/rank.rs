@@ -0,0 +1,237 @@
+//! Structural rank and singularity detection.
+//!
+//! The structural rank of a matrix is the size of a maximum matching
+//! of the bipartite graph of its nonzero pattern. It is an upper bound
+//! on the numerical rank, so a square matrix with structural rank
+//! less than `n` is singular for all numerical values.
+
+use anyhow::Result;
+use num_traits::ToPrimitive;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error returned when a matrix is found to be singular.
+///
+/// Lists the rows and columns without a pivot, e.g. the equations
+/// and variables of a disconnected network island.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SingularMatrix {
+    /// Structural or numerical rank.
+    pub rank: usize,
+    /// Rows that could not be matched or pivoted.
+    pub rows: Vec<usize>,
+    /// Columns that could not be matched or pivoted. May be empty if
+    /// the solver can not identify them.
+    pub cols: Vec<usize>,
+    /// True if singularity was detected from the nonzero pattern alone.
+    pub structural: bool,
+}
+
+impl Display for SingularMatrix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "matrix is {} singular (rank {}): rows {:?}, columns {:?}",
+            if self.structural {
+                "structurally"
+            } else {
+                "numerically"
+            },
+            self.rank,
+            self.rows,
+            self.cols
+        )
+    }
+}
+
+impl Error for SingularMatrix {}
+
+/// Maximum matching of the rows and columns of an `m x n` matrix.
+///
+/// Returns `(row_match, col_match)` where `row_match[i]` is the column
+/// matched to row `i` and `col_match[j]` is the row matched to column `j`.
+pub fn max_matching<I: ToPrimitive>(
+    m: usize,
+    n: usize,
+    a_i: &[I],
+    a_p: &[I],
+) -> (Vec<Option<usize>>, Vec<Option<usize>>) {
+    let a_p: Vec<usize> = a_p.iter().map(|p| p.to_usize().unwrap()).collect();
+    let a_i: Vec<usize> = a_i[..a_p[n]]
+        .iter()
+        .map(|i| i.to_usize().unwrap())
+        .collect();
+
+    let mut row_match = vec![None; m];
+    let mut cheap = a_p[..n].to_vec();
+    let mut w = vec![usize::MAX; n];
+    let mut js = vec![0; n];
+    let mut is = vec![0; n];
+    let mut ps = vec![0; n];
+
+    for k in 0..n {
+        // Depth-first search for an augmenting path starting at column k.
+        let mut found = false;
+        let mut head = 0;
+        js[0] = k;
+        loop {
+            let j = js[head];
+            if w[j] != k {
+                w[j] = k;
+                let mut p = cheap[j];
+                while p < a_p[j + 1] && !found {
+                    found = row_match[a_i[p]].is_none();
+                    if found {
+                        is[head] = a_i[p];
+                    }
+                    p += 1;
+                }
+                cheap[j] = p;
+                if found {
+                    break;
+                }
+                ps[head] = a_p[j];
+            }
+            let mut p = ps[head];
+            while p < a_p[j + 1] {
+                let i = a_i[p];
+                let jm: usize = row_match[i].unwrap();
+                if w[jm] != k {
+                    ps[head] = p + 1;
+                    is[head] = i;
+                    head += 1;
+                    js[head] = jm;
+                    break;
+                }
+                p += 1;
+            }
+            if p == a_p[j + 1] {
+                if head == 0 {
+                    break;
+                }
+                head -= 1;
+            }
+        }
+        if found {
+            for h in (0..=head).rev() {
+                row_match[is[h]] = Some(js[h]);
+            }
+        }
+    }
+
+    let mut col_match = vec![None; n];
+    for (i, j) in row_match.iter().enumerate() {
+        if let Some(j) = j {
+            col_match[*j] = Some(i);
+        }
+    }
+    (row_match, col_match)
+}
+
+/// Returns the structural rank of an `m x n` matrix.
+pub fn structural_rank<I: ToPrimitive>(m: usize, n: usize, a_i: &[I], a_p: &[I]) -> usize {
+    let (_, col_match) = max_matching(m, n, a_i, a_p);
+    col_match.iter().filter(|i| i.is_some()).count()
+}
+
+/// Returns a [`SingularMatrix`] error if the `n x n` matrix is
+/// structurally singular.
+pub fn check_structural_rank<I: ToPrimitive>(n: usize, a_i: &[I], a_p: &[I]) -> Result<()> {
+    let (row_match, col_match) = max_matching(n, n, a_i, a_p);
+    let rank = col_match.iter().filter(|i| i.is_some()).count();
+    if rank < n {
+        let unmatched = |v: &[Option<usize>]| {
+            v.iter()
+                .enumerate()
+                .filter(|(_, k)| k.is_none())
+                .map(|(i, _)| i)
+                .collect()
+        };
+        return Err(SingularMatrix {
+            rank,
+            rows: unmatched(&row_match),
+            cols: unmatched(&col_match),
+            structural: true,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Returns a numerically [`SingularMatrix`] error if the modulus of any
+/// pivot of a factorization of an `n x n` matrix is not larger than
+/// `n * eps * a_max`, where `a_max` is the largest modulus of the matrix,
+/// or is not finite. `pivots` yields the row, column and modulus of each pivot.
+pub fn check_pivots(
+    n: usize,
+    a_max: f64,
+    pivots: impl Iterator<Item = (usize, usize, f64)>,
+) -> Result<()> {
+    let tol = n as f64 * f64::EPSILON * a_max;
+    let (rows, cols): (Vec<usize>, Vec<usize>) = pivots
+        .filter(|&(_, _, u)| !u.is_finite() || u <= tol)
+        .map(|(i, j, _)| (i, j))
+        .unzip();
+    if !rows.is_empty() {
+        return Err(SingularMatrix {
+            rank: n - rows.len(),
+            rows,
+            cols,
+            structural: false,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_pivots, check_structural_rank, structural_rank, SingularMatrix};
+
+    #[test]
+    fn test_structural_rank() {
+        // Columns 1 and 2 only have entries in row 1, row 2 is empty.
+        //
+        //     {1, , , 1}
+        //     { ,1,1,  }
+        //     { , , ,  }
+        //     {1, , , 1}
+        let a_i: Vec<usize> = vec![0, 3, 1, 1, 0, 3];
+        let a_p: Vec<usize> = vec![0, 2, 3, 4, 6];
+
+        assert_eq!(structural_rank(4, 4, &a_i, &a_p), 3);
+
+        let err = check_structural_rank(4, &a_i, &a_p).unwrap_err();
+        let err = err.downcast_ref::<SingularMatrix>().unwrap();
+        assert_eq!(err.rank, 3);
+        assert_eq!(err.rows, vec![2]);
+        assert_eq!(err.cols.len(), 1);
+        assert!(err.cols[0] == 1 || err.cols[0] == 2);
+    }
+
+    #[test]
+    fn test_augmenting_path() {
+        // A greedy matching of column 0 to row 0 must be undone.
+        //
+        //     {1,1}
+        //     {1, }
+        let a_i: Vec<usize> = vec![0, 1, 0];
+        let a_p: Vec<usize> = vec![0, 2, 3];
+
+        assert_eq!(structural_rank(2, 2, &a_i, &a_p), 2);
+        assert!(check_structural_rank(2, &a_i, &a_p).is_ok());
+    }
+
+    #[test]
+    fn test_check_pivots() {
+        let pivots = [(0, 1, 2.0), (1, 0, 1e-17), (2, 2, f64::NAN)];
+        assert!(check_pivots(3, 2.0, pivots[..1].iter().copied()).is_ok());
+
+        let err = check_pivots(3, 2.0, pivots.iter().copied()).unwrap_err();
+        let err = err.downcast_ref::<SingularMatrix>().unwrap();
+        assert_eq!(err.rank, 1);
+        assert_eq!(err.rows, vec![1, 2]);
+        assert_eq!(err.cols, vec![0, 2]);
+        assert!(!err.structural);
+    }
+}
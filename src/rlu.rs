@@ -1,9 +1,28 @@
 use anyhow::{format_err, Result};
 
-use crate::{FactorSolver, Solver};
+use crate::batch::try_map;
+use crate::det::permutation_sign;
+use crate::rank::{check_pivots, check_structural_rank, SingularMatrix};
+use crate::selinv::{solve_entry, Takahashi};
+use crate::triangular::{ColVecs, Columns, Diag};
+use crate::{
+    assert_send_sync, BatchFactorSolver, FactorSolver, LogDet, SelectedInversion, SelectedSolver,
+    Solver, SparseSolver, SparseVec, Workspace,
+};
 use num_traits::NumAssignOps;
 
-use rlu::{lsolve, ltsolve, solve, usolve, utsolve, Int, Matrix, Scalar};
+use rlu::{lsolve, ltsolve, usolve, utsolve, Int, Matrix, Scalar};
+
+/// Factors `(cp, L, U, rp, Ut)` from [`RLU`], with column ordering `cp` and
+/// row `i` of the matrix in row `rp[i]` of `L U`. `Ut` holds the rows of `U`,
+/// diagonal first, for row access to `U`.
+pub type RLUFactor<I, S> = (
+    Vec<I>,
+    Matrix<I, S>,
+    Matrix<I, S>,
+    Vec<Option<usize>>,
+    Matrix<usize, S>,
+);
 
 #[derive(Default)]
 pub struct RLU {
@@ -13,7 +32,7 @@ pub struct RLU {
 impl<I, S> Solver<I, S> for RLU
 where
     I: Int + NumAssignOps,
-    S: Scalar,
+    S: Scalar<Norm = f64> + Default,
 {
     fn solve(
         &self,
@@ -24,67 +43,362 @@ where
         b: &mut [S],
         trans: bool,
     ) -> Result<()> {
-        let (p, _p_inv, _info) = amd::order::<I>(I::from_usize(n), &a_p, &a_i, &self.control)
-            .map_err(|st| format_err!("amd status: {:?}", st))?;
+        check_structural_rank(n, a_i, a_p)?;
 
-        solve(n, &a_i, &a_p, &a_x, Some(&p), b, trans);
+        let (p, _p_inv, _info) = amd::order::<I>(I::from_usize(n), a_p, a_i, &self.control)
+            .map_err(|st| format_err!("amd status: {:?}", st))?;
 
-        Ok(())
+        let f = numeric(n, a_i, a_p, a_x, p)?;
+        FactorSolver::solve(self, &f, b, trans)
     }
 }
 
-impl<I, S> FactorSolver<I, S, (Vec<I>, Matrix<I, S>, Matrix<I, S>, Vec<Option<usize>>)> for RLU
+impl<I, S> FactorSolver<I, S, RLUFactor<I, S>> for RLU
 where
     I: Int + NumAssignOps,
-    S: Scalar,
+    S: Scalar<Norm = f64> + Default,
 {
-    fn factor(
+    fn factor(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<RLUFactor<I, S>> {
+        check_structural_rank(n, a_i, a_p)?;
+
+        let (cp, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), a_p, a_i, &self.control)
+            .map_err(|st| format_err!("amd status: {:?}", st))?;
+
+        numeric(n, a_i, a_p, a_x, cp)
+    }
+
+    /// Reuses the column ordering of `f`.
+    fn refactor(
         &self,
+        f: &mut RLUFactor<I, S>,
         n: usize,
         a_i: &[I],
         a_p: &[I],
         a_x: &[S],
-    ) -> Result<(Vec<I>, Matrix<I, S>, Matrix<I, S>, Vec<Option<usize>>)> {
-        let (cp, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), &a_p, &a_i, &self.control)
-            .map_err(|st| format_err!("amd status: {:?}", st))?;
+    ) -> Result<()> {
+        *f = numeric(n, a_i, a_p, a_x, f.0.clone())?;
+        Ok(())
+    }
 
-        let (l_mat, u_mat, rp) = rlu::lu_decomposition(n, &a_i, &a_p, &a_x, Some(&cp), true);
-        Ok((cp, l_mat, u_mat, rp))
+    fn solve(&self, f: &RLUFactor<I, S>, b: &mut [S], trans: bool) -> Result<()> {
+        self.solve_with(f, b, trans, &mut Workspace::new())
     }
 
-    fn solve(
+    fn solve_with(
         &self,
-        f: &(Vec<I>, Matrix<I, S>, Matrix<I, S>, Vec<Option<usize>>),
+        f: &RLUFactor<I, S>,
         b: &mut [S],
         trans: bool,
+        ws: &mut Workspace<S>,
     ) -> Result<()> {
-        let n = b.len();
-        let (cp, l_mat, u_mat, rp) = f;
+        let (cp, l_mat, u_mat, rp, _) = f;
+        let n = rp.len();
+        if n == 0 || !b.len().is_multiple_of(n) {
+            return Err(format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                n
+            ));
+        }
+
+        let x = ws.slice(n);
+        for b in b.chunks_exact_mut(n) {
+            if !trans {
+                for (i, r) in rp.iter().enumerate() {
+                    x[r.unwrap()] = b[i]; // x = P*b
+                }
+                lsolve(l_mat, x);
+                usolve(u_mat, x);
+                for (k, c) in cp.iter().enumerate() {
+                    b[c.to_index()] = x[k]; // b = Q*x
+                }
+            } else {
+                for (k, c) in cp.iter().enumerate() {
+                    x[k] = b[c.to_index()]; // x = Q'*b
+                }
+                utsolve(u_mat, x);
+                ltsolve(l_mat, x);
+                for (i, r) in rp.iter().enumerate() {
+                    b[i] = x[r.unwrap()]; // b = P'*x
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<I, S> BatchFactorSolver<I, S, RLUFactor<I, S>> for RLU
+where
+    I: Int + NumAssignOps + Send + Sync,
+    S: Scalar<Norm = f64> + Default + Send + Sync,
+{
+    fn factor_batch(
+        &self,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[&[S]],
+    ) -> Result<Vec<RLUFactor<I, S>>> {
+        check_structural_rank(n, a_i, a_p)?;
+
+        let (cp, _p_inv, _info) = amd::order::<I>(I::from(n).unwrap(), a_p, a_i, &self.control)
+            .map_err(|st| format_err!("amd status: {:?}", st))?;
+
+        try_map(a_x, |a_x| numeric(n, a_i, a_p, a_x, cp.clone()))
+    }
+}
+
+/// Numeric factorization with the column ordering `cp`.
+fn numeric<I, S>(n: usize, a_i: &[I], a_p: &[I], a_x: &[S], cp: Vec<I>) -> Result<RLUFactor<I, S>>
+where
+    I: Int + NumAssignOps,
+    S: Scalar<Norm = f64>,
+{
+    let (l_mat, u_mat, rp) = rlu::lu_decomposition(n, a_i, a_p, a_x, Some(&cp), true);
+
+    // Rows that were never selected as pivots.
+    let rows: Vec<usize> = (0..n).filter(|&i| rp[i].is_none()).collect();
+    if !rows.is_empty() {
+        return Err(SingularMatrix {
+            rank: n - rows.len(),
+            rows,
+            cols: Vec::default(),
+            structural: false,
+        }
+        .into());
+    }
+
+    // The pivot is last in each column of U.
+    let mut p = vec![0; n];
+    for (i, r) in rp.iter().enumerate() {
+        p[r.unwrap()] = i;
+    }
+    let a_max = a_x.iter().fold(0.0, |m: f64, x| m.max(x.norm()));
+    check_pivots(
+        n,
+        a_max,
+        u_mat
+            .iter()
+            .enumerate()
+            .map(|(k, u)| (p[k], cp[k].to_index(), u.last().map_or(0.0, |u| u.1.norm()))),
+    )?;
+
+    // Rows of U, with the pivot first.
+    let mut ut_mat: Matrix<usize, S> = vec![Vec::default(); n];
+    for (j, u) in u_mat.iter().enumerate() {
+        if let Some((&(_, u_jj), u)) = u.split_last() {
+            ut_mat[j].push((j, u_jj));
+            for &(i, x) in u {
+                ut_mat[i.to_index()].push((j, x));
+            }
+        }
+    }
+
+    Ok((cp, l_mat, u_mat, rp, ut_mat))
+}
+
+impl<I, S> SparseSolver<S, RLUFactor<I, S>> for RLU
+where
+    I: Int,
+    S: Scalar<Norm = f64> + crate::Scalar,
+{
+    fn solve_sparse(&self, f: &RLUFactor<I, S>, b_i: &[usize], b_x: &[S]) -> Result<SparseVec<S>> {
+        self.solve_sparse_with(f, b_i, b_x, &mut Workspace::new())
+    }
+
+    fn solve_sparse_with(
+        &self,
+        f: &RLUFactor<I, S>,
+        b_i: &[usize],
+        b_x: &[S],
+        ws: &mut Workspace<S>,
+    ) -> Result<SparseVec<S>> {
+        let (cp, l_mat, u_mat, rp, _) = f;
+        let n = rp.len();
+        if b_i.len() != b_x.len() {
+            return Err(format_err!(
+                "b_i length {} must be {}",
+                b_i.len(),
+                b_x.len()
+            ));
+        }
+        if let Some(i) = b_i.iter().find(|&&i| i >= n) {
+            return Err(format_err!("index {} out of range", i));
+        }
+        let l = ColVecs {
+            cols: l_mat,
+            diag: Diag::Unit,
+        };
+        let u = ColVecs {
+            cols: u_mat,
+            diag: Diag::Last,
+        };
+
+        let (x, marked) = ws.sparse(n);
+
+        // x = P*b
+        let c_i: Vec<usize> = b_i.iter().map(|&i| rp[i].unwrap()).collect();
+        for (&k, &b) in c_i.iter().zip(b_x) {
+            x[k] += b;
+        }
+
+        let pattern = l.reach(&c_i, marked);
+        l.solve(&pattern, x); // x = L\x
+        let pattern = u.reach(&pattern, marked);
+        u.solve(&pattern, x); // x = U\x
+
+        // b = Q*x
+        let mut entries: Vec<(usize, S)> = pattern
+            .iter()
+            .map(|&k| (cp[k].to_index(), std::mem::take(&mut x[k])))
+            .collect();
+        entries.sort_unstable_by_key(|&(i, _)| i);
+
+        Ok(SparseVec {
+            n,
+            indices: entries.iter().map(|&(i, _)| i).collect(),
+            values: entries.iter().map(|&(_, x)| x).collect(),
+        })
+    }
+}
+
+impl<I, S> SelectedSolver<S, RLUFactor<I, S>> for RLU
+where
+    I: Int,
+    S: Scalar<Norm = f64> + crate::Scalar,
+{
+    fn solve_selected(&self, f: &RLUFactor<I, S>, b: &[S], rows: &[usize]) -> Result<Vec<S>> {
+        let (cp, l_mat, _, rp, ut_mat) = f;
+        let n = rp.len();
+        if b.len() != n {
+            return Err(format_err!("b length {} must be {}", b.len(), n));
+        }
+        if let Some(r) = rows.iter().find(|&&r| r >= n) {
+            return Err(format_err!("row {} out of range", r));
+        }
+        let mut qinv = vec![0; n];
+        for (k, c) in cp.iter().enumerate() {
+            qinv[c.to_index()] = k;
+        }
 
         let mut x = vec![S::zero(); n];
-        for i in 0..n {
-            x[rp[i].unwrap()] = b[i];
+        for (i, r) in rp.iter().enumerate() {
+            x[r.unwrap()] = b[i]; // x = P*b
         }
+        lsolve(l_mat, &mut x); // x = L\x
 
-        if !trans {
-            lsolve(&l_mat, &mut x);
-            usolve(&u_mat, &mut x);
-        } else {
-            ltsolve(&l_mat, &mut x);
-            utsolve(&u_mat, &mut x);
+        // Row access to U is needed to prune the backward solve.
+        let (_, ut) = factors(l_mat, ut_mat);
+        let wanted: Vec<usize> = rows.iter().map(|&r| qinv[r]).collect();
+        let pattern = ut.reach(&wanted, &mut vec![false; n]);
+        ut.solve_transposed(&pattern, &mut x); // x = U\x
+
+        Ok(wanted.iter().map(|&k| x[k]).collect())
+    }
+}
+
+impl<I, S> SelectedInversion<S, RLUFactor<I, S>> for RLU
+where
+    I: Int,
+    S: Scalar<Norm = f64> + crate::Scalar,
+{
+    fn inverse_selected(&self, f: &RLUFactor<I, S>) -> Result<(Vec<usize>, Vec<usize>, Vec<S>)> {
+        let (cp, _, _, rp, _) = f;
+        let z = takahashi(f);
+
+        // P A Q = L U, so inv(A)(q[a], p[b]) = inv(LU)(a, b).
+        let q: Vec<usize> = cp.iter().map(|c| c.to_index()).collect();
+        let mut p = vec![0; rp.len()];
+        for (i, r) in rp.iter().enumerate() {
+            p[r.unwrap()] = i;
         }
+        Ok(z.to_csc(&q, &p))
+    }
+
+    fn inverse_diag(&self, f: &RLUFactor<I, S>) -> Result<Vec<S>> {
+        let (cp, l_mat, _, rp, ut_mat) = f;
+        let n = rp.len();
+        let (l, ut) = factors(l_mat, ut_mat);
+        let z = takahashi(f);
 
-        for i in 0..n {
-            b[cp[i].to_index()] = x[i];
+        let mut qinv = vec![0; n];
+        for (k, c) in cp.iter().enumerate() {
+            qinv[c.to_index()] = k;
         }
+        let mut x = vec![S::zero(); n];
+        let mut marked = vec![false; n];
+        Ok((0..n)
+            .map(|i| {
+                let (a, b) = (qinv[i], rp[i].unwrap());
+                // Pivoting may move the diagonal off the pattern.
+                z.get(a, b)
+                    .unwrap_or_else(|| solve_entry(&l, &ut, a, b, &mut x, &mut marked))
+            })
+            .collect())
+    }
+}
 
-        Ok(())
+/// Returns `L` and the transpose of `U`, with the rows of `U` as columns.
+fn factors<'a, I, S>(
+    l_mat: &'a Matrix<I, S>,
+    ut_mat: &'a Matrix<usize, S>,
+) -> (ColVecs<'a, I, S>, ColVecs<'a, usize, S>) {
+    let l = ColVecs {
+        cols: l_mat,
+        diag: Diag::Unit,
+    };
+    let ut = ColVecs {
+        cols: ut_mat,
+        diag: Diag::First,
+    };
+    (l, ut)
+}
+
+fn takahashi<I, S>(f: &RLUFactor<I, S>) -> Takahashi<S>
+where
+    I: Int,
+    S: Scalar<Norm = f64> + crate::Scalar,
+{
+    let (_, l_mat, _, rp, ut_mat) = f;
+    let (l, ut) = factors(l_mat, ut_mat);
+    let d: Vec<S> = (0..rp.len()).map(|k| ut.diag(k)).collect();
+    Takahashi::new(rp.len(), &l, &ut, &d)
+}
+
+impl<I, S> LogDet<S> for RLUFactor<I, S>
+where
+    I: Int,
+    S: Scalar<Norm = f64> + crate::Scalar,
+{
+    fn log_det(&self) -> Result<(S, f64)> {
+        let (cp, _, u_mat, rp, _) = self;
+
+        // P A Q = L U, where L has a unit diagonal.
+        let rp: Vec<usize> = rp.iter().map(|r| r.unwrap()).collect();
+        let mut sign = S::from_f64(permutation_sign(&rp) * permutation_sign(cp));
+        let mut ln = 0.0;
+        for u in u_mat {
+            let u_kk = u.last().map_or(S::zero(), |u| u.1);
+            let u_abs = crate::Scalar::norm(u_kk);
+            if u_abs == 0.0 {
+                return Ok((S::zero(), f64::NEG_INFINITY));
+            }
+            sign *= u_kk / S::from_f64(u_abs);
+            ln += u_abs.ln();
+        }
+        Ok((sign, ln))
     }
 }
 
+const _: () = assert_send_sync::<RLU>();
+const _: () = assert_send_sync::<RLUFactor<usize, f64>>();
+
 #[cfg(test)]
 mod tests {
     use super::RLU;
+    #[cfg(feature = "matrix")]
+    use crate::matrix::Dataset;
     use crate::test;
     use anyhow::Result;
 
@@ -107,4 +421,97 @@ mod tests {
         let solver = RLU::default();
         test::test_factor_solver_bbus(&solver, 1, 1e-11)
     }
+
+    #[test]
+    fn singular_test() -> Result<()> {
+        let solver = RLU::default();
+        test::singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn numerically_singular_test() -> Result<()> {
+        let solver = RLU::default();
+        test::numerically_singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn rounded_singular_test() -> Result<()> {
+        let solver = RLU::default();
+        test::rounded_singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn log_det_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_log_det_test(&solver)
+    }
+
+    #[test]
+    fn pivoting_log_det_test() -> Result<()> {
+        let solver = RLU::default();
+        test::pivoting_log_det_test(&solver)
+    }
+
+    #[test]
+    fn multi_rhs_test() -> Result<()> {
+        let solver = RLU::default();
+        test::pivoting_multi_rhs_test(&solver)
+    }
+
+    #[test]
+    fn sparse_solve_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_sparse_solve_test(&solver)
+    }
+
+    #[test]
+    fn selected_solve_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_selected_solve_test(&solver)
+    }
+
+    #[test]
+    fn pivoting_selected_inverse_test() -> Result<()> {
+        let solver = RLU::default();
+        test::pivoting_selected_inverse_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_selected_inverse() -> Result<()> {
+        let solver = RLU::default();
+        let (n, a_p, a_i, a_x) = Dataset::get("ACTIVSg200_Bbus")?.read::<f64>(true)?;
+        test::test_selected_inverse(&solver, n, &a_i, &a_p, &a_x, 1e-9)
+    }
+
+    #[test]
+    fn refactor_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_refactor_test(&solver)
+    }
+
+    #[test]
+    fn workspace_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_workspace_test(&solver)
+    }
+
+    #[test]
+    fn batch_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_batch_test(&solver)
+    }
+
+    #[test]
+    fn schur_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_schur_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn solve_par_test() -> Result<()> {
+        let solver = RLU::default();
+        test::simple_solve_par_test(&solver)
+    }
 }
@@ -1,7 +1,18 @@
-use crate::{FactorSolver, Solver};
-use anyhow::Result;
-use rsparse::data::{Nmrc, Symb};
-use rsparse::{lsolve, ltsolve, lu, sqr, usolve, utsolve};
+use crate::det::permutation_sign;
+use crate::rank::{check_pivots, check_structural_rank, SingularMatrix};
+use crate::selinv::{solve_entry, Takahashi};
+use crate::triangular::{Columns, Diag, Triangular};
+use crate::{
+    assert_send_sync, FactorSolver, LeastSquaresSolver, LogDet, SelectedInversion, SelectedSolver,
+    Solver, SparseSolver, SparseVec, Workspace,
+};
+use anyhow::{format_err, Result};
+use rsparse::data::{Nmrc, Sprs, Symb};
+use rsparse::{lsolve, ltsolve, sqr, usolve, utsolve};
+
+/// Factors `(n, S, N, Ut)` from [`RSparse`], where `Ut = (p, i, x)` is the
+/// transpose of `N.u` in compressed-column form, kept for row access to `U`.
+pub type RSparseFactor = (usize, Symb, Nmrc, (Vec<usize>, Vec<usize>, Vec<f64>));
 
 pub struct RSparse {
     /// - -1:natural,
@@ -28,11 +39,20 @@ impl Solver<usize, f64> for RSparse {
         a_p: &[usize],
         a_x: &[f64],
         b: &mut [f64],
-        _trans: bool,
+        trans: bool,
     ) -> Result<()> {
+        let f = FactorSolver::factor(self, n, a_i, a_p, a_x)?;
+        FactorSolver::solve(self, &f, b, trans)
+    }
+}
+
+impl FactorSolver<usize, f64, RSparseFactor> for RSparse {
+    fn factor(&self, n: usize, a_i: &[usize], a_p: &[usize], a_x: &[f64]) -> Result<RSparseFactor> {
+        check_structural_rank(n, a_i, a_p)?;
+
         let nzmax = a_x.len();
 
-        let mut a = rsparse::data::Sprs::new();
+        let mut a = Sprs::new();
         a.nzmax = usize::max(nzmax, 1);
         a.m = n;
         a.n = n;
@@ -40,57 +60,435 @@ impl Solver<usize, f64> for RSparse {
         a.i = a_i.to_vec();
         a.x = a_x.to_vec();
 
-        let mut b_vec = b.to_vec();
-        rsparse::lusol(&a, &mut b_vec, self.order, self.tol);
-        b.clone_from_slice(&b_vec);
+        // Ordering and symbolic analysis, then numeric LU factorization.
+        let s = sqr(&a, self.order, false);
+        let n = lu(&a, &s, self.tol)?;
+
+        // The diagonal entry is last in each column of U.
+        let mut p = vec![0; a.n];
+        for i in 0..a.n {
+            p[perm_at(&n.pinv, i)] = i;
+        }
+        let a_max = a_x.iter().fold(0.0, |m: f64, x| m.max(x.abs()));
+        check_pivots(
+            a.n,
+            a_max,
+            (0..a.n).map(|k| {
+                (
+                    p[k],
+                    perm_at(&s.q, k),
+                    n.u.x[n.u.p[k + 1] as usize - 1].abs(),
+                )
+            }),
+        )?;
+
+        let u = Triangular {
+            p: &n.u.p,
+            i: &n.u.i,
+            x: &n.u.x,
+            diag: Diag::Last,
+        };
+        let ut = u.transpose(a.n);
+
+        Ok((a.n, s, n, ut))
+    }
+
+    fn solve(&self, f: &RSparseFactor, b: &mut [f64], trans: bool) -> Result<()> {
+        self.solve_with(f, b, trans, &mut Workspace::new())
+    }
+
+    fn solve_with(
+        &self,
+        f: &RSparseFactor,
+        b: &mut [f64],
+        trans: bool,
+        ws: &mut Workspace<f64>,
+    ) -> Result<()> {
+        let (an, s, n, _) = f;
+        if *an == 0 || !b.len().is_multiple_of(*an) {
+            return Err(format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                an
+            ));
+        }
+
+        let x = ws.vec(*an);
+        for b in b.chunks_exact_mut(*an) {
+            if !trans {
+                ipvec(*an, &n.pinv, b, x); // x = P*b
+                lsolve(&n.l, x); // x = L\x
+                usolve(&n.u, x); // x = U\x
+                ipvec(*an, &s.q, x, b); // b = Q*x
+            } else {
+                pvec(*an, &s.q, b, x); // x = Q'*b
+                utsolve(&n.u, x); // x = U'\x
+                ltsolve(&n.l, x); // x = L'\x
+                pvec(*an, &n.pinv, x, b); // b = P'*x
+            }
+        }
 
         Ok(())
     }
 }
 
-impl FactorSolver<usize, f64, (usize, Symb, Nmrc)> for RSparse {
-    fn factor(
+impl LeastSquaresSolver<usize, f64> for RSparse {
+    fn solve(
         &self,
+        m: usize,
         n: usize,
         a_i: &[usize],
         a_p: &[usize],
         a_x: &[f64],
-    ) -> Result<(usize, Symb, Nmrc)> {
+        b: &mut [f64],
+    ) -> Result<()> {
+        if self.order == 0 || self.order == 1 {
+            return Err(format_err!("order {} not supported for QR", self.order));
+        }
+        if b.len() != usize::max(m, n) {
+            return Err(format_err!("b must have length {}", usize::max(m, n)));
+        }
         let nzmax = a_x.len();
 
-        let mut a = rsparse::data::Sprs::new();
+        let mut a = Sprs::new();
         a.nzmax = usize::max(nzmax, 1);
-        a.m = n;
+        a.m = m;
         a.n = n;
         a.p = a_p.iter().map(|&i| i as isize).collect();
         a.i = a_i.to_vec();
         a.x = a_x.to_vec();
 
-        let mut s;
-        let n;
-        s = sqr(&a, self.order, false); // ordering and symbolic analysis
-        n = lu(&a, &mut s, self.tol); // numeric LU factorization
+        let mut b_vec = b.to_vec();
+        rsparse::qrsol(&a, &mut b_vec, self.order);
+        b.clone_from_slice(&b_vec);
 
-        Ok((a.n, s, n))
+        Ok(())
     }
+}
 
-    fn solve(&self, f: &(usize, Symb, Nmrc), b: &mut [f64], trans: bool) -> Result<()> {
-        let (an, s, n) = f;
+impl SparseSolver<f64, RSparseFactor> for RSparse {
+    fn solve_sparse(
+        &self,
+        f: &RSparseFactor,
+        b_i: &[usize],
+        b_x: &[f64],
+    ) -> Result<SparseVec<f64>> {
+        self.solve_sparse_with(f, b_i, b_x, &mut Workspace::new())
+    }
 
-        let mut x = vec![0.0; *an];
+    fn solve_sparse_with(
+        &self,
+        f: &RSparseFactor,
+        b_i: &[usize],
+        b_x: &[f64],
+        ws: &mut Workspace<f64>,
+    ) -> Result<SparseVec<f64>> {
+        let (an, s, n, _) = f;
+        if b_i.len() != b_x.len() {
+            return Err(format_err!(
+                "b_i length {} must be {}",
+                b_i.len(),
+                b_x.len()
+            ));
+        }
+        if let Some(i) = b_i.iter().find(|&&i| i >= *an) {
+            return Err(format_err!("index {} out of range", i));
+        }
+        let l = Triangular {
+            p: &n.l.p,
+            i: &n.l.i,
+            x: &n.l.x,
+            diag: Diag::First,
+        };
+        let u = Triangular {
+            p: &n.u.p,
+            i: &n.u.i,
+            x: &n.u.x,
+            diag: Diag::Last,
+        };
+
+        let (x, marked) = ws.sparse(*an);
+
+        // x = P*b
+        let c_i: Vec<usize> = b_i.iter().map(|&i| perm_at(&n.pinv, i)).collect();
+        for (&k, &b) in c_i.iter().zip(b_x) {
+            x[k] += b;
+        }
+
+        let pattern = l.reach(&c_i, marked);
+        l.solve(&pattern, x); // x = L\x
+        let pattern = u.reach(&pattern, marked);
+        u.solve(&pattern, x); // x = U\x
+
+        // b = Q*x
+        let mut entries: Vec<(usize, f64)> = pattern
+            .iter()
+            .map(|&k| (perm_at(&s.q, k), std::mem::take(&mut x[k])))
+            .collect();
+        entries.sort_unstable_by_key(|&(i, _)| i);
 
+        Ok(SparseVec {
+            n: *an,
+            indices: entries.iter().map(|&(i, _)| i).collect(),
+            values: entries.iter().map(|&(_, x)| x).collect(),
+        })
+    }
+}
+
+impl SelectedSolver<f64, RSparseFactor> for RSparse {
+    fn solve_selected(&self, f: &RSparseFactor, b: &[f64], rows: &[usize]) -> Result<Vec<f64>> {
+        let (an, s, n, (t_p, t_i, t_x)) = f;
+        if b.len() != *an {
+            return Err(format_err!("b length {} must be {}", b.len(), an));
+        }
+        if let Some(r) = rows.iter().find(|&&r| r >= *an) {
+            return Err(format_err!("row {} out of range", r));
+        }
+        let mut qinv = vec![0; *an];
+        for k in 0..*an {
+            qinv[perm_at(&s.q, k)] = k;
+        }
+
+        let mut x = vec![0.0; *an];
         ipvec(*an, &n.pinv, b, &mut x); // x = P*b
-        if !trans {
-            lsolve(&n.l, &mut x); // x = L\x
-            usolve(&n.u, &mut x); // x = U\x
-        } else {
-            utsolve(&n.u, &mut x); // x = U'\x
-            ltsolve(&n.l, &mut x); // x = L'\x
+        lsolve(&n.l, &mut x); // x = L\x
+
+        // Row access to U is needed to prune the backward solve.
+        let ut = Triangular {
+            p: t_p,
+            i: t_i,
+            x: t_x,
+            diag: Diag::First,
+        };
+        let wanted: Vec<usize> = rows.iter().map(|&r| qinv[r]).collect();
+        let pattern = ut.reach(&wanted, &mut vec![false; *an]);
+        ut.solve_transposed(&pattern, &mut x); // x = U\x
+
+        Ok(wanted.iter().map(|&k| x[k]).collect())
+    }
+}
+
+impl SelectedInversion<f64, RSparseFactor> for RSparse {
+    fn inverse_selected(&self, f: &RSparseFactor) -> Result<(Vec<usize>, Vec<usize>, Vec<f64>)> {
+        let (an, s, n, _) = f;
+        let z = takahashi(f);
+
+        // P A Q = L U, so inv(A)(q[a], p[b]) = inv(LU)(a, b).
+        let q: Vec<usize> = (0..*an).map(|k| perm_at(&s.q, k)).collect();
+        let mut p = vec![0; *an];
+        for i in 0..*an {
+            p[perm_at(&n.pinv, i)] = i;
         }
-        ipvec(*an, &s.q, &x, b); // b = Q*x
+        Ok(z.to_csc(&q, &p))
+    }
 
-        Ok(())
+    fn inverse_diag(&self, f: &RSparseFactor) -> Result<Vec<f64>> {
+        let (an, s, n, _) = f;
+        let (l, ut) = factors(f);
+        let z = takahashi(f);
+
+        let mut qinv = vec![0; *an];
+        for k in 0..*an {
+            qinv[perm_at(&s.q, k)] = k;
+        }
+        let mut x = vec![0.0; *an];
+        let mut marked = vec![false; *an];
+        Ok((0..*an)
+            .map(|i| {
+                let (a, b) = (qinv[i], perm_at(&n.pinv, i));
+                // Pivoting may move the diagonal off the pattern.
+                z.get(a, b)
+                    .unwrap_or_else(|| solve_entry(&l, &ut, a, b, &mut x, &mut marked))
+            })
+            .collect())
+    }
+}
+
+/// Returns `L` and the transpose of `U`, both with the diagonal first.
+fn factors(
+    f: &RSparseFactor,
+) -> (
+    Triangular<'_, isize, usize, f64>,
+    Triangular<'_, usize, usize, f64>,
+) {
+    let (_, _, n, (t_p, t_i, t_x)) = f;
+    let l = Triangular {
+        p: &n.l.p,
+        i: &n.l.i,
+        x: &n.l.x,
+        diag: Diag::First,
+    };
+    let ut = Triangular {
+        p: t_p,
+        i: t_i,
+        x: t_x,
+        diag: Diag::First,
+    };
+    (l, ut)
+}
+
+fn takahashi(f: &RSparseFactor) -> Takahashi<f64> {
+    let (l, ut) = factors(f);
+    let d: Vec<f64> = (0..f.0).map(|k| ut.diag(k)).collect();
+    Takahashi::new(f.0, &l, &ut, &d)
+}
+
+impl LogDet<f64> for RSparseFactor {
+    fn log_det(&self) -> Result<(f64, f64)> {
+        let (n, s, f, _) = self;
+
+        // P A Q = L U, where L has a unit diagonal.
+        let mut sign = 1.0;
+        if let Some(pinv) = &f.pinv {
+            sign *= permutation_sign(pinv);
+        }
+        if let Some(q) = &s.q {
+            sign *= permutation_sign(&q[..*n]);
+        }
+        let mut ln = 0.0;
+        for k in 0..*n {
+            let u_kk = f.u.x[f.u.p[k + 1] as usize - 1];
+            if u_kk == 0.0 {
+                return Ok((0.0, f64::NEG_INFINITY));
+            }
+            sign *= u_kk.signum();
+            ln += u_kk.abs().ln();
+        }
+        Ok((sign, ln))
+    }
+}
+
+/// Left-looking LU factorization of `A(:, q)` with threshold partial
+/// pivoting, as `rsparse::lu`, which panics if a column has no nonzero
+/// pivot. Such columns are skipped instead and a numerically
+/// [`SingularMatrix`] error lists the rows that were not pivoted.
+fn lu(a: &Sprs, s: &Symb, tol: f64) -> Result<Nmrc> {
+    let n = a.n;
+    let mut pinv: Vec<Option<usize>> = vec![None; n];
+    let (mut l_p, mut l_i, mut l_x) = (vec![0], Vec::new(), Vec::new());
+    let (mut u_p, mut u_i, mut u_x) = (vec![0], Vec::new(), Vec::new());
+    let mut cols = Vec::new();
+
+    let mut x = vec![0.0; n];
+    let mut mark = vec![usize::MAX; n];
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    let mut pattern = Vec::with_capacity(n);
+    for k in 0..n {
+        let col = perm_at(&s.q, k);
+        let (a_p0, a_p1) = (a.p[col] as usize, a.p[col + 1] as usize);
+
+        // Rows reachable from A(:, col) in the graph of L, in reverse
+        // topological order.
+        pattern.clear();
+        for &i in &a.i[a_p0..a_p1] {
+            if mark[i] == k {
+                continue;
+            }
+            mark[i] = k;
+            stack.push((i, 0));
+            while let Some((j, next)) = stack.pop() {
+                let col_j = pinv[j].map_or(0..0, |kk| l_p[kk] + 1..l_p[kk + 1]);
+                match l_i[col_j.clone()][next.min(col_j.len())..]
+                    .iter()
+                    .position(|&r| mark[r] != k)
+                {
+                    Some(d) => {
+                        let r = l_i[col_j.start + next + d];
+                        mark[r] = k;
+                        stack.push((j, next + d + 1));
+                        stack.push((r, 0));
+                    }
+                    None => pattern.push(j),
+                }
+            }
+        }
+
+        // x = L \ A(:, col)
+        for p in a_p0..a_p1 {
+            x[a.i[p]] = a.x[p];
+        }
+        for &j in pattern.iter().rev() {
+            if let Some(kk) = pinv[j] {
+                for p in l_p[kk] + 1..l_p[kk + 1] {
+                    x[l_i[p]] -= l_x[p] * x[j];
+                }
+            }
+        }
+
+        // Entries in pivotal rows belong to U, the largest of the others
+        // is the pivot unless the diagonal is within the tolerance of it.
+        let mut ipiv = None;
+        let mut a_f = 0.0;
+        for &i in &pattern {
+            match pinv[i] {
+                Some(kk) => {
+                    u_i.push(kk);
+                    u_x.push(x[i]);
+                }
+                None if x[i].abs() > a_f => {
+                    a_f = x[i].abs();
+                    ipiv = Some(i);
+                }
+                None => {}
+            }
+        }
+        if pinv[col].is_none() && ipiv.is_some() && x[col].abs() >= a_f * tol {
+            ipiv = Some(col);
+        }
+
+        match ipiv {
+            Some(ipiv) => {
+                let pivot = x[ipiv];
+                u_i.push(k);
+                u_x.push(pivot);
+                pinv[ipiv] = Some(k);
+                l_i.push(ipiv);
+                l_x.push(1.0);
+                for &i in &pattern {
+                    if pinv[i].is_none() {
+                        l_i.push(i);
+                        l_x.push(x[i] / pivot);
+                    }
+                }
+            }
+            None => cols.push(col),
+        }
+        for &i in &pattern {
+            x[i] = 0.0;
+        }
+        l_p.push(l_i.len());
+        u_p.push(u_i.len());
+    }
+
+    if !cols.is_empty() {
+        let rows: Vec<usize> = (0..n).filter(|&i| pinv[i].is_none()).collect();
+        return Err(SingularMatrix {
+            rank: n - rows.len(),
+            rows,
+            cols,
+            structural: false,
+        }
+        .into());
+    }
+
+    // Row indices of L in pivotal order.
+    for i in l_i.iter_mut() {
+        *i = pinv[*i].unwrap();
     }
+    let sprs = |p: Vec<usize>, i: Vec<usize>, x: Vec<f64>| Sprs {
+        nzmax: i.len(),
+        m: n,
+        n,
+        p: p.into_iter().map(|p| p as isize).collect(),
+        i,
+        x,
+    };
+    Ok(Nmrc {
+        l: sprs(l_p, l_i, l_x),
+        u: sprs(u_p, u_i, u_x),
+        pinv: Some(pinv.into_iter().map(|k| k.unwrap() as isize).collect()),
+        b: Vec::new(),
+    })
 }
 
 /// x(P) = b, for dense vectors x and b; P=None denotes identity
@@ -105,10 +503,33 @@ fn ipvec(n: usize, p: &Option<Vec<isize>>, b: &[f64], x: &mut [f64]) {
     }
 }
 
+/// x = b(P), for dense vectors x and b; P=None denotes identity
+///
+fn pvec(n: usize, p: &Option<Vec<isize>>, b: &[f64], x: &mut [f64]) {
+    for k in 0..n {
+        x[k] = b[perm_at(p, k)];
+    }
+}
+
+/// Returns `p[k]`, where `p` = None denotes identity.
+fn perm_at(p: &Option<Vec<isize>>, k: usize) -> usize {
+    match p {
+        Some(p) => p[k] as usize,
+        None => k,
+    }
+}
+
+const _: () = assert_send_sync::<RSparse>();
+const _: () = assert_send_sync::<RSparseFactor>();
+
 #[cfg(test)]
 mod tests {
     use super::RSparse;
+    #[cfg(feature = "matrix")]
+    use crate::matrix::Dataset;
+    use crate::rank::SingularMatrix;
     use crate::test;
+    use crate::FactorSolver;
     use anyhow::Result;
 
     #[test]
@@ -117,6 +538,15 @@ mod tests {
         test::simple_solver_test::<usize, f64, RSparse>(solver)
     }
 
+    #[test]
+    fn simple_least_squares_test() -> Result<()> {
+        let solver = RSparse {
+            order: 2,
+            ..Default::default()
+        };
+        test::simple_least_squares_test::<usize, f64, RSparse>(solver)
+    }
+
     #[test]
     #[cfg(feature = "matrix")]
     fn test_solver() -> Result<()> {
@@ -130,4 +560,101 @@ mod tests {
         let solver = RSparse::default();
         test::test_factor_solver_bbus(&solver, 1, 1e-11)
     }
+
+    #[test]
+    fn singular_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn numerically_singular_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::numerically_singular_factor_test(&solver)?;
+
+        // The factorization continues past the zero pivot.
+        let a_i = vec![0, 1, 3, 0, 2, 0, 1, 3, 1, 3];
+        let a_p = vec![0, 3, 5, 8, 10];
+        let a_x = vec![1.0, 2.0, 4.0, 2.0, 1.0, 1.0, 2.0, 4.0, 1.0, 2.0];
+        let err = FactorSolver::factor(&solver, 4, &a_i, &a_p, &a_x).unwrap_err();
+        let singular = err.downcast_ref::<SingularMatrix>().unwrap();
+        assert_eq!((singular.rank, singular.rows.len()), (3, 1));
+        assert_eq!(singular.cols.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn rounded_singular_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::rounded_singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn workspace_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_workspace_test(&solver)
+    }
+
+    #[test]
+    fn schur_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_schur_test(&solver)
+    }
+
+    #[test]
+    fn log_det_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_log_det_test(&solver)
+    }
+
+    #[test]
+    fn pivoting_log_det_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::pivoting_log_det_test(&solver)
+    }
+
+    #[test]
+    fn sparse_solve_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_sparse_solve_test(&solver)
+    }
+
+    #[test]
+    fn selected_solve_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_selected_solve_test(&solver)
+    }
+
+    #[test]
+    fn low_rank_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_low_rank_test(&solver)
+    }
+
+    #[test]
+    fn pivoting_multi_rhs_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::pivoting_multi_rhs_test(&solver)
+    }
+
+    #[test]
+    fn pivoting_selected_inverse_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::pivoting_selected_inverse_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_selected_inverse() -> Result<()> {
+        let solver = RSparse::default();
+        let (n, a_p, a_i, a_x) = Dataset::get("ACTIVSg200_Bbus")?.read(true)?;
+        test::test_selected_inverse(&solver, n, &a_i, &a_p, &a_x, 1e-9)
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn solve_par_test() -> Result<()> {
+        let solver = RSparse::default();
+        test::simple_solve_par_test(&solver)
+    }
 }
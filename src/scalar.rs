@@ -0,0 +1,49 @@
+use num_traits::NumAssign;
+use std::fmt::Debug;
+use std::ops::Neg;
+
+#[cfg(feature = "num-complex")]
+use num_complex::Complex64;
+
+/// Scalar types supported by the pure Rust solvers.
+pub trait Scalar:
+    NumAssign + Neg<Output = Self> + Copy + Default + Debug + Send + Sync + 'static
+{
+    /// Modulus of the value.
+    fn norm(self) -> f64;
+
+    /// Complex conjugate of the value (identity for real scalars).
+    fn conj(self) -> Self;
+
+    /// Converts a real value.
+    fn from_f64(x: f64) -> Self;
+}
+
+impl Scalar for f64 {
+    fn norm(self) -> f64 {
+        self.abs()
+    }
+
+    fn conj(self) -> Self {
+        self
+    }
+
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+}
+
+#[cfg(feature = "num-complex")]
+impl Scalar for Complex64 {
+    fn norm(self) -> f64 {
+        Complex64::norm(self)
+    }
+
+    fn conj(self) -> Self {
+        Complex64::conj(&self)
+    }
+
+    fn from_f64(x: f64) -> Self {
+        Complex64::new(x, 0.0)
+    }
+}
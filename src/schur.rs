@@ -0,0 +1,118 @@
+//! Schur complements of sparse matrices.
+//!
+//! Partitioning the `n x n` matrix `A` into interior (`I`) and boundary
+//! (`B`) rows and columns, the Schur complement
+//!
+//! ```txt
+//!     S = A_BB - A_BI inv(A_II) A_IB
+//! ```
+//!
+//! is the matrix of the boundary equations after elimination of
+//! the interior variables.
+
+use crate::{FactorSolver, Scalar};
+use anyhow::{format_err, Result};
+use num_traits::PrimInt;
+
+/// Returns the Schur complement of the interior block of an `n x n` matrix,
+/// where the boundary is given by the `keep` indices. The interior block
+/// is factorized with `solver` and one solve is performed per boundary
+/// index. The result is a `keep.len()` square matrix `(s_p, s_i, s_x)` in
+/// compressed-column form, in the order of `keep`, without exact zeros.
+pub fn schur_complement<I, S, F, T>(
+    solver: &T,
+    n: usize,
+    a_i: &[I],
+    a_p: &[I],
+    a_x: &[S],
+    keep: &[usize],
+) -> Result<(Vec<I>, Vec<I>, Vec<S>)>
+where
+    I: PrimInt,
+    S: Scalar,
+    T: FactorSolver<I, S, F> + ?Sized,
+{
+    // Position of each index in the interior or boundary block.
+    let mut boundary = vec![None; n];
+    for (k, &j) in keep.iter().enumerate() {
+        if j >= n {
+            return Err(format_err!("keep index {} out of range", j));
+        }
+        if boundary[j].replace(k).is_some() {
+            return Err(format_err!("duplicate keep index {}", j));
+        }
+    }
+    let cols_i: Vec<usize> = (0..n).filter(|&j| boundary[j].is_none()).collect();
+    let mut interior = vec![None; n];
+    for (k, &j) in cols_i.iter().enumerate() {
+        interior[j] = Some(k);
+    }
+    let (n_i, n_b) = (cols_i.len(), keep.len());
+
+    let col = |j: usize| {
+        (a_p[j].to_usize().unwrap()..a_p[j + 1].to_usize().unwrap())
+            .map(|p| (a_i[p].to_usize().unwrap(), a_x[p]))
+    };
+
+    // A_II in compressed-column form.
+    let mut ii_p = vec![I::zero()];
+    let mut ii_i = Vec::default();
+    let mut ii_x = Vec::default();
+    for &j in &cols_i {
+        for (i, x) in col(j) {
+            if let Some(i) = interior[i] {
+                ii_i.push(I::from(i).unwrap());
+                ii_x.push(x);
+            }
+        }
+        ii_p.push(I::from(ii_i.len()).unwrap());
+    }
+    let f = if n_i > 0 {
+        Some(solver.factor(n_i, &ii_i, &ii_p, &ii_x)?)
+    } else {
+        None
+    };
+
+    let mut s_p = vec![I::zero()];
+    let mut s_i = Vec::default();
+    let mut s_x = Vec::default();
+    let mut x = vec![S::zero(); n_i];
+    let mut s = vec![S::zero(); n_b];
+    for &j in keep {
+        // s = A_BB(:,j), x = A_IB(:,j)
+        x.fill(S::zero());
+        for (i, a) in col(j) {
+            match (boundary[i], interior[i]) {
+                (Some(k), _) => s[k] += a,
+                (_, Some(k)) => x[k] += a,
+                _ => unreachable!(),
+            }
+        }
+
+        // s -= A_BI inv(A_II) x
+        if let Some(f) = &f {
+            solver.solve(f, &mut x, false)?;
+            for (&c, &x_c) in cols_i.iter().zip(&x) {
+                if x_c == S::zero() {
+                    continue;
+                }
+                for (i, a) in col(c) {
+                    if let Some(k) = boundary[i] {
+                        s[k] -= a * x_c;
+                    }
+                }
+            }
+        }
+
+        for (k, s_k) in s.iter_mut().enumerate() {
+            if *s_k != S::zero() {
+                s_i.push(I::from(k).unwrap());
+                s_x.push(*s_k);
+            }
+            *s_k = S::zero();
+        }
+        s_p.push(I::from(s_i.len()).unwrap());
+    }
+
+    Ok((s_p, s_i, s_x))
+}
@@ -0,0 +1,267 @@
+//! Takahashi selected inversion.
+//!
+//! Given factors `LU`, with `L` unit lower triangular and `U = DŨ` where
+//! `Ũ` is unit upper triangular, the entries of `Z = (LU)^{-1}` on the
+//! filled, symmetrized pattern of `L + U'` satisfy
+//!
+//! ```txt
+//!     Z_ij = -sum_{k>j} Z_ik L_kj           (i > j)
+//!     Z_ji = -sum_{k>j} Ũ_jk Z_ki           (i > j)
+//!     Z_jj = 1/d_j - sum_{k>j} Ũ_jk Z_kj
+//! ```
+//!
+//! and only reference entries on that pattern, so they can be computed
+//! column by column from `j = n-1` down to `0`.
+
+use crate::csc::compress;
+use crate::triangular::Columns;
+use crate::Scalar;
+
+/// Entries of `Z = (LU)^{-1}` in the factor ordering.
+pub(crate) struct Takahashi<S> {
+    /// Column pointers of the strictly lower pattern.
+    z_p: Vec<usize>,
+    /// Sorted row indices of the strictly lower pattern.
+    z_i: Vec<usize>,
+    /// `Z_ij` for `i > j`, in column `j`.
+    z_l: Vec<S>,
+    /// `Z_ji` for `i > j`, in column `j`.
+    z_u: Vec<S>,
+    /// Diagonal of `Z`.
+    z_d: Vec<S>,
+}
+
+impl<S: Scalar> Takahashi<S> {
+    /// Selected inversion of `LU` where `l` is unit lower triangular and
+    /// `ut` is the transpose of `U`, with `d` the diagonal of `U`. The
+    /// off-diagonal entries of `ut` are scaled by `ut.diag(j)`.
+    pub fn new(n: usize, l: &impl Columns<S>, ut: &impl Columns<S>, d: &[S]) -> Self {
+        let (z_p, z_i) = symbolic(n, l, ut);
+        let nnz = z_p[n];
+
+        let mut z_l = vec![S::zero(); nnz];
+        let mut z_u = vec![S::zero(); nnz];
+        let mut z_d = vec![S::zero(); n];
+
+        let mut l_j = vec![S::zero(); n];
+        let mut u_j = vec![S::zero(); n];
+        let mut w_l = vec![S::zero(); n];
+        let mut w_u = vec![S::zero(); n];
+        let mut mark = vec![false; n];
+
+        for j in (0..n).rev() {
+            let cols = &z_i[z_p[j]..z_p[j + 1]];
+
+            // Scatter column j of L and row j of Ũ.
+            for (i, x) in l.col(j) {
+                l_j[i] = x;
+            }
+            let u_jj = ut.diag(j);
+            for (i, x) in ut.col(j) {
+                u_j[i] = x / u_jj;
+            }
+            for &k in cols {
+                mark[k] = true;
+            }
+
+            for &k in cols {
+                let (l_kj, u_jk) = (l_j[k], u_j[k]);
+                w_l[k] += z_d[k] * l_kj;
+                w_u[k] += u_jk * z_d[k];
+                for t in z_p[k]..z_p[k + 1] {
+                    let r = z_i[t];
+                    if mark[r] {
+                        w_l[r] += z_l[t] * l_kj; // Z_rk L_kj
+                        w_l[k] += z_u[t] * l_j[r]; // Z_kr L_rj
+                        w_u[r] += u_jk * z_u[t]; // Ũ_jk Z_kr
+                        w_u[k] += u_j[r] * z_l[t]; // Ũ_jr Z_rk
+                    }
+                }
+            }
+
+            let mut z_jj = S::one() / d[j];
+            for (t, &k) in (z_p[j]..).zip(cols) {
+                z_l[t] = -w_l[k];
+                z_u[t] = -w_u[k];
+                z_jj -= u_j[k] * z_l[t];
+
+                l_j[k] = S::zero();
+                u_j[k] = S::zero();
+                w_l[k] = S::zero();
+                w_u[k] = S::zero();
+                mark[k] = false;
+            }
+            z_d[j] = z_jj;
+        }
+
+        Self {
+            z_p,
+            z_i,
+            z_l,
+            z_u,
+            z_d,
+        }
+    }
+
+    /// Returns `Z_ab`, if it is on the pattern.
+    pub fn get(&self, a: usize, b: usize) -> Option<S> {
+        let (i, j) = if a > b { (a, b) } else { (b, a) };
+        if i == j {
+            return Some(self.z_d[j]);
+        }
+        let start = self.z_p[j];
+        let t = start + self.z_i[start..self.z_p[j + 1]].binary_search(&i).ok()?;
+        Some(if a > b { self.z_l[t] } else { self.z_u[t] })
+    }
+
+    /// Returns the entries as a compressed-column matrix `(p, i, x)` with
+    /// sorted row indices, where `Z_ab` is entry `(q[a], r[b])`.
+    pub fn to_csc(&self, q: &[usize], r: &[usize]) -> (Vec<usize>, Vec<usize>, Vec<S>) {
+        let n = self.z_d.len();
+        let nnz = n + 2 * self.z_i.len();
+        let mut t_i = Vec::with_capacity(nnz);
+        let mut t_j = Vec::with_capacity(nnz);
+        let mut t_x = Vec::with_capacity(nnz);
+        for j in 0..n {
+            t_i.push(q[j]);
+            t_j.push(r[j]);
+            t_x.push(self.z_d[j]);
+            for t in self.z_p[j]..self.z_p[j + 1] {
+                let i = self.z_i[t];
+                t_i.extend([q[i], q[j]]);
+                t_j.extend([r[j], r[i]]);
+                t_x.extend([self.z_l[t], self.z_u[t]]);
+            }
+        }
+        compress(n, &t_i, &t_j, &t_x)
+    }
+}
+
+/// Returns `Z_ab` for an entry that is not on the pattern, as the product
+/// of sparse solves `U'w = e_a` and `Ly = e_b`. `x` and `marked` must be
+/// zero and false on entry and are restored on return.
+#[cfg_attr(not(any(feature = "rlu", feature = "rsparse")), allow(dead_code))]
+pub(crate) fn solve_entry<S: Scalar>(
+    l: &impl Columns<S>,
+    ut: &impl Columns<S>,
+    a: usize,
+    b: usize,
+    x: &mut [S],
+    marked: &mut [bool],
+) -> S {
+    x[b] = S::one();
+    let pattern = l.reach(&[b], marked);
+    l.solve(&pattern, x); // x = L\e_b
+    let y: Vec<(usize, S)> = pattern
+        .iter()
+        .map(|&k| (k, std::mem::take(&mut x[k])))
+        .collect();
+
+    x[a] = S::one();
+    let pattern = ut.reach(&[a], marked);
+    ut.solve(&pattern, x); // x = U'\e_a
+    let z_ab = y.iter().fold(S::zero(), |z, &(k, y_k)| z + x[k] * y_k);
+    for &k in &pattern {
+        x[k] = S::zero();
+    }
+    z_ab
+}
+
+/// Strictly lower pattern of `L + U'` closed under fill, so that the
+/// pattern of each column is a clique in the pattern of its ancestors.
+fn symbolic<S: Scalar>(
+    n: usize,
+    l: &impl Columns<S>,
+    ut: &impl Columns<S>,
+) -> (Vec<usize>, Vec<usize>) {
+    let mut z_p = vec![0; n + 1];
+    let mut z_i = Vec::default();
+    let mut children: Vec<Vec<usize>> = vec![Vec::default(); n];
+    let mut mark = vec![usize::MAX; n];
+
+    for j in 0..n {
+        let start = z_i.len();
+        let mut add = |i: usize, z_i: &mut Vec<usize>| {
+            if i > j && mark[i] != j {
+                mark[i] = j;
+                z_i.push(i);
+            }
+        };
+        l.col(j).for_each(|(i, _)| add(i, &mut z_i));
+        ut.col(j).for_each(|(i, _)| add(i, &mut z_i));
+        for &c in &children[j] {
+            for t in z_p[c]..z_p[c + 1] {
+                add(z_i[t], &mut z_i);
+            }
+        }
+        z_i[start..].sort_unstable();
+        z_p[j + 1] = z_i.len();
+
+        // The parent in the elimination tree is the first off-diagonal.
+        if let Some(&parent) = z_i.get(start) {
+            children[parent].push(j);
+        }
+    }
+    (z_p, z_i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{solve_entry, Takahashi};
+    use crate::triangular::{Diag, Triangular};
+
+    #[test]
+    fn test_takahashi() {
+        // A = LU, where
+        //
+        //     L = {1,  ,  }    U = {2, 1,  }
+        //         { , 1,  }        { , 4,  }
+        //         {1,  , 1}        { ,  , 2}
+        //
+        // where entry (2,1) is fill from column 0.
+        let l_p: Vec<usize> = vec![0, 1, 1, 1];
+        let l_i: Vec<usize> = vec![2];
+        let l_x = vec![1.0];
+        let l = Triangular {
+            p: &l_p,
+            i: &l_i,
+            x: &l_x,
+            diag: Diag::Unit,
+        };
+        // U' with the diagonal first in each column.
+        let ut_p: Vec<usize> = vec![0, 2, 3, 4];
+        let ut_i: Vec<usize> = vec![0, 1, 1, 2];
+        let ut_x = vec![2.0, 1.0, 4.0, 2.0];
+        let ut = Triangular {
+            p: &ut_p,
+            i: &ut_i,
+            x: &ut_x,
+            diag: Diag::First,
+        };
+        let d = vec![2.0, 4.0, 2.0];
+
+        let z = Takahashi::new(3, &l, &ut, &d);
+
+        let mut x = vec![0.0; 3];
+        let mut marked = vec![false; 3];
+        assert_eq!(solve_entry(&l, &ut, 0, 1, &mut x, &mut marked), -0.125);
+        assert_eq!(solve_entry(&l, &ut, 2, 0, &mut x, &mut marked), -0.5);
+        assert!(x.iter().all(|&x| x == 0.0) && marked.iter().all(|m| !m));
+
+        // inv(LU) computed densely.
+        let inv = [[0.5, -0.125, 0.0], [0.0, 0.25, 0.0], [-0.5, 0.0, 0.5]];
+        for (a, row) in inv.iter().enumerate() {
+            for (b, &expect) in row.iter().enumerate() {
+                let actual = z.get(a, b).unwrap();
+                assert!(
+                    f64::abs(actual - expect) < 1e-14,
+                    "Z[{},{}] expected {} actual {}",
+                    a,
+                    b,
+                    expect,
+                    actual
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Parallel supernodal LU factorization in pure Rust.
+//!
+//! Rows are permuted to put large entries on the diagonal and columns are
+//! ordered with [AMD](https://crates.io/crates/amd) applied to the pattern of
+//! `A + A'`. Chains of columns of the elimination tree are grouped into
+//! relaxed supernodes, which may store a few explicit zeros, and their
+//! frontal matrices are factorized with dense kernels. Independent
+//! subtrees of the assembly tree are factorized in parallel if the `rayon`
+//! feature is enabled.
+
+mod numeric;
+mod symbolic;
+
+use crate::batch::try_map;
+use crate::csc::transpose;
+use crate::rank::check_structural_rank;
+use crate::{assert_send_sync, FactorSolver, Scalar, Solver, Workspace};
+use anyhow::{format_err, Result};
+use num_traits::{NumAssignOps, PrimInt};
+use std::fmt::Display;
+use std::sync::Arc;
+
+use numeric::{factor_front, Permuted, Supernode, Update};
+use symbolic::Symbolic;
+
+/// Supernodal LU solver.
+///
+/// Pivoting is restricted to the diagonal block of each supernode. Pivots
+/// with a modulus smaller than `pivot_tol * max|a_x|` are replaced by a
+/// value of that modulus (static pivoting), and solutions are then improved
+/// by iterative refinement against the input matrix.
+pub struct SNLU {
+    pub control: amd::Control,
+
+    /// Static pivoting threshold, relative to the largest entry of the matrix.
+    pub pivot_tol: f64,
+
+    /// Maximum number of iterative refinement steps if any pivots
+    /// were perturbed.
+    pub refine: usize,
+}
+
+impl Default for SNLU {
+    fn default() -> Self {
+        Self {
+            control: amd::Control::default(),
+            pivot_tol: 1e-14,
+            refine: 10,
+        }
+    }
+}
+
+/// Factors `A(r, q) = L U` from [`SNLU`].
+pub struct SNLUFactor<S> {
+    n: usize,
+    /// Shared by refactorizations with the same pattern.
+    sym: Arc<Symbolic>,
+    supernodes: Vec<Supernode<S>>,
+    perturbed: usize,
+    /// Input matrix, kept for iterative refinement if any pivots
+    /// were perturbed.
+    a_p: Vec<usize>,
+    a_i: Vec<usize>,
+    a_x: Vec<S>,
+}
+
+impl<S: Scalar> SNLUFactor<S> {
+    /// Order of the factorized matrix.
+    pub fn n(&self) -> usize {
+        self.n
+    }
+
+    /// Number of stored entries in `L` and `U`.
+    pub fn nnz(&self) -> usize {
+        self.supernodes
+            .iter()
+            .map(|s| s.lu.len() + s.u12.len())
+            .sum()
+    }
+
+    /// Number of supernodes.
+    pub fn n_super(&self) -> usize {
+        self.supernodes.len()
+    }
+
+    /// Number of pivots that were replaced by static pivoting. Solutions
+    /// are refined if this is nonzero.
+    pub fn perturbed(&self) -> usize {
+        self.perturbed
+    }
+
+    fn solve_in_place(&self, x: &mut [S], b: &mut [S], trans: bool) {
+        let (r, q) = if trans {
+            (&self.sym.q, &self.sym.r)
+        } else {
+            (&self.sym.r, &self.sym.q)
+        };
+        for (x, &i) in x.iter_mut().zip(r) {
+            *x = b[i];
+        }
+        if !trans {
+            for s in &self.supernodes {
+                s.lsolve(x);
+            }
+            for s in self.supernodes.iter().rev() {
+                s.usolve(x);
+            }
+        } else {
+            for s in &self.supernodes {
+                s.utsolve(x);
+            }
+            for s in self.supernodes.iter().rev() {
+                s.ltsolve(x);
+            }
+        }
+        for (&x, &j) in x.iter().zip(q) {
+            b[j] = x;
+        }
+    }
+
+    /// Solves for each column of `b`, where `b.len()` is a multiple of `n`,
+    /// with up to `refine` steps of iterative refinement if any pivots were
+    /// perturbed.
+    fn solve_all(
+        &self,
+        b: &mut [S],
+        trans: bool,
+        refine: usize,
+        ws: &mut Workspace<S>,
+    ) -> Result<()> {
+        let n = self.n;
+        if n == 0 || !b.len().is_multiple_of(n) {
+            return Err(format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                n
+            ));
+        }
+        if self.perturbed == 0 || refine == 0 {
+            let x = ws.slice(n);
+            for b in b.chunks_exact_mut(n) {
+                self.solve_in_place(x, b, trans);
+            }
+            return Ok(());
+        }
+
+        let (x, rest) = ws.slice(3 * n).split_at_mut(n);
+        let (r, b0) = rest.split_at_mut(n);
+        for b in b.chunks_exact_mut(n) {
+            b0.copy_from_slice(b);
+            self.solve_in_place(x, b, trans);
+            let mut r_last = f64::INFINITY;
+            for _ in 0..refine {
+                // r = b - A x, or b - A' x
+                r.copy_from_slice(b0);
+                for j in 0..n {
+                    for p in self.a_p[j]..self.a_p[j + 1] {
+                        let i = self.a_i[p];
+                        if !trans {
+                            r[i] -= self.a_x[p] * b[j];
+                        } else {
+                            r[j] -= self.a_x[p] * b[i];
+                        }
+                    }
+                }
+                let r_max = r.iter().fold(0.0, |m: f64, r| m.max(r.norm()));
+                if r_max == 0.0 || r_max >= r_last {
+                    break;
+                }
+                r_last = r_max;
+                self.solve_in_place(x, r, trans);
+                for (b, &r) in b.iter_mut().zip(r.iter()) {
+                    *b += r;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SNLU {
+    fn factorize<I, S>(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<SNLUFactor<S>>
+    where
+        I: PrimInt + NumAssignOps + Display,
+        S: Scalar,
+    {
+        check_structural_rank(n, a_i, a_p)?;
+
+        let (a_i, a_p) = to_usize(n, a_i, a_p);
+        let sym = symbolic::analyze(n, &a_i, &a_p, a_x, &self.control)?;
+        self.numeric(n, &a_i, &a_p, a_x, Arc::new(sym))
+    }
+
+    /// Numeric factorization with the orderings and supernodes of `sym`.
+    fn numeric<S: Scalar>(
+        &self,
+        n: usize,
+        a_i: &[usize],
+        a_p: &[usize],
+        a_x: &[S],
+        sym: Arc<Symbolic>,
+    ) -> Result<SNLUFactor<S>> {
+        // C = A(r, q) in compressed-column and compressed-row form.
+        let mut r_inv = vec![0; n];
+        for (k, &i) in sym.r.iter().enumerate() {
+            r_inv[i] = k;
+        }
+        let mut c_p = Vec::with_capacity(n + 1);
+        let mut c_i = Vec::with_capacity(a_p[n]);
+        let mut c_x = Vec::with_capacity(a_p[n]);
+        c_p.push(0);
+        for &j in &sym.q {
+            for p in a_p[j]..a_p[j + 1] {
+                c_i.push(r_inv[a_i[p]]);
+                c_x.push(a_x[p]);
+            }
+            c_p.push(c_i.len());
+        }
+        let (t_p, t_j, t_x) = transpose(n, n, &c_p, &c_i, &c_x);
+        let a = Permuted {
+            c_p: &c_p,
+            c_i: &c_i,
+            c_x: &c_x,
+            t_p: &t_p,
+            t_j: &t_j,
+            t_x: &t_x,
+        };
+
+        let a_max = a_x.iter().fold(0.0, |m: f64, &x| m.max(x.norm()));
+        let tol = self.pivot_tol * if a_max > 0.0 { a_max } else { 1.0 };
+
+        let n_super = sym.n_super();
+        let mut children = vec![Vec::default(); n_super];
+        for (s, p) in sym.parent.iter().enumerate() {
+            if let Some(p) = p {
+                children[*p].push(s);
+            }
+        }
+
+        // Supernodes in the same level of the assembly tree are independent.
+        let mut supernodes = Vec::with_capacity(n_super);
+        supernodes.resize_with(n_super, || None);
+        let mut updates: Vec<Option<Update<S>>> = Vec::with_capacity(n_super);
+        updates.resize_with(n_super, || None);
+        let mut perturbed = 0;
+        for level in sym.levels() {
+            let tasks: Vec<(usize, Vec<Update<S>>)> = level
+                .iter()
+                .map(|&s| {
+                    let u = children[s]
+                        .iter()
+                        .map(|&c| updates[c].take().unwrap())
+                        .collect();
+                    (s, u)
+                })
+                .collect();
+
+            let fronts = try_map(&tasks, |(s, u)| {
+                let (c0, c1) = (sym.super_p[*s], sym.super_p[*s + 1]);
+                Ok(factor_front(&a, c0, c1 - c0, &sym.rows[*s], u, tol))
+            })?;
+
+            for (&s, (node, update, count)) in level.iter().zip(fronts) {
+                supernodes[s] = Some(node);
+                updates[s] = Some(update);
+                perturbed += count;
+            }
+        }
+
+        let (a_p, a_i, a_x) = if perturbed > 0 {
+            (a_p.to_vec(), a_i[..a_p[n]].to_vec(), a_x[..a_p[n]].to_vec())
+        } else {
+            Default::default()
+        };
+
+        Ok(SNLUFactor {
+            n,
+            sym,
+            supernodes: supernodes.into_iter().map(Option::unwrap).collect(),
+            perturbed,
+            a_p,
+            a_i,
+            a_x,
+        })
+    }
+}
+
+/// Converts the pattern of an `n x n` matrix to `usize` indices.
+fn to_usize<I: PrimInt>(n: usize, a_i: &[I], a_p: &[I]) -> (Vec<usize>, Vec<usize>) {
+    let a_p: Vec<usize> = a_p[..=n].iter().map(|p| p.to_usize().unwrap()).collect();
+    let a_i: Vec<usize> = a_i[..a_p[n]]
+        .iter()
+        .map(|i| i.to_usize().unwrap())
+        .collect();
+    (a_i, a_p)
+}
+
+impl<I, S> Solver<I, S> for SNLU
+where
+    I: PrimInt + NumAssignOps + Display,
+    S: Scalar,
+{
+    fn solve(
+        &self,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[S],
+        b: &mut [S],
+        trans: bool,
+    ) -> Result<()> {
+        let f = self.factorize(n, a_i, a_p, a_x)?;
+        FactorSolver::<I, S, SNLUFactor<S>>::solve(self, &f, b, trans)
+    }
+}
+
+impl<I, S> FactorSolver<I, S, SNLUFactor<S>> for SNLU
+where
+    I: PrimInt + NumAssignOps + Display,
+    S: Scalar,
+{
+    fn factor(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<SNLUFactor<S>> {
+        self.factorize(n, a_i, a_p, a_x)
+    }
+
+    /// Reuses the orderings and supernodes of `f`, including the row
+    /// permutation chosen for the values it was factorized with.
+    fn refactor(
+        &self,
+        f: &mut SNLUFactor<S>,
+        n: usize,
+        a_i: &[I],
+        a_p: &[I],
+        a_x: &[S],
+    ) -> Result<()> {
+        if n != f.n {
+            return Err(format_err!("expected order {}, found {}", f.n, n));
+        }
+        let (a_i, a_p) = to_usize(n, a_i, a_p);
+        *f = self.numeric(n, &a_i, &a_p, a_x, f.sym.clone())?;
+        Ok(())
+    }
+
+    fn solve(&self, f: &SNLUFactor<S>, b: &mut [S], trans: bool) -> Result<()> {
+        FactorSolver::<I, S, SNLUFactor<S>>::solve_with(self, f, b, trans, &mut Workspace::new())
+    }
+
+    fn solve_with(
+        &self,
+        f: &SNLUFactor<S>,
+        b: &mut [S],
+        trans: bool,
+        ws: &mut Workspace<S>,
+    ) -> Result<()> {
+        f.solve_all(b, trans, self.refine, ws)
+    }
+}
+
+const _: () = assert_send_sync::<SNLU>();
+const _: () = assert_send_sync::<SNLUFactor<f64>>();
+const _: () = assert_send_sync::<SNLUFactor<num_complex::Complex64>>();
+
+#[cfg(test)]
+mod tests {
+    use super::SNLU;
+    use crate::test;
+    use crate::FactorSolver;
+    use anyhow::Result;
+
+    #[test]
+    fn simple_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::simple_solver_test::<usize, f64, SNLU>(solver)
+    }
+
+    #[test]
+    fn multi_rhs_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::pivoting_multi_rhs_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_solver() -> Result<()> {
+        let solver = SNLU::default();
+        test::test_solver_bbus(&solver, 1, 1e-11)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_solver_gen() -> Result<()> {
+        let solver = SNLU::default();
+        test::test_solver_gen(&solver, 1e-9)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_factor_solver() -> Result<()> {
+        let solver = SNLU::default();
+        test::test_factor_solver_bbus(&solver, 1, 1e-11)
+    }
+
+    #[test]
+    #[cfg(feature = "matrix")]
+    fn test_factor_solver_ybus() -> Result<()> {
+        let solver = SNLU::default();
+        test::test_factor_solver_ybus(&solver, 2, 1e-9)
+    }
+
+    #[test]
+    fn singular_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::singular_factor_test(&solver)
+    }
+
+    #[test]
+    fn perturbed_test() -> Result<()> {
+        //     A = {4, 1,      }
+        //         {1, 4,      }
+        //         {    , 0.0099}
+        let n = 3;
+        let a_i = vec![0, 1, 0, 1, 2];
+        let a_p = vec![0, 2, 4, 5];
+        let a_x = vec![4.0, 1.0, 1.0, 4.0, 0.0099];
+        let x = [1.0, 2.0, 3.0];
+        let b0 = vec![6.0, 9.0, 0.0297];
+
+        // Pivots smaller than 0.01 are perturbed.
+        let solver = SNLU {
+            pivot_tol: 0.0025,
+            ..Default::default()
+        };
+        let f = FactorSolver::<usize, f64, _>::factor(&solver, n, &a_i, &a_p, &a_x)?;
+        assert_eq!(f.perturbed(), 1);
+
+        for trans in [false, true] {
+            let mut b = b0.clone();
+            FactorSolver::<usize, f64, _>::solve(&solver, &f, &mut b, trans)?;
+            for i in 0..n {
+                assert!(
+                    f64::abs(b[i] - x[i]) < 1e-12,
+                    "x[{}] expected {} actual {}",
+                    i,
+                    x[i],
+                    b[i]
+                );
+            }
+        }
+
+        // Without refinement the solution is not accurate.
+        let solver = SNLU {
+            pivot_tol: 0.0025,
+            refine: 0,
+            ..Default::default()
+        };
+        let mut b = b0;
+        FactorSolver::<usize, f64, _>::solve(&solver, &f, &mut b, false)?;
+        assert!(f64::abs(b[2] - x[2]) > 1e-3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn refactor_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::simple_refactor_test(&solver)
+    }
+
+    #[test]
+    fn workspace_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::simple_workspace_test(&solver)
+    }
+
+    #[test]
+    fn schur_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::simple_schur_test(&solver)
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn solve_par_test() -> Result<()> {
+        let solver = SNLU::default();
+        test::simple_solve_par_test(&solver)
+    }
+}
@@ -0,0 +1,325 @@
+use crate::Scalar;
+
+/// Dense factors of one supernode.
+pub(super) struct Supernode<S> {
+    /// First column.
+    pub c0: usize,
+    /// Number of columns.
+    pub nc: usize,
+    /// Sorted row indices below the diagonal block.
+    pub rows: Vec<usize>,
+    /// `[L11\U11; L21]`, `m x nc` column-major with `m = nc + rows.len()`.
+    pub lu: Vec<S>,
+    /// `U12`, `nc x (m - nc)` column-major.
+    pub u12: Vec<S>,
+    /// Row interchanges within the diagonal block.
+    pub piv: Vec<usize>,
+}
+
+/// Contribution of a supernode to the front of its parent.
+pub(super) struct Update<S> {
+    pub rows: Vec<usize>,
+    /// `rows.len()` square, column-major.
+    pub x: Vec<S>,
+}
+
+/// The permuted matrix in compressed-column and compressed-row form.
+pub(super) struct Permuted<'a, S> {
+    pub c_p: &'a [usize],
+    pub c_i: &'a [usize],
+    pub c_x: &'a [S],
+    pub t_p: &'a [usize],
+    pub t_j: &'a [usize],
+    pub t_x: &'a [S],
+}
+
+/// Assembles and partially factorizes the frontal matrix of the supernode
+/// with columns `c0..c0 + nc`. Pivots with a modulus less than `tol` are
+/// perturbed, the number of which is returned.
+pub(super) fn factor_front<S: Scalar>(
+    a: &Permuted<S>,
+    c0: usize,
+    nc: usize,
+    rows: &[usize],
+    children: &[Update<S>],
+    tol: f64,
+) -> (Supernode<S>, Update<S>, usize) {
+    let c1 = c0 + nc;
+    let m = nc + rows.len();
+    let local = |i: usize| {
+        if i < c1 {
+            i - c0
+        } else {
+            nc + rows.binary_search(&i).expect("row not in front")
+        }
+    };
+
+    // Assemble the entries of A, the columns and rows of the supernode.
+    let mut f = vec![S::zero(); m * m];
+    for j in c0..c1 {
+        for p in a.c_p[j]..a.c_p[j + 1] {
+            let i = a.c_i[p];
+            if i >= c0 {
+                f[(j - c0) * m + local(i)] += a.c_x[p];
+            }
+        }
+    }
+    for i in c0..c1 {
+        for p in a.t_p[i]..a.t_p[i + 1] {
+            let j = a.t_j[p];
+            if j >= c1 {
+                f[local(j) * m + (i - c0)] += a.t_x[p];
+            }
+        }
+    }
+
+    // Extend-add the updates from the children.
+    let mut loc = Vec::default();
+    for u in children {
+        loc.clear();
+        loc.extend(u.rows.iter().map(|&i| local(i)));
+        let len = u.rows.len();
+        for (b, &lb) in loc.iter().enumerate() {
+            let col = &mut f[lb * m..(lb + 1) * m];
+            for (a, &la) in loc.iter().enumerate() {
+                col[la] += u.x[b * len + a];
+            }
+        }
+    }
+
+    let (piv, perturbed) = factor_panel(&mut f, m, nc, tol);
+    solve_u12(&mut f, m, nc);
+    update_schur(&mut f, m, nc);
+
+    let u12 = (nc..m)
+        .flat_map(|c| f[c * m..c * m + nc].iter().copied())
+        .collect();
+    let x = (nc..m)
+        .flat_map(|c| f[c * m + nc..(c + 1) * m].iter().copied())
+        .collect();
+    f.truncate(nc * m);
+
+    (
+        Supernode {
+            c0,
+            nc,
+            rows: rows.to_vec(),
+            lu: f,
+            u12,
+            piv,
+        },
+        Update {
+            rows: rows.to_vec(),
+            x,
+        },
+        perturbed,
+    )
+}
+
+/// LU factorization of the first `nc` columns of the `m x m` front, with
+/// row interchanges restricted to the diagonal block.
+fn factor_panel<S: Scalar>(f: &mut [S], m: usize, nc: usize, tol: f64) -> (Vec<usize>, usize) {
+    let mut piv = vec![0; nc];
+    let mut perturbed = 0;
+    for k in 0..nc {
+        let col = &f[k * m..(k + 1) * m];
+        let r = (k..nc)
+            .max_by(|&a, &b| col[a].norm().total_cmp(&col[b].norm()))
+            .unwrap();
+        piv[k] = r;
+        if r != k {
+            for c in 0..m {
+                f.swap(c * m + k, c * m + r);
+            }
+        }
+
+        let d = f[k * m + k];
+        if d.norm() < tol {
+            f[k * m + k] = if d.norm() == 0.0 {
+                S::from_f64(tol)
+            } else {
+                d * S::from_f64(tol / d.norm())
+            };
+            perturbed += 1;
+        }
+        let d = f[k * m + k];
+
+        for x in &mut f[k * m + k + 1..(k + 1) * m] {
+            *x /= d;
+        }
+
+        // Right-looking update of the remaining panel columns.
+        let (left, right) = f.split_at_mut((k + 1) * m);
+        let l = &left[k * m + k + 1..];
+        for col in right[..(nc - k - 1) * m].chunks_exact_mut(m) {
+            let u = col[k];
+            if u != S::zero() {
+                for (x, &l) in col[k + 1..].iter_mut().zip(l) {
+                    *x -= l * u;
+                }
+            }
+        }
+    }
+    (piv, perturbed)
+}
+
+/// `U12 = L11 \ F12`
+fn solve_u12<S: Scalar>(f: &mut [S], m: usize, nc: usize) {
+    let (panel, rest) = f.split_at_mut(nc * m);
+    for col in rest.chunks_exact_mut(m) {
+        for k in 0..nc {
+            let u = col[k];
+            if u != S::zero() {
+                let l = &panel[k * m + k + 1..k * m + nc];
+                for (x, &l) in col[k + 1..nc].iter_mut().zip(l) {
+                    *x -= l * u;
+                }
+            }
+        }
+    }
+}
+
+/// `F22 -= L21 U12`
+fn update_schur<S: Scalar>(f: &mut [S], m: usize, nc: usize) {
+    let (panel, rest) = f.split_at_mut(nc * m);
+    for col in rest.chunks_exact_mut(m) {
+        let (u, x) = col.split_at_mut(nc);
+        for (k, &u) in u.iter().enumerate() {
+            if u != S::zero() {
+                let l = &panel[k * m + nc..(k + 1) * m];
+                for (x, &l) in x.iter_mut().zip(l) {
+                    *x -= l * u;
+                }
+            }
+        }
+    }
+}
+
+impl<S: Scalar> Supernode<S> {
+    fn m(&self) -> usize {
+        self.nc + self.rows.len()
+    }
+
+    /// `x = L \ P x` for the columns of this supernode.
+    pub fn lsolve(&self, x: &mut [S]) {
+        let (c0, nc, m) = (self.c0, self.nc, self.m());
+        for (k, &r) in self.piv.iter().enumerate() {
+            x.swap(c0 + k, c0 + r);
+        }
+        for k in 0..nc {
+            let x_k = x[c0 + k];
+            let col = &self.lu[k * m..(k + 1) * m];
+            for i in k + 1..nc {
+                x[c0 + i] -= col[i] * x_k;
+            }
+            for (&i, &l) in self.rows.iter().zip(&col[nc..]) {
+                x[i] -= l * x_k;
+            }
+        }
+    }
+
+    /// `x = U \ x` for the columns of this supernode.
+    pub fn usolve(&self, x: &mut [S]) {
+        let (c0, nc, m) = (self.c0, self.nc, self.m());
+        for (&i, u) in self.rows.iter().zip(self.u12.chunks_exact(nc)) {
+            let x_i = x[i];
+            for k in 0..nc {
+                x[c0 + k] -= u[k] * x_i;
+            }
+        }
+        for k in (0..nc).rev() {
+            let col = &self.lu[k * m..(k + 1) * m];
+            x[c0 + k] /= col[k];
+            let x_k = x[c0 + k];
+            for i in 0..k {
+                x[c0 + i] -= col[i] * x_k;
+            }
+        }
+    }
+
+    /// `x = U' \ x` for the columns of this supernode.
+    pub fn utsolve(&self, x: &mut [S]) {
+        let (c0, nc, m) = (self.c0, self.nc, self.m());
+        for k in 0..nc {
+            let col = &self.lu[k * m..(k + 1) * m];
+            let mut x_k = x[c0 + k];
+            for i in 0..k {
+                x_k -= col[i] * x[c0 + i];
+            }
+            x[c0 + k] = x_k / col[k];
+        }
+        for (&i, u) in self.rows.iter().zip(self.u12.chunks_exact(nc)) {
+            for k in 0..nc {
+                let x_k = x[c0 + k];
+                x[i] -= u[k] * x_k;
+            }
+        }
+    }
+
+    /// `x = P' (L' \ x)` for the columns of this supernode.
+    pub fn ltsolve(&self, x: &mut [S]) {
+        let (c0, nc, m) = (self.c0, self.nc, self.m());
+        for k in (0..nc).rev() {
+            let col = &self.lu[k * m..(k + 1) * m];
+            let mut x_k = x[c0 + k];
+            for (&i, &l) in self.rows.iter().zip(&col[nc..]) {
+                x_k -= l * x[i];
+            }
+            for i in k + 1..nc {
+                x_k -= col[i] * x[c0 + i];
+            }
+            x[c0 + k] = x_k;
+        }
+        for (k, &r) in self.piv.iter().enumerate().rev() {
+            x.swap(c0 + k, c0 + r);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{factor_front, Permuted};
+
+    #[test]
+    fn test_factor_front() {
+        // One supernode with a zero in the first diagonal position.
+        //
+        //     {0, 2, 1}
+        //     {1, 1,  }
+        //     {2,  , 3}
+        let c_p = [0, 2, 4, 6];
+        let c_i = [1, 2, 0, 1, 0, 2];
+        let c_x = [1.0, 2.0, 2.0, 1.0, 1.0, 3.0];
+        let t_p = [0, 2, 4, 6];
+        let t_j = [1, 2, 0, 1, 0, 2];
+        let t_x = [2.0, 1.0, 1.0, 1.0, 2.0, 3.0];
+        let a = Permuted {
+            c_p: &c_p,
+            c_i: &c_i,
+            c_x: &c_x,
+            t_p: &t_p,
+            t_j: &t_j,
+            t_x: &t_x,
+        };
+        let (s, u, perturbed) = factor_front(&a, 0, 3, &[], &[], 1e-14);
+        assert_eq!(perturbed, 0);
+        assert!(u.rows.is_empty());
+        assert_eq!(s.piv[0], 2);
+
+        // A x = b, where x = [1, 2, 3]
+        let mut x = vec![7.0, 3.0, 11.0];
+        s.lsolve(&mut x);
+        s.usolve(&mut x);
+        for (i, x) in x.iter().enumerate() {
+            assert!(f64::abs(x - (i + 1) as f64) < 1e-14);
+        }
+
+        // A' x = b
+        let mut x = vec![8.0, 4.0, 10.0];
+        s.utsolve(&mut x);
+        s.ltsolve(&mut x);
+        for (i, x) in x.iter().enumerate() {
+            assert!(f64::abs(x - (i + 1) as f64) < 1e-14);
+        }
+    }
+}
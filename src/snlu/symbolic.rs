@@ -0,0 +1,404 @@
+use crate::Scalar;
+use anyhow::{format_err, Result};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Orderings and supernode partition of the matrix `C = A(r, q)`.
+pub(super) struct Symbolic {
+    /// Row `k` of `C` is row `r[k]` of `A`.
+    pub r: Vec<usize>,
+    /// Column `k` of `C` is column `q[k]` of `A`.
+    pub q: Vec<usize>,
+    /// First column of each supernode, followed by `n`.
+    pub super_p: Vec<usize>,
+    /// Sorted row indices below the diagonal block of each supernode.
+    pub rows: Vec<Vec<usize>>,
+    /// Parent of each supernode in the assembly tree.
+    pub parent: Vec<Option<usize>>,
+}
+
+impl Symbolic {
+    pub fn n_super(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Groups the supernodes into levels of the assembly tree, such that
+    /// the children of all supernodes in a level are in earlier levels.
+    pub fn levels(&self) -> Vec<Vec<usize>> {
+        let mut level = vec![0; self.n_super()];
+        let mut levels: Vec<Vec<usize>> = Vec::default();
+        // Children are numbered before their parents.
+        for s in 0..self.n_super() {
+            if levels.len() <= level[s] {
+                levels.push(Vec::default());
+            }
+            levels[level[s]].push(s);
+            if let Some(p) = self.parent[s] {
+                level[p] = usize::max(level[p], level[s] + 1);
+            }
+        }
+        levels
+    }
+}
+
+/// Computes a row permutation that puts large entries on the diagonal, a
+/// fill-reducing ordering of the symmetrized pattern and the supernodes
+/// of its elimination tree.
+pub(super) fn analyze<S: Scalar>(
+    n: usize,
+    a_i: &[usize],
+    a_p: &[usize],
+    a_x: &[S],
+    control: &amd::Control,
+) -> Result<Symbolic> {
+    // Row matched to each column, maximizing the product of the diagonal.
+    let m = weighted_matching(n, a_i, a_p, a_x)
+        .ok_or_else(|| format_err!("matrix is structurally singular"))?;
+    let mut m_inv = vec![0; n];
+    for (j, &i) in m.iter().enumerate() {
+        m_inv[i] = j;
+    }
+
+    // Fill-reducing ordering of A(m, :) with a zero-free diagonal.
+    let mut b_i: Vec<usize> = a_i[..a_p[n]].iter().map(|&i| m_inv[i]).collect();
+    for j in 0..n {
+        b_i[a_p[j]..a_p[j + 1]].sort_unstable();
+    }
+    let (p, _p_inv, _info) = amd::order::<usize>(n, a_p, &b_i, control)
+        .map_err(|st| format_err!("amd status: {:?}", st))?;
+
+    // Postorder the elimination tree so that supernodes are contiguous.
+    let adj = symmetric_pattern(n, a_p, &b_i, &p);
+    let parent = etree(&adj);
+    let post = postorder(&parent);
+    let q: Vec<usize> = post.iter().map(|&k| p[k]).collect();
+    let r: Vec<usize> = q.iter().map(|&j| m[j]).collect();
+
+    let adj = symmetric_pattern(n, a_p, &b_i, &q);
+    let parent = etree(&adj);
+    let structure = column_structure(&adj, &parent);
+
+    let super_p = relaxed_supernodes(&parent, &structure);
+
+    let n_super = super_p.len() - 1;
+    let mut super_of = vec![0; n];
+    for s in 0..n_super {
+        super_of[super_p[s]..super_p[s + 1]].fill(s);
+    }
+    let rows: Vec<Vec<usize>> = (0..n_super)
+        .map(|s| structure[super_p[s + 1] - 1].clone())
+        .collect();
+    let parent = (0..n_super)
+        .map(|s| parent[super_p[s + 1] - 1].map(|k| super_of[k]))
+        .collect();
+
+    Ok(Symbolic {
+        r,
+        q,
+        super_p,
+        rows,
+        parent,
+    })
+}
+
+/// Relaxed supernodes, as a list of first columns followed by `n`.
+///
+/// Column `k` joins the supernode of column `k-1` if `k` is its parent,
+/// and either their structures are nested (a fundamental supernode) or
+/// the explicit zeros in the lower part of the dense supernode stay below
+/// the fraction in `RELAX` for its number of columns.
+fn relaxed_supernodes(parent: &[Option<usize>], structure: &[Vec<usize>]) -> Vec<usize> {
+    const RELAX: [(usize, f64); 3] = [(4, 1.0), (16, 0.8), (48, 0.1)];
+    const RELAX_MAX: f64 = 0.05;
+
+    let n = parent.len();
+    let mut children = vec![0; n];
+    for p in parent.iter().flatten() {
+        children[*p] += 1;
+    }
+    let mut super_p = vec![0];
+    // Number of row indices below the diagonal of the columns of the
+    // current supernode.
+    let mut nnz = structure.first().map_or(0, Vec::len);
+    for k in 1..n {
+        let c0 = *super_p.last().unwrap();
+        let fundamental = children[k] == 1 && structure[k - 1].len() == structure[k].len() + 1;
+        let join = parent[k - 1] == Some(k) && {
+            // Columns c0..=k, with the rows of column k below the block.
+            let nc = k - c0 + 1;
+            let entries = nc * (nc - 1) / 2 + nc * structure[k].len();
+            let zeros = entries - (nnz + structure[k].len());
+            let max = RELAX
+                .iter()
+                .find(|&&(cols, _)| nc <= cols)
+                .map_or(RELAX_MAX, |&(_, z)| z);
+            fundamental || (zeros as f64) < max * entries as f64
+        };
+        if join {
+            nnz += structure[k].len();
+        } else {
+            super_p.push(k);
+            nnz = structure[k].len();
+        }
+    }
+    if n > 0 {
+        super_p.push(n);
+    }
+    super_p
+}
+
+/// Returns the row matched to each column such that the product of the
+/// moduli of the matched entries is maximized, using shortest augmenting
+/// paths with the cost `log(max_i |a_ij|) - log|a_ij|`.
+fn weighted_matching<S: Scalar>(
+    n: usize,
+    a_i: &[usize],
+    a_p: &[usize],
+    a_x: &[S],
+) -> Option<Vec<usize>> {
+    let cost: Vec<f64> = (0..n)
+        .flat_map(|j| {
+            let col = &a_x[a_p[j]..a_p[j + 1]];
+            let max = col.iter().fold(0.0, |m: f64, x| m.max(x.norm()));
+            col.iter().map(move |x| {
+                let x = x.norm().max(f64::MIN_POSITIVE);
+                max.max(f64::MIN_POSITIVE).ln() - x.ln()
+            })
+        })
+        .collect();
+
+    // Potentials keep the reduced costs non-negative and zero on the matching.
+    let mut u = vec![0.0; n]; // rows
+    let mut v = vec![0.0; n]; // columns
+    let mut row_match: Vec<Option<usize>> = vec![None; n];
+    let mut col_match: Vec<Option<usize>> = vec![None; n];
+
+    let mut dist = vec![f64::INFINITY; n];
+    let mut pred = vec![0; n];
+    let mut done = vec![false; n];
+    let mut touched = Vec::default();
+    let mut visited = Vec::default();
+    let mut heap = BinaryHeap::default();
+    for s in 0..n {
+        // Dijkstra from column s over the rows, alternating along the matching.
+        heap.clear();
+        visited.clear();
+        let (mut j, mut d) = (s, 0.0);
+        let (t, d_t) = loop {
+            for p in a_p[j]..a_p[j + 1] {
+                let i = a_i[p];
+                let d_i = d + cost[p] + v[j] - u[i];
+                if !done[i] && d_i < dist[i] {
+                    if dist[i] == f64::INFINITY {
+                        touched.push(i);
+                    }
+                    dist[i] = d_i;
+                    pred[i] = j;
+                    heap.push(Dist(d_i, i));
+                }
+            }
+            let i = loop {
+                match heap.pop() {
+                    Some(Dist(d_i, i)) if !done[i] && d_i <= dist[i] => break i,
+                    Some(_) => {}
+                    None => return None,
+                }
+            };
+            done[i] = true;
+            visited.push(i);
+            match row_match[i] {
+                Some(r) => (j, d) = (r, dist[i]),
+                None => break (i, dist[i]),
+            }
+        };
+
+        // Update the potentials of the visited rows and their columns.
+        v[s] -= d_t;
+        for &i in &visited {
+            if i != t {
+                let d = dist[i] - d_t;
+                u[i] += d;
+                v[row_match[i].unwrap()] += d;
+            }
+        }
+        for &i in &touched {
+            dist[i] = f64::INFINITY;
+            done[i] = false;
+        }
+        touched.clear();
+
+        // Augment along the path from t back to s.
+        let mut i = t;
+        loop {
+            let j = pred[i];
+            let next = col_match[j];
+            row_match[i] = Some(j);
+            col_match[j] = Some(i);
+            match next {
+                Some(r) if j != s => i = r,
+                _ => break,
+            }
+        }
+    }
+
+    col_match.into_iter().collect()
+}
+
+/// Heap entry ordered by increasing distance.
+struct Dist(f64, usize);
+
+impl PartialEq for Dist {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Dist {}
+
+impl PartialOrd for Dist {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Dist {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.total_cmp(&self.0)
+    }
+}
+
+/// Adjacency lists of `B(q, q) + B(q, q)'`, without the diagonal.
+fn symmetric_pattern(n: usize, b_p: &[usize], b_i: &[usize], q: &[usize]) -> Vec<Vec<usize>> {
+    let mut q_inv = vec![0; n];
+    for (k, &j) in q.iter().enumerate() {
+        q_inv[j] = k;
+    }
+    let mut adj = vec![Vec::default(); n];
+    for j in 0..n {
+        for &i in &b_i[b_p[j]..b_p[j + 1]] {
+            let (i, j) = (q_inv[i], q_inv[j]);
+            if i != j {
+                adj[j].push(i);
+                adj[i].push(j);
+            }
+        }
+    }
+    adj
+}
+
+/// Elimination tree of a symmetric pattern.
+fn etree(adj: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let n = adj.len();
+    let mut parent = vec![None; n];
+    let mut ancestor: Vec<Option<usize>> = vec![None; n];
+    for (k, adj_k) in adj.iter().enumerate() {
+        for &i in adj_k.iter().filter(|&&i| i < k) {
+            // Traverse from i to the root of its subtree, with path compression.
+            let mut i = i;
+            loop {
+                let next = ancestor[i].replace(k);
+                match next {
+                    None => {
+                        parent[i] = Some(k);
+                        break;
+                    }
+                    Some(a) if a == k => break,
+                    Some(a) => i = a,
+                }
+            }
+        }
+    }
+    parent
+}
+
+/// Postorder of a forest, with children visited in increasing order.
+fn postorder(parent: &[Option<usize>]) -> Vec<usize> {
+    let n = parent.len();
+    let mut children: Vec<Vec<usize>> = vec![Vec::default(); n];
+    let mut roots = Vec::default();
+    for (k, p) in parent.iter().enumerate() {
+        match p {
+            Some(p) => children[*p].push(k),
+            None => roots.push(k),
+        }
+    }
+    let mut post = Vec::with_capacity(n);
+    let mut stack = Vec::default();
+    for root in roots {
+        stack.push((root, 0));
+        while let Some(&(k, c)) = stack.last() {
+            if c < children[k].len() {
+                stack.last_mut().unwrap().1 += 1;
+                stack.push((children[k][c], 0));
+            } else {
+                stack.pop();
+                post.push(k);
+            }
+        }
+    }
+    post
+}
+
+/// Sorted row indices below the diagonal of each column of the factors.
+fn column_structure(adj: &[Vec<usize>], parent: &[Option<usize>]) -> Vec<Vec<usize>> {
+    let n = adj.len();
+    let mut children: Vec<Vec<usize>> = vec![Vec::default(); n];
+    for (k, p) in parent.iter().enumerate() {
+        if let Some(p) = p {
+            children[*p].push(k);
+        }
+    }
+    let mut structure: Vec<Vec<usize>> = Vec::with_capacity(n);
+    let mut mark = vec![usize::MAX; n];
+    for k in 0..n {
+        let mut rows = Vec::default();
+        let below = adj[k].iter().copied();
+        let from_children = children[k]
+            .iter()
+            .flat_map(|&c| structure[c].iter().copied());
+        for i in below.chain(from_children) {
+            if i > k && mark[i] != k {
+                mark[i] = k;
+                rows.push(i);
+            }
+        }
+        rows.sort_unstable();
+        structure.push(rows);
+    }
+    structure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{etree, postorder, relaxed_supernodes};
+
+    #[test]
+    fn test_etree() {
+        // Arrow matrix with the dense row and column first.
+        let adj = vec![vec![1, 2, 3], vec![0], vec![0], vec![0]];
+        let parent = etree(&adj);
+        assert_eq!(parent, vec![Some(1), Some(2), Some(3), None]);
+        assert_eq!(postorder(&parent), vec![0, 1, 2, 3]);
+
+        // Star with the center last.
+        let adj = vec![vec![3], vec![3], vec![3], vec![0, 1, 2]];
+        let parent = etree(&adj);
+        assert_eq!(parent, vec![Some(3), Some(3), Some(3), None]);
+        assert_eq!(postorder(&parent), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_relaxed_supernodes() {
+        // Arrow matrix with the dense row and column first: one
+        // fundamental supernode.
+        let parent = vec![Some(1), Some(2), Some(3), None];
+        let structure = vec![vec![1, 2, 3], vec![2, 3], vec![3], vec![]];
+        assert_eq!(relaxed_supernodes(&parent, &structure), vec![0, 4]);
+
+        // Star with the center last: the center has three children, so
+        // it only joins the last of them when relaxed.
+        let parent = vec![Some(3), Some(3), Some(3), None];
+        let structure = vec![vec![3], vec![3], vec![3], vec![]];
+        assert_eq!(relaxed_supernodes(&parent, &structure), vec![0, 1, 2, 4]);
+    }
+}
@@ -1,7 +1,9 @@
+mod alloc;
 mod simple;
 #[cfg(feature = "matrix")]
 mod solver;
 
+pub use alloc::*;
 pub use simple::*;
 #[cfg(feature = "matrix")]
 pub use solver::*;
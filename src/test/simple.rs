@@ -1,7 +1,39 @@
-use crate::Solver;
+use crate::lowrank::{LowRank, LowRankUpdated};
+use crate::rank::SingularMatrix;
+use crate::schur::schur_complement;
+use crate::test::count_allocations;
+use crate::{
+    BatchFactorSolver, FactorSolver, LeastSquaresSolver, LogDet, SelectedInversion, SelectedSolver,
+    Solver, SparseSolver, SparseVec, Workspace,
+};
 use anyhow::Result;
 use num_traits::{Float, FromPrimitive, PrimInt};
 
+/// The simple 10x10 test matrix of [`simple_solver_test`] as `(a_i, a_p, a_x, b)`.
+fn simple_matrix() -> (Vec<usize>, Vec<usize>, Vec<f64>, Vec<f64>) {
+    let a_i = vec![
+        0, 7, 8, 1, 4, 9, 2, 9, 3, 6, 7, 8, 9, 1, 4, 5, 3, 6, 9, 0, 3, 7, 8, 0, 3, 7, 8, 1, 2, 3,
+        6, 9,
+    ];
+    let a_p = vec![0, 3, 6, 8, 13, 15, 16, 19, 23, 27, 32];
+    let a_x = vec![
+        2.1, 0.14, 0.09, 1.1, 0.06, 0.03, 1.7, 0.04, 1.0, 0.32, 0.19, 0.32, 0.44, 0.06, 1.6, 2.2,
+        0.32, 1.9, 0.43, 0.14, 0.19, 1.1, 0.22, 0.09, 0.32, 0.22, 2.4, 0.03, 0.04, 0.44, 0.43, 3.2,
+    ];
+    let b = vec![
+        0.403, 0.28, 0.55, 1.504, 0.812, 1.32, 1.888, 1.168, 2.473, 3.695,
+    ];
+    (a_i, a_p, a_x, b)
+}
+
+/// The 4x4 test matrix of [`pivoting_log_det_test`] as `(a_i, a_p, a_x)`.
+fn pivoting_matrix() -> (Vec<usize>, Vec<usize>, Vec<f64>) {
+    let a_i = vec![1, 3, 0, 2, 1, 3, 0, 2];
+    let a_p = vec![0, 2, 4, 6, 8];
+    let a_x = vec![3.0, 1.0, 2.0, 1.0, 1.0, 2.0, 1.0, 4.0];
+    (a_i, a_p, a_x)
+}
+
 /// Simple 10x10 matrix test.
 ///
 /// ```txt
@@ -27,37 +59,73 @@ where
     S: Solver<I, F>,
 {
     let n: usize = 10;
+    let (a_i, a_p, a_x, b) = simple_matrix();
+    let a_i: Vec<I> = a_i.iter().map(|&i| I::from(i).unwrap()).collect();
+    let a_p: Vec<I> = a_p.iter().map(|&i| I::from(i).unwrap()).collect();
+    let a_x: Vec<F> = a_x.iter().map(|&x| F::from(x).unwrap()).collect();
+    let mut b: Vec<F> = b.iter().map(|&x| F::from(x).unwrap()).collect();
+
+    solver.solve(n, &a_i, &a_p, &a_x, &mut b, false)?;
+
+    let x = b;
+
+    (1..=10).zip(x).for_each(|(i, x)| {
+        let expect = (i as f64) / 10.0;
+        let actual = x.to_f64().unwrap();
+        assert!(
+            f64::abs(actual - expect) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i - 1,
+            expect,
+            actual
+        );
+    });
+
+    Ok(())
+}
+
+/// Overdetermined 20x10 least-squares test.
+///
+/// The simple 10x10 test matrix is stacked on top of the identity matrix
+/// and the right-hand-side is consistent with `x = {0.1, 0.2, ..., 1.0}`.
+pub fn simple_least_squares_test<I, F, S>(solver: S) -> Result<()>
+where
+    I: PrimInt + FromPrimitive,
+    F: Float,
+    S: LeastSquaresSolver<I, F>,
+{
+    let (m, n): (usize, usize) = (20, 10);
     let a_i: Vec<I> = [
-        0, 7, 8, 1, 4, 9, 2, 9, 3, 6, 7, 8, 9, 1, 4, 5, 3, 6, 9, 0, 3, 7, 8, 0, 3, 7, 8, 1, 2, 3,
-        6, 9,
+        0, 7, 8, 10, 1, 4, 9, 11, 2, 9, 12, 3, 6, 7, 8, 9, 13, 1, 4, 14, 5, 15, 3, 6, 9, 16, 0, 3,
+        7, 8, 17, 0, 3, 7, 8, 18, 1, 2, 3, 6, 9, 19,
     ]
     .iter()
     .map(|&i| I::from(i).unwrap())
     .collect();
-    let a_p: Vec<I> = [0, 3, 6, 8, 13, 15, 16, 19, 23, 27, 32]
+    let a_p: Vec<I> = [0, 4, 8, 11, 17, 20, 22, 26, 31, 36, 42]
         .iter()
         .map(|&i| I::from(i).unwrap())
         .collect();
     let a_x: Vec<F> = [
-        2.1, 0.14, 0.09, 1.1, 0.06, 0.03, 1.7, 0.04, 1.0, 0.32, 0.19, 0.32, 0.44, 0.06, 1.6, 2.2,
-        0.32, 1.9, 0.43, 0.14, 0.19, 1.1, 0.22, 0.09, 0.32, 0.22, 2.4, 0.03, 0.04, 0.44, 0.43, 3.2,
+        2.1, 0.14, 0.09, 1.0, 1.1, 0.06, 0.03, 1.0, 1.7, 0.04, 1.0, 1.0, 0.32, 0.19, 0.32, 0.44,
+        1.0, 0.06, 1.6, 1.0, 2.2, 1.0, 0.32, 1.9, 0.43, 1.0, 0.14, 0.19, 1.1, 0.22, 1.0, 0.09,
+        0.32, 0.22, 2.4, 1.0, 0.03, 0.04, 0.44, 0.43, 3.2, 1.0,
     ]
     .iter()
     .map(|&i| F::from(i).unwrap())
     .collect();
 
     let mut b: Vec<F> = [
-        0.403, 0.28, 0.55, 1.504, 0.812, 1.32, 1.888, 1.168, 2.473, 3.695,
+        0.403, 0.28, 0.55, 1.504, 0.812, 1.32, 1.888, 1.168, 2.473, 3.695, 0.1, 0.2, 0.3, 0.4, 0.5,
+        0.6, 0.7, 0.8, 0.9, 1.0,
     ]
     .iter()
     .map(|&i| F::from(i).unwrap())
     .collect();
 
-    solver.solve(n, &a_i, &a_p, &a_x, &mut b, false)?;
-
-    let x = b;
+    solver.solve(m, n, &a_i, &a_p, &a_x, &mut b)?;
 
-    (1..=10).zip(x).for_each(|(i, x)| {
+    (1..=10).zip(b).for_each(|(i, x)| {
         let expect = (i as f64) / 10.0;
         let actual = x.to_f64().unwrap();
         assert!(
@@ -69,5 +137,644 @@ where
         );
     });
 
+    // Overdetermined with a nonzero residual.
+    //
+    //     A = {1.0,    }    b = {1.0}
+    //         {   , 1.0}        {1.0}
+    //         {1.0, 1.0}        {0.0}
+    let to_i = |v: &[usize]| -> Vec<I> { v.iter().map(|&i| I::from(i).unwrap()).collect() };
+    let to_f = |v: &[f64]| -> Vec<F> { v.iter().map(|&x| F::from(x).unwrap()).collect() };
+    let mut b = to_f(&[1.0, 1.0, 0.0]);
+    solver.solve(
+        3,
+        2,
+        &to_i(&[0, 2, 1, 2]),
+        &to_i(&[0, 2, 4]),
+        &to_f(&[1.0, 1.0, 1.0, 1.0]),
+        &mut b,
+    )?;
+    for (i, x) in b[..2].iter().enumerate() {
+        let actual = x.to_f64().unwrap();
+        assert!(
+            f64::abs(actual - 1.0 / 3.0) < 1e-12,
+            "x[{}] error, expected 1/3 actual {}",
+            i,
+            actual
+        );
+    }
+
+    // Underdetermined, with minimum-norm solution x = {0, 1, 1}.
+    //
+    //     A = {1.0,    , 1.0}    b = {1.0}
+    //         {   , 1.0, 1.0}        {2.0}
+    let mut b = to_f(&[1.0, 2.0, 0.0]);
+    solver.solve(
+        2,
+        3,
+        &to_i(&[0, 1, 0, 1]),
+        &to_i(&[0, 1, 2, 4]),
+        &to_f(&[1.0, 1.0, 1.0, 1.0]),
+        &mut b,
+    )?;
+    for (i, (x, expect)) in b.iter().zip([0.0, 1.0, 1.0]).enumerate() {
+        let actual = x.to_f64().unwrap();
+        assert!(
+            f64::abs(actual - expect) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i,
+            expect,
+            actual
+        );
+    }
+
+    Ok(())
+}
+
+/// Structurally singular 4x4 matrix test.
+///
+/// ```txt
+///     A = {2.0,    ,    , 0.5}
+///         {   , 1.0, 1.0,    }
+///         {   ,    ,    ,    }
+///         {0.5,    ,    , 2.0}
+/// ```
+pub fn singular_factor_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let a_i = vec![0, 3, 1, 1, 0, 3];
+    let a_p = vec![0, 2, 3, 4, 6];
+    let a_x = vec![2.0, 0.5, 1.0, 1.0, 0.5, 2.0];
+
+    let err = match solver.factor(4, &a_i, &a_p, &a_x) {
+        Ok(_) => panic!("expected singular matrix error"),
+        Err(err) => err,
+    };
+    let singular = err
+        .downcast_ref::<SingularMatrix>()
+        .expect("expected singular matrix error");
+    assert_eq!(singular.rank, 3);
+    assert_eq!(singular.rows, vec![2]);
+
+    Ok(())
+}
+
+/// Numerically singular 4x4 matrix test, with full structural rank.
+///
+/// Columns 0 and 2 are equal, so with these values a pivot is exactly zero.
+///
+/// ```txt
+///     A = {1.0, 2.0, 1.0,    }
+///         {2.0,    , 2.0, 1.0}
+///         {   , 1.0,    ,    }
+///         {4.0,    , 4.0, 2.0}
+/// ```
+pub fn numerically_singular_factor_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let a_i = vec![0, 1, 3, 0, 2, 0, 1, 3, 1, 3];
+    let a_p = vec![0, 3, 5, 8, 10];
+    let a_x = vec![1.0, 2.0, 4.0, 2.0, 1.0, 1.0, 2.0, 4.0, 1.0, 2.0];
+
+    let err = match solver.factor(4, &a_i, &a_p, &a_x) {
+        Ok(_) => panic!("expected singular matrix error"),
+        Err(err) => err,
+    };
+    let singular = err
+        .downcast_ref::<SingularMatrix>()
+        .expect("expected singular matrix error");
+    // Solvers may not be able to continue past a zero pivot.
+    assert!(singular.rank < 4);
+    assert!(!singular.structural);
+    assert!(
+        singular.cols.is_empty() || singular.cols.iter().any(|j| [0, 2].contains(j)),
+        "{:?}",
+        singular.cols
+    );
+
+    Ok(())
+}
+
+/// Numerically singular 4x4 matrix test, with full structural rank.
+///
+/// Column 2 is `0.3 * A(:, 0) + 0.7 * A(:, 1)`, rounded, so the last
+/// pivot is of the order of the rounding error instead of zero.
+///
+/// ```txt
+///     A = {0.1, 0.2,  x ,    }
+///         {0.7,    ,  x , 0.5}
+///         {   , 0.9,  x ,    }
+///         {0.3, 0.6,  x , 0.2}
+/// ```
+pub fn rounded_singular_factor_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let c0 = [0.1, 0.7, 0.0, 0.3];
+    let c1 = [0.2, 0.0, 0.9, 0.6];
+    let c2: Vec<f64> = c0.iter().zip(c1).map(|(x, y)| 0.3 * x + 0.7 * y).collect();
+
+    let a_i = vec![0, 1, 3, 0, 2, 3, 0, 1, 2, 3, 1, 3];
+    let a_p = vec![0, 3, 6, 10, 12];
+    let a_x = vec![
+        c0[0], c0[1], c0[3], c1[0], c1[2], c1[3], c2[0], c2[1], c2[2], c2[3], 0.5, 0.2,
+    ];
+
+    let err = match solver.factor(4, &a_i, &a_p, &a_x) {
+        Ok(_) => panic!("expected singular matrix error"),
+        Err(err) => err,
+    };
+    let singular = err
+        .downcast_ref::<SingularMatrix>()
+        .expect("expected singular matrix error");
+    assert_eq!(singular.rank, 3);
+    assert!(!singular.structural);
+    assert_eq!(singular.cols.len(), 1);
+    assert!(singular.cols[0] < 3, "{:?}", singular.cols);
+
+    Ok(())
+}
+
+/// Determinant of the simple 10x10 test matrix, `det(A) = 174.35016689863...`.
+///
+/// The test is repeated with the sign of `A(5, 5)` flipped, which is the
+/// only entry in its row and column, so that `det(A)` is negative.
+pub fn simple_log_det_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()>
+where
+    F: LogDet<f64>,
+{
+    let n: usize = 10;
+    let (a_i, a_p, mut a_x, _) = simple_matrix();
+    let ln_det = 5.161065730383205;
+
+    for sign in [1.0, -1.0] {
+        a_x[15] = sign * 2.2;
+
+        let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+        let (s, ln) = f.log_det()?;
+
+        assert_eq!(s, sign);
+        assert!(
+            f64::abs(ln - ln_det) < 1e-12,
+            "ln|det(A)| error, expected {} actual {}",
+            ln_det,
+            ln
+        );
+    }
+
+    Ok(())
+}
+
+/// Determinant of a 4x4 matrix without diagonal entries, so that every
+/// pivot is off the diagonal and the permutations contribute to the sign.
+///
+/// ```txt
+///     A = {   , 2.0,    , 1.0}
+///         {3.0,    , 1.0,    }
+///         {   , 1.0,    , 4.0}
+///         {1.0,    , 2.0,    }
+/// ```
+///
+/// `det(A) = 35`, or `-45` with `A(0, 1) = -2`.
+pub fn pivoting_log_det_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()>
+where
+    F: LogDet<f64>,
+{
+    let (a_i, a_p, mut a_x) = pivoting_matrix();
+
+    for (a_01, det) in [(2.0, 35.0), (-2.0, -45.0)] {
+        a_x[2] = a_01;
+
+        let f = solver.factor(4, &a_i, &a_p, &a_x)?;
+        let (s, ln) = f.log_det()?;
+
+        assert_eq!(s, f64::signum(det));
+        assert!(
+            f64::abs(ln - f64::ln(f64::abs(det))) < 1e-12,
+            "ln|det(A)| error, expected {} actual {}",
+            f64::ln(f64::abs(det)),
+            ln
+        );
+    }
+
+    Ok(())
+}
+
+/// Selected inversion test with the matrix from [`pivoting_log_det_test`],
+/// where pivoting moves every diagonal entry of `A` off the diagonal of the
+/// factors. Compares with columns of the inverse computed by solving for
+/// each unit vector.
+pub fn pivoting_selected_inverse_test<F, S>(solver: &S) -> Result<()>
+where
+    S: FactorSolver<usize, f64, F> + SelectedInversion<f64, F>,
+{
+    let n: usize = 4;
+    let (a_i, a_p, a_x) = pivoting_matrix();
+
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+    let (z_p, z_i, z_x) = solver.inverse_selected(&f)?;
+    let z_d = solver.inverse_diag(&f)?;
+    assert_eq!(z_p.len(), n + 1);
+    assert_eq!(z_d.len(), n);
+
+    for j in 0..n {
+        let mut x = vec![0.0; n];
+        x[j] = 1.0;
+        solver.solve(&f, &mut x, false)?;
+
+        let entries = (z_p[j]..z_p[j + 1]).map(|p| (z_i[p], z_x[p]));
+        for (i, z_ij) in entries.chain([(j, z_d[j])]) {
+            assert!(
+                f64::abs(z_ij - x[i]) < 1e-12,
+                "inv(A)[{},{}] expected {} actual {}",
+                i,
+                j,
+                x[i],
+                z_ij
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Solves three right-hand-sides at once, with and without transposing,
+/// for the 4x4 matrix of [`pivoting_selected_inverse_test`].
+pub fn pivoting_multi_rhs_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let n: usize = 4;
+    let (a_i, a_p, a_x) = pivoting_matrix();
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+
+    // x_k = [1, 2, ..., n] + 10 k
+    let x: Vec<f64> = (0..3 * n)
+        .map(|i| (i % n + 1 + 10 * (i / n)) as f64)
+        .collect();
+    for trans in [false, true] {
+        let mut b = vec![0.0; x.len()];
+        for (x, b) in x.chunks_exact(n).zip(b.chunks_exact_mut(n)) {
+            for j in 0..n {
+                for p in a_p[j]..a_p[j + 1] {
+                    if trans {
+                        b[j] += a_x[p] * x[a_i[p]];
+                    } else {
+                        b[a_i[p]] += a_x[p] * x[j];
+                    }
+                }
+            }
+        }
+        solver.solve(&f, &mut b, trans)?;
+
+        for (i, (&actual, &expect)) in b.iter().zip(&x).enumerate() {
+            assert!(
+                f64::abs(actual - expect) < 1e-12,
+                "x[{}] error, expected {} actual {} (trans {})",
+                i,
+                expect,
+                actual,
+                trans
+            );
+        }
+    }
+
+    let mut b = vec![0.0; n + 1];
+    assert!(solver.solve(&f, &mut b, false).is_err());
+    Ok(())
+}
+
+/// Sparse right-hand-side test with the simple 10x10 test matrix.
+///
+/// Solves for multiples of columns 0 and 5 of `A`, giving unit vector
+/// solutions. Column 5 only contains the diagonal, so its solution
+/// must have exactly one nonzero.
+pub fn simple_sparse_solve_test<F, S>(solver: &S) -> Result<()>
+where
+    S: FactorSolver<usize, f64, F> + SparseSolver<f64, F>,
+{
+    let n: usize = 10;
+    let (a_i, a_p, a_x, _) = simple_matrix();
+
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+
+    let x = solver.solve_sparse(&f, &[5], &[4.4])?;
+    assert_eq!(x.n, n);
+    assert_eq!(x.indices, vec![5]);
+    assert!(f64::abs(x.values[0] - 2.0) < 1e-12);
+
+    let x = solver.solve_sparse(&f, &[0, 7, 8], &[2.1, 0.14, 0.09])?;
+    assert!(x.indices.windows(2).all(|w| w[0] < w[1]));
+    let mut dense = vec![0.0; n];
+    x.indices
+        .iter()
+        .zip(&x.values)
+        .for_each(|(&i, &x)| dense[i] = x);
+    dense.iter().enumerate().for_each(|(i, &x)| {
+        let expect = if i == 0 { 1.0 } else { 0.0 };
+        assert!(
+            f64::abs(x - expect) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i,
+            expect,
+            x
+        );
+    });
+
+    assert!(solver.solve_sparse(&f, &[n], &[1.0]).is_err());
+    assert!(solver.solve_sparse(&f, &[0, 7], &[2.1]).is_err());
+
+    // A workspace is left clean between solves.
+    let mut ws = Workspace::new();
+    for _ in 0..2 {
+        let y = solver.solve_sparse_with(&f, &[0, 7, 8], &[2.1, 0.14, 0.09], &mut ws)?;
+        assert_eq!(y, x);
+        let y = solver.solve_sparse_with(&f, &[5], &[4.4], &mut ws)?;
+        assert_eq!(y.indices, vec![5]);
+    }
+
+    Ok(())
+}
+
+pub fn simple_selected_solve_test<F, S>(solver: &S) -> Result<()>
+where
+    S: FactorSolver<usize, f64, F> + SelectedSolver<f64, F>,
+{
+    let n: usize = 10;
+    let (a_i, a_p, a_x, _) = simple_matrix();
+
+    // b = A*x, where x = [1, 2, ..., n]
+    let mut b = vec![0.0; n];
+    for j in 0..n {
+        for p in a_p[j]..a_p[j + 1] {
+            b[a_i[p]] += a_x[p] * (j + 1) as f64;
+        }
+    }
+
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+
+    let rows = [9, 2, 5];
+    let x = solver.solve_selected(&f, &b, &rows)?;
+    assert_eq!(x.len(), rows.len());
+    rows.iter().zip(&x).for_each(|(&i, &x)| {
+        let expect = (i + 1) as f64;
+        assert!(
+            f64::abs(x - expect) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i,
+            expect,
+            x
+        );
+    });
+
+    assert!(solver.solve_selected(&f, &b, &[n]).is_err());
+
+    Ok(())
+}
+
+/// Rank-2 update of the simple 10x10 matrix, solved with the
+/// Woodbury formula and by refactorization.
+pub fn simple_low_rank_test<F, S>(solver: &S) -> Result<()>
+where
+    S: FactorSolver<usize, f64, F>,
+{
+    let n: usize = 10;
+    let (a_i, a_p, a_x, _) = simple_matrix();
+    let col = |indices: Vec<usize>, values: Vec<f64>| SparseVec { n, indices, values };
+    let update = LowRank {
+        u: vec![col(vec![0, 3], vec![1.0, -1.0]), col(vec![5], vec![1.0])],
+        c: vec![0.5, 0.1, -0.2, 0.3],
+        v: vec![col(vec![2], vec![1.0]), col(vec![5, 9], vec![1.0, -1.0])],
+    };
+
+    // Dense A + U C V'
+    let mut a = vec![vec![0.0; n]; n];
+    for j in 0..n {
+        for p in a_p[j]..a_p[j + 1] {
+            a[a_i[p]][j] += a_x[p];
+        }
+    }
+    for (l, v) in update.v.iter().enumerate() {
+        for (m, u) in update.u.iter().enumerate() {
+            for (&i, &u_i) in u.indices.iter().zip(&u.values) {
+                for (&j, &v_j) in v.indices.iter().zip(&v.values) {
+                    a[i][j] += u_i * update.c[l * 2 + m] * v_j;
+                }
+            }
+        }
+    }
+
+    for max_rank in [2, 1] {
+        let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+        let f = LowRankUpdated::new(solver, &a_i, &a_p, &a_x, f, update.clone(), max_rank)?;
+        assert_eq!(f.refactored(), max_rank < 2);
+
+        for trans in [false, true] {
+            // b = A*x, where x = [1, 2, ..., n]
+            let mut b: Vec<f64> = (0..n)
+                .map(|i| {
+                    (0..n)
+                        .map(|j| (j + 1) as f64 * if trans { a[j][i] } else { a[i][j] })
+                        .sum()
+                })
+                .collect();
+            f.solve(solver, &mut b, trans)?;
+
+            b.iter().enumerate().for_each(|(i, &x)| {
+                let expect = (i + 1) as f64;
+                assert!(
+                    f64::abs(x - expect) < 1e-12,
+                    "x[{}] error, expected {} actual {}",
+                    i,
+                    expect,
+                    x
+                );
+            });
+        }
+    }
+
+    // Empty, nonzero first and decreasing column pointers.
+    for b_p in [vec![], vec![1, 3], vec![0, 3, 2]] {
+        let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+        assert!(LowRankUpdated::new(solver, &a_i, &b_p, &a_x, f, update.clone(), 2).is_err());
+    }
+
+    Ok(())
+}
+
+/// Schur complement of the simple 10x10 matrix on three boundary indices.
+///
+/// If `Ax = b` where `b` is zero on the interior, then `S x_B = b_B`.
+pub fn simple_schur_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let n: usize = 10;
+    let (a_i, a_p, a_x, _) = simple_matrix();
+    let keep = [9, 3, 0];
+
+    let (s_p, s_i, s_x) = schur_complement(solver, n, &a_i, &a_p, &a_x, &keep)?;
+    assert_eq!(s_p.len(), keep.len() + 1);
+
+    let mut x = vec![0.0; n];
+    for (k, &j) in keep.iter().enumerate() {
+        x[j] = (k + 1) as f64;
+    }
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+    solver.solve(&f, &mut x, false)?;
+
+    // b_B = S x_B
+    let mut b = vec![0.0; keep.len()];
+    for (j, &c) in keep.iter().enumerate() {
+        for p in s_p[j]..s_p[j + 1] {
+            b[s_i[p]] += s_x[p] * x[c];
+        }
+    }
+    b.iter().enumerate().for_each(|(k, &b)| {
+        let expect = (k + 1) as f64;
+        assert!(
+            f64::abs(b - expect) < 1e-12,
+            "b[{}] error, expected {} actual {}",
+            k,
+            expect,
+            b
+        );
+    });
+
+    // Eliminating nothing returns the matrix itself.
+    let all: Vec<usize> = (0..n).collect();
+    let (s_p, s_i, s_x) = schur_complement(solver, n, &a_i, &a_p, &a_x, &all)?;
+    assert_eq!((s_p, s_i, s_x), (a_p, a_i, a_x));
+
+    Ok(())
+}
+
+/// Solves the simple 10x10 system for three right-hand-sides in parallel.
+#[cfg(feature = "rayon")]
+pub fn simple_solve_par_test<F, S>(solver: &S) -> Result<()>
+where
+    F: Sync,
+    S: FactorSolver<usize, f64, F> + Sync,
+{
+    let n: usize = 10;
+    let (a_i, a_p, a_x, b0) = simple_matrix();
+
+    // Column k is scaled by k + 1.
+    let nrhs = 3;
+    let mut b: Vec<f64> = (0..nrhs)
+        .flat_map(|k| b0.iter().map(move |&b| b * (k + 1) as f64))
+        .collect();
+
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+    solver.solve_par(&f, n, &mut b, false)?;
+
+    b.iter().enumerate().for_each(|(i, &x)| {
+        let expect = ((i % n + 1) as f64) / 10.0 * (i / n + 1) as f64;
+        assert!(
+            f64::abs(x - expect) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i,
+            expect,
+            x
+        );
+    });
+
+    assert!(solver.solve_par(&f, n, &mut b[1..], false).is_err());
+
+    Ok(())
+}
+
+/// Factorizes three scaled copies of the simple 10x10 matrix as a batch.
+pub fn simple_batch_test<F, S>(solver: &S) -> Result<()>
+where
+    F: Sync,
+    S: BatchFactorSolver<usize, f64, F> + Sync,
+{
+    let n: usize = 10;
+    let (a_i, a_p, a_x, b0) = simple_matrix();
+
+    // Matrix k is scaled by k + 1.
+    let batch: Vec<Vec<f64>> = (1..=3)
+        .map(|k| a_x.iter().map(|&x| x * k as f64).collect())
+        .collect();
+    let batch: Vec<&[f64]> = batch.iter().map(|a_x| a_x.as_slice()).collect();
+
+    let f = solver.factor_batch(n, &a_i, &a_p, &batch)?;
+    assert_eq!(f.len(), batch.len());
+
+    let mut b = [b0.clone(), b0.clone(), b0];
+    let mut b_mut: Vec<&mut [f64]> = b.iter_mut().map(|b| b.as_mut_slice()).collect();
+    solver.solve_batch(&f, &mut b_mut, false)?;
+
+    for (k, x) in b.iter().enumerate() {
+        x.iter().enumerate().for_each(|(i, &x)| {
+            let expect = ((i + 1) as f64) / 10.0 / (k + 1) as f64;
+            assert!(
+                f64::abs(x - expect) < 1e-12,
+                "x[{}][{}] error, expected {} actual {}",
+                k,
+                i,
+                expect,
+                x
+            );
+        });
+    }
+
+    Ok(())
+}
+
+/// Solves the simple 10x10 system repeatedly with one workspace and checks
+/// that no solve after the first allocates.
+pub fn simple_workspace_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let n: usize = 10;
+    let (a_i, a_p, a_x, b0) = simple_matrix();
+
+    let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+    let mut ws = Workspace::new();
+    let mut b = b0.clone();
+    solver.solve_with(&f, &mut b, false, &mut ws)?;
+
+    let (result, allocations) = count_allocations(|| -> Result<()> {
+        for _ in 0..10 {
+            b.copy_from_slice(&b0);
+            solver.solve_with(&f, &mut b, false, &mut ws)?;
+        }
+        Ok(())
+    });
+    result?;
+    assert_eq!(allocations, 0);
+
+    b.iter().enumerate().for_each(|(i, &x)| {
+        let expect = ((i + 1) as f64) / 10.0;
+        assert!(
+            f64::abs(x - expect) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i,
+            expect,
+            x
+        );
+    });
+
+    Ok(())
+}
+
+/// Refactorizes the simple 10x10 matrix with new values and checks the
+/// solution of the new system.
+pub fn simple_refactor_test<F>(solver: &dyn FactorSolver<usize, f64, F>) -> Result<()> {
+    let n: usize = 10;
+    let (a_i, a_p, a_x, _) = simple_matrix();
+    let mut f = solver.factor(n, &a_i, &a_p, &a_x)?;
+
+    let a_x: Vec<f64> = a_x
+        .iter()
+        .enumerate()
+        .map(|(p, x)| x * (1.0 + 0.1 * (p % 3) as f64))
+        .collect();
+    let x: Vec<f64> = (0..n).map(|i| (i + 1) as f64 / 10.0).collect();
+    let mut b = vec![0.0; n];
+    for j in 0..n {
+        for p in a_p[j]..a_p[j + 1] {
+            b[a_i[p]] += a_x[p] * x[j];
+        }
+    }
+
+    solver.refactor(&mut f, n, &a_i, &a_p, &a_x)?;
+    solver.solve(&f, &mut b, false)?;
+
+    b.iter().zip(&x).enumerate().for_each(|(i, (&b, &x))| {
+        assert!(
+            f64::abs(b - x) < 1e-12,
+            "x[{}] error, expected {} actual {}",
+            i,
+            x,
+            b
+        );
+    });
+
     Ok(())
 }
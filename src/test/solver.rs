@@ -1,18 +1,16 @@
+use crate::matrix::{gen, Dataset};
 use crate::Solver;
-use crate::{matrix, FactorSolver};
+use crate::{FactorSolver, Scalar, SelectedInversion};
 
 use anyhow::Result;
 use approx::assert_abs_diff_eq;
+use num_complex::Complex64;
 use sparsetools::{csc_matvec, csr_matvec};
 
 pub fn test_solver_bbus(solver: &dyn Solver<usize, f64>, nrhs: usize, epsilon: f64) -> Result<()> {
     let trans = false;
-    for (n, a_p, a_i, a_x) in [
-        matrix::activsg2000_bbus(!trans),
-        matrix::activsg10k_bbus(!trans),
-        // matrix::activsg25k_bbus(!trans),
-        // matrix::activsg70k_bbus(!trans),
-    ] {
+    for name in ["ACTIVSg2000_Bbus", "ACTIVSg10k_Bbus"] {
+        let (n, a_p, a_i, a_x) = Dataset::get(name)?.read(!trans)?;
         let mut b0 = Vec::default();
         for _ in 0..nrhs {
             b0.extend((0..n).map(|i| 1.0 + i as f64 / n as f64))
@@ -27,18 +25,30 @@ pub fn test_solver_bbus(solver: &dyn Solver<usize, f64>, nrhs: usize, epsilon: f
     Ok(())
 }
 
+/// Solves generated Laplacian and ill-conditioned unsymmetric matrices.
+pub fn test_solver_gen(solver: &dyn Solver<usize, f64>, epsilon: f64) -> Result<()> {
+    for (n, a_p, a_i, a_x) in [
+        gen::laplacian_3d(12, 10, 8),
+        gen::random_conditioned(500, 3, 1e4, 1),
+    ] {
+        let x: Vec<f64> = (0..n).map(|i| 1.0 + i as f64 / n as f64).collect();
+        let b = test_solver::<usize, f64>(solver, n, a_i, a_p, a_x, &x, false)?;
+
+        for i in 0..n {
+            assert_abs_diff_eq!(b[i], x[i], epsilon = epsilon);
+        }
+    }
+    Ok(())
+}
+
 pub fn test_factor_solver_bbus<F>(
     solver: &dyn FactorSolver<usize, f64, F>,
     nrhs: usize,
     epsilon: f64,
 ) -> Result<()> {
     let trans = false;
-    for (n, a_p, a_i, a_x) in [
-        matrix::activsg2000_bbus(!trans),
-        matrix::activsg10k_bbus(!trans),
-        // matrix::activsg25k_bbus(!trans),
-        // matrix::activsg70k_bbus(!trans),
-    ] {
+    for name in ["ACTIVSg2000_Bbus", "ACTIVSg10k_Bbus"] {
+        let (n, a_p, a_i, a_x) = Dataset::get(name)?.read(!trans)?;
         let mut b0 = Vec::default();
         for _ in 0..nrhs {
             b0.extend((0..n).map(|i| 1.0 + i as f64 / n as f64))
@@ -53,13 +63,99 @@ pub fn test_factor_solver_bbus<F>(
     Ok(())
 }
 
+pub fn test_factor_solver_ybus<F>(
+    solver: &dyn FactorSolver<usize, Complex64, F>,
+    nrhs: usize,
+    epsilon: f64,
+) -> Result<()> {
+    for name in ["ACTIVSg200_Ybus", "ACTIVSg2000_Ybus"] {
+        let (n, a_p, a_i, a_x) = Dataset::get(name)?.read::<Complex64>(true)?;
+        let mut x = Vec::default();
+        for k in 0..nrhs {
+            x.extend((0..n).map(|i| Complex64::new(1.0 + i as f64 / n as f64, k as f64)))
+        }
+
+        let mut b = vec![Complex64::default(); x.len()];
+        for (x_k, b_k) in x.chunks_exact(n).zip(b.chunks_exact_mut(n)) {
+            for j in 0..n {
+                for p in a_p[j]..a_p[j + 1] {
+                    b_k[a_i[p]] += a_x[p] * x_k[j];
+                }
+            }
+        }
+
+        let f = solver.factor(n, &a_i, &a_p, &a_x)?;
+        solver.solve(&f, &mut b, false)?;
+
+        for i in 0..x.len() {
+            assert!(
+                (b[i] - x[i]).norm() < epsilon,
+                "x[{}] error, expected {} actual {}",
+                i,
+                x[i],
+                b[i]
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Compares the selected inverse with columns of the inverse
+/// computed by solving for each unit vector.
+pub fn test_selected_inverse<S, F, T>(
+    solver: &T,
+    n: usize,
+    a_i: &[usize],
+    a_p: &[usize],
+    a_x: &[S],
+    epsilon: f64,
+) -> Result<()>
+where
+    S: Scalar,
+    T: FactorSolver<usize, S, F> + SelectedInversion<S, F>,
+{
+    let f = solver.factor(n, a_i, a_p, a_x)?;
+    let (z_p, z_i, z_x) = solver.inverse_selected(&f)?;
+    let z_d = solver.inverse_diag(&f)?;
+    assert_eq!(z_p.len(), n + 1);
+    assert_eq!(z_d.len(), n);
+
+    let mut x = vec![S::zero(); n];
+    for j in 0..n {
+        x.fill(S::zero());
+        x[j] = S::one();
+        solver.solve(&f, &mut x, false)?;
+
+        for p in z_p[j]..z_p[j + 1] {
+            let i = z_i[p];
+            assert!(
+                (z_x[p] - x[i]).norm() < epsilon,
+                "inv(A)[{},{}] expected {:?} actual {:?}",
+                i,
+                j,
+                x[i],
+                z_x[p]
+            );
+        }
+        assert!(
+            (z_d[j] - x[j]).norm() < epsilon,
+            "inv(A)[{},{}] expected {:?} actual {:?}",
+            j,
+            j,
+            x[j],
+            z_d[j]
+        );
+    }
+    Ok(())
+}
+
 fn test_solver<I, S>(
     solver: &dyn Solver<I, S>,
     n: usize,
     a_i: Vec<I>,
     a_p: Vec<I>,
     a_x: Vec<S>,
-    x: &Vec<S>,
+    x: &[S],
     trans: bool,
 ) -> Result<Vec<S>>
 where
@@ -90,7 +186,7 @@ fn test_factor_solver<I, S, F>(
     a_i: Vec<I>,
     a_p: Vec<I>,
     a_x: Vec<S>,
-    x: &Vec<S>,
+    x: &[S],
     trans: bool,
 ) -> Result<Vec<S>>
 where
@@ -1,3 +1,4 @@
+use crate::Workspace;
 use anyhow::Result;
 
 /// Solve sparse systems of linear equations.
@@ -21,4 +22,142 @@ pub trait FactorSolver<I, S, F> {
 
     /// Solve for one or more right-hand-sides using matrix factors from [`factor`](Solver::factor).
     fn solve(&self, f: &F, b: &mut [S], trans: bool) -> Result<()>;
+
+    /// Refactorize `f` with new values `a_x` for the pattern `a_i`/`a_p`
+    /// it was factorized with, reusing the symbolic analysis if the backend
+    /// supports it.
+    fn refactor(&self, f: &mut F, n: usize, a_i: &[I], a_p: &[I], a_x: &[S]) -> Result<()> {
+        *f = self.factor(n, a_i, a_p, a_x)?;
+        Ok(())
+    }
+
+    /// Solve like [`solve`](FactorSolver::solve), taking scratch memory
+    /// from `ws` instead of allocating it for each call.
+    fn solve_with(&self, f: &F, b: &mut [S], trans: bool, ws: &mut Workspace<S>) -> Result<()> {
+        let _ = ws;
+        self.solve(f, b, trans)
+    }
+
+    /// Solve for multiple right-hand-sides of length `n` in parallel, with
+    /// the columns of `b` split across the rayon thread pool.
+    #[cfg(feature = "rayon")]
+    fn solve_par(&self, f: &F, n: usize, b: &mut [S], trans: bool) -> Result<()>
+    where
+        Self: Sync,
+        F: Sync,
+        S: Send,
+    {
+        use rayon::prelude::*;
+
+        if n == 0 || !b.len().is_multiple_of(n) {
+            return Err(anyhow::format_err!(
+                "b length {} is not a multiple of {}",
+                b.len(),
+                n
+            ));
+        }
+        b.par_chunks_mut(n)
+            .try_for_each(|b| self.solve(f, b, trans))
+    }
+}
+
+/// Factorize and solve batches of matrices with the same nonzero pattern.
+pub trait BatchFactorSolver<I, S, F>: FactorSolver<I, S, F> {
+    /// Factorize the matrix with values from each of the arrays in `a_x`,
+    /// sharing one symbolic analysis of the pattern `a_i`/`a_p`. The
+    /// matrices are factorized in parallel if the `rayon` feature is enabled.
+    fn factor_batch(&self, n: usize, a_i: &[I], a_p: &[I], a_x: &[&[S]]) -> Result<Vec<F>>;
+
+    /// Solve for the right-hand-side(s) in each of `b` using the
+    /// corresponding factors from [`factor_batch`](BatchFactorSolver::factor_batch).
+    fn solve_batch(&self, f: &[F], b: &mut [&mut [S]], trans: bool) -> Result<()>
+    where
+        Self: Sync,
+        F: Sync,
+        S: Send,
+    {
+        if f.len() != b.len() {
+            return Err(anyhow::format_err!(
+                "{} factors for {} right-hand-sides",
+                f.len(),
+                b.len()
+            ));
+        }
+        crate::batch::try_zip_mut(f, b, |f, b| self.solve(f, b, trans))
+    }
+}
+
+/// Fails to compile unless `T` is `Send + Sync`.
+#[allow(dead_code)]
+pub(crate) const fn assert_send_sync<T: Send + Sync>() {}
+
+/// Solve sparse least-squares problems with rectangular matrices.
+pub trait LeastSquaresSolver<I, S> {
+    /// Solve `min ||Ax - b||` for an `m x n` matrix with `m >= n`, or find
+    /// the minimum-norm solution of `Ax = b` if `m < n`. The length of `b`
+    /// must be `max(m, n)` and the solution is returned in its first `n` entries.
+    fn solve(&self, m: usize, n: usize, a_i: &[I], a_p: &[I], a_x: &[S], b: &mut [S])
+        -> Result<()>;
+}
+
+/// Determinant of a factorized matrix.
+pub trait LogDet<S> {
+    /// Returns `(sign, ln|det(A)|)`, where `sign` is `1` or `-1` for real
+    /// matrices and the phase `det(A) / |det(A)|` for complex matrices.
+    /// The sign is zero and the logarithm negative infinity if the
+    /// matrix is singular.
+    fn log_det(&self) -> Result<(S, f64)>;
+}
+
+/// Sparse vector with sorted indices.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SparseVec<S> {
+    /// Length of the vector.
+    pub n: usize,
+    /// Indices of the nonzero entries.
+    pub indices: Vec<usize>,
+    /// Values of the nonzero entries.
+    pub values: Vec<S>,
+}
+
+/// Solve with sparse right-hand-side vectors.
+pub trait SparseSolver<S, F> {
+    /// Solve for a sparse right-hand-side, with nonzeros `b_x` at `b_i`, using
+    /// matrix factors from [`factor`](FactorSolver::factor). Only the entries
+    /// of the factors reachable from the nonzeros of `b` are visited. Fails if
+    /// `b_i` and `b_x` differ in length or an index is out of range.
+    fn solve_sparse(&self, f: &F, b_i: &[usize], b_x: &[S]) -> Result<SparseVec<S>>;
+
+    /// Solve like [`solve_sparse`](SparseSolver::solve_sparse), taking the
+    /// dense scratch memory from `ws` instead of allocating it for each call.
+    fn solve_sparse_with(
+        &self,
+        f: &F,
+        b_i: &[usize],
+        b_x: &[S],
+        ws: &mut Workspace<S>,
+    ) -> Result<SparseVec<S>> {
+        let _ = ws;
+        self.solve_sparse(f, b_i, b_x)
+    }
+}
+
+/// Solve for selected entries of the solution vector.
+pub trait SelectedSolver<S, F> {
+    /// Solve for a dense right-hand-side `b`, using matrix factors from
+    /// [`factor`](FactorSolver::factor), and return the entries of `x` at
+    /// `rows`. The backward solve only visits the columns of the factors
+    /// that these entries depend on.
+    fn solve_selected(&self, f: &F, b: &[S], rows: &[usize]) -> Result<Vec<S>>;
+}
+
+/// Selected entries of the inverse of a factorized matrix.
+pub trait SelectedInversion<S, F> {
+    /// Returns the entries of `A^{-1}` on the symmetrized and filled pattern
+    /// of the factors from [`factor`](FactorSolver::factor), as a
+    /// compressed-column matrix `(p, i, x)` with sorted row indices.
+    fn inverse_selected(&self, f: &F) -> Result<(Vec<usize>, Vec<usize>, Vec<S>)>;
+
+    /// Returns the diagonal of `A^{-1}`.
+    fn inverse_diag(&self, f: &F) -> Result<Vec<S>>;
 }
@@ -0,0 +1,279 @@
+//! Sparse triangular solves with sparse right-hand-sides.
+#![cfg_attr(not(all(feature = "rlu", feature = "rsparse")), allow(dead_code))]
+
+use crate::csc::transpose;
+use crate::Scalar;
+use num_traits::ToPrimitive;
+
+/// Position of the diagonal entry in each column of a triangular matrix.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Diag {
+    /// First entry of each column (lower triangular).
+    First,
+    /// Last entry of each column (upper triangular).
+    Last,
+    /// Not stored, all ones.
+    Unit,
+}
+
+/// Column access to a triangular matrix, with sparse solves in terms of it.
+pub(crate) trait Columns<S: Scalar> {
+    /// Number of off-diagonal entries in column `j`.
+    fn nnz(&self, j: usize) -> usize;
+
+    /// Off-diagonal entry `k` of column `j`, as `(i, x)`.
+    fn entry(&self, j: usize, k: usize) -> (usize, S);
+
+    /// Diagonal entry of column `j`.
+    fn diag(&self, j: usize) -> S;
+
+    /// Off-diagonal entries `(i, x)` of column `j`.
+    fn col(&self, j: usize) -> impl Iterator<Item = (usize, S)> + '_ {
+        (0..self.nnz(j)).map(move |k| self.entry(j, k))
+    }
+
+    /// Nodes reachable from `b_i` in the graph of the matrix, in
+    /// topological order. `marked` must be false on entry and is
+    /// restored on return.
+    fn reach(&self, b_i: &[usize], marked: &mut [bool]) -> Vec<usize> {
+        let mut xi = Vec::default();
+        let mut stack = Vec::default();
+        for &k in b_i {
+            if marked[k] {
+                continue;
+            }
+            marked[k] = true;
+            stack.push((k, 0));
+            while let Some(&(j, q)) = stack.last() {
+                if q < self.nnz(j) {
+                    stack.last_mut().unwrap().1 += 1;
+                    let (i, _) = self.entry(j, q);
+                    if !marked[i] {
+                        marked[i] = true;
+                        stack.push((i, 0));
+                    }
+                } else {
+                    stack.pop();
+                    xi.push(j);
+                }
+            }
+        }
+        for &j in &xi {
+            marked[j] = false;
+        }
+        xi.reverse();
+        xi
+    }
+
+    /// Solves `Tx = b` in place, where the nonzero pattern of `x` is
+    /// `pattern` from [`reach`](Self::reach).
+    fn solve(&self, pattern: &[usize], x: &mut [S]) {
+        for &j in pattern {
+            x[j] /= self.diag(j);
+            let x_j = x[j];
+            for (i, t) in self.col(j) {
+                x[i] -= t * x_j;
+            }
+        }
+    }
+
+    /// Solves `T'x = b` in place for the entries in `pattern` from
+    /// [`reach`](Self::reach), which are all that these entries depend on.
+    fn solve_transposed(&self, pattern: &[usize], x: &mut [S]) {
+        for &j in pattern.iter().rev() {
+            let mut x_j = x[j];
+            for (i, t) in self.col(j) {
+                x_j -= t * x[i];
+            }
+            x[j] = x_j / self.diag(j);
+        }
+    }
+}
+
+/// Borrowed triangular matrix in compressed-column form.
+pub(crate) struct Triangular<'a, P, I, S> {
+    pub p: &'a [P],
+    pub i: &'a [I],
+    pub x: &'a [S],
+    pub diag: Diag,
+}
+
+impl<P, I, S> Triangular<'_, P, I, S>
+where
+    P: ToPrimitive,
+    I: ToPrimitive,
+    S: Scalar,
+{
+    /// Range of the off-diagonal entries in column `j`.
+    fn off_diag(&self, j: usize) -> (usize, usize) {
+        let (start, end) = (
+            self.p[j].to_usize().unwrap(),
+            self.p[j + 1].to_usize().unwrap(),
+        );
+        match self.diag {
+            Diag::First => (start + 1, end),
+            Diag::Last => (start, end - 1),
+            Diag::Unit => (start, end),
+        }
+    }
+
+    /// Returns the transpose `(p, i, x)` with sorted row indices.
+    pub fn transpose(&self, n: usize) -> (Vec<usize>, Vec<usize>, Vec<S>) {
+        transpose(n, n, self.p, self.i, self.x)
+    }
+}
+
+impl<P, I, S> Columns<S> for Triangular<'_, P, I, S>
+where
+    P: ToPrimitive,
+    I: ToPrimitive,
+    S: Scalar,
+{
+    fn nnz(&self, j: usize) -> usize {
+        let (start, end) = self.off_diag(j);
+        end - start
+    }
+
+    fn entry(&self, j: usize, k: usize) -> (usize, S) {
+        let p = self.off_diag(j).0 + k;
+        (self.i[p].to_usize().unwrap(), self.x[p])
+    }
+
+    fn diag(&self, j: usize) -> S {
+        match self.diag {
+            Diag::First => self.x[self.p[j].to_usize().unwrap()],
+            Diag::Last => self.x[self.p[j + 1].to_usize().unwrap() - 1],
+            Diag::Unit => S::one(),
+        }
+    }
+
+    fn col(&self, j: usize) -> impl Iterator<Item = (usize, S)> + '_ {
+        let (start, end) = self.off_diag(j);
+        (start..end).map(|p| (self.i[p].to_usize().unwrap(), self.x[p]))
+    }
+}
+
+/// Borrowed triangular matrix stored as a vector of `(i, x)` columns, as
+/// the factors from [rlu](https://crates.io/crates/rlu).
+pub(crate) struct ColVecs<'a, I, S> {
+    pub cols: &'a [Vec<(I, S)>],
+    pub diag: Diag,
+}
+
+impl<I, S> Columns<S> for ColVecs<'_, I, S>
+where
+    I: ToPrimitive,
+    S: Scalar,
+{
+    fn nnz(&self, j: usize) -> usize {
+        match self.diag {
+            Diag::First | Diag::Last => self.cols[j].len() - 1,
+            Diag::Unit => self.cols[j].len(),
+        }
+    }
+
+    fn entry(&self, j: usize, k: usize) -> (usize, S) {
+        let (i, x) = match self.diag {
+            Diag::First => &self.cols[j][k + 1],
+            Diag::Last | Diag::Unit => &self.cols[j][k],
+        };
+        (i.to_usize().unwrap(), *x)
+    }
+
+    fn diag(&self, j: usize) -> S {
+        match self.diag {
+            Diag::First => self.cols[j][0].1,
+            Diag::Last => self.cols[j][self.cols[j].len() - 1].1,
+            Diag::Unit => S::one(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ColVecs, Columns, Diag, Triangular};
+
+    #[test]
+    fn test_reach() {
+        //     {2,  ,  ,  }
+        //     {1, 2,  ,  }
+        //     { ,  , 2,  }
+        //     { , 1, 1, 2}
+        let l_p: Vec<usize> = vec![0, 2, 4, 6, 7];
+        let l_i: Vec<usize> = vec![0, 1, 1, 3, 2, 3, 3];
+        let l_x = vec![2.0, 1.0, 2.0, 1.0, 2.0, 1.0, 2.0];
+        let l = Triangular {
+            p: &l_p,
+            i: &l_i,
+            x: &l_x,
+            diag: Diag::First,
+        };
+        let mut marked = vec![false; 4];
+
+        assert_eq!(l.reach(&[2], &mut marked), vec![2, 3]);
+        assert_eq!(l.reach(&[0], &mut marked), vec![0, 1, 3]);
+        assert!(marked.iter().all(|m| !m));
+
+        let mut x = vec![4.0, 0.0, 0.0, 0.0];
+        let pattern = l.reach(&[0], &mut marked);
+        l.solve(&pattern, &mut x);
+        assert_eq!(x, vec![2.0, -1.0, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_solve_transposed() {
+        //     {1,  ,  ,  }
+        //     {1, 1,  ,  }
+        //     { ,  , 1,  }
+        //     { , 1, 1, 1}
+        let l_p: Vec<usize> = vec![0, 1, 2, 3, 3];
+        let l_i: Vec<usize> = vec![1, 3, 3];
+        let l_x = vec![1.0, 1.0, 1.0];
+        let l = Triangular {
+            p: &l_p,
+            i: &l_i,
+            x: &l_x,
+            diag: Diag::Unit,
+        };
+        let mut marked = vec![false; 4];
+
+        // Row 1 of L' only depends on rows 1 and 3.
+        let pattern = l.reach(&[1], &mut marked);
+        assert_eq!(pattern, vec![1, 3]);
+
+        let mut x = vec![f64::NAN, 3.0, f64::NAN, 2.0];
+        l.solve_transposed(&pattern, &mut x);
+        assert_eq!(x[1], 1.0);
+        assert_eq!(x[3], 2.0);
+
+        let (t_p, t_i, t_x) = l.transpose(4);
+        assert_eq!(t_p, vec![0, 0, 1, 1, 3]);
+        assert_eq!(t_i, vec![0, 1, 2]);
+        assert_eq!(t_x, vec![1.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_col_vecs() {
+        //     {2,  ,  ,  }
+        //     {1, 2,  ,  }
+        //     { ,  , 2,  }
+        //     { , 1, 1, 2}
+        let cols: Vec<Vec<(usize, f64)>> = vec![
+            vec![(1, 1.0), (0, 2.0)],
+            vec![(3, 1.0), (1, 2.0)],
+            vec![(3, 1.0), (2, 2.0)],
+            vec![(3, 2.0)],
+        ];
+        let l = ColVecs {
+            cols: &cols,
+            diag: Diag::Last,
+        };
+        let mut marked = vec![false; 4];
+
+        let mut x = vec![4.0, 0.0, 0.0, 0.0];
+        let pattern = l.reach(&[0], &mut marked);
+        assert_eq!(pattern, vec![0, 1, 3]);
+        l.solve(&pattern, &mut x);
+        assert_eq!(x, vec![2.0, -1.0, 0.0, 0.5]);
+    }
+}
@@ -0,0 +1,63 @@
+/// Scratch memory that can be kept between solves, so that repeated calls
+/// to [`FactorSolver::solve_with`](crate::FactorSolver::solve_with) do not
+/// allocate once it has grown to the order of the system.
+#[derive(Debug, Clone, Default)]
+pub struct Workspace<S> {
+    x: Vec<S>,
+    /// Zero between sparse solves.
+    #[cfg_attr(not(any(feature = "rlu", feature = "rsparse")), allow(dead_code))]
+    y: Vec<S>,
+    /// False between sparse solves.
+    #[cfg_attr(not(any(feature = "rlu", feature = "rsparse")), allow(dead_code))]
+    marked: Vec<bool>,
+}
+
+impl<S: Clone + Default> Workspace<S> {
+    /// Creates an empty workspace, which grows on first use.
+    pub fn new() -> Self {
+        Self {
+            x: Vec::default(),
+            y: Vec::default(),
+            marked: Vec::default(),
+        }
+    }
+
+    /// Creates a workspace for systems of order `n`.
+    pub fn with_capacity(n: usize) -> Self {
+        Self {
+            x: vec![S::default(); n],
+            y: Vec::default(),
+            marked: Vec::default(),
+        }
+    }
+
+    /// Returns a scratch vector of length `n`. Allocates only if `n` is
+    /// larger than any previous request.
+    pub fn slice(&mut self, n: usize) -> &mut [S] {
+        if self.x.len() < n {
+            self.x.resize(n, S::default());
+        }
+        &mut self.x[..n]
+    }
+
+    /// Returns the scratch vector with length `n`, for routines that take
+    /// a `&mut Vec`. Allocates only if `n` is larger than any previous
+    /// request.
+    pub fn vec(&mut self, n: usize) -> &mut Vec<S> {
+        self.x.resize(n, S::default());
+        &mut self.x
+    }
+
+    /// Returns a zeroed vector and unset marks of length `n`, for solves
+    /// with sparse right-hand-sides. The caller must zero the entries and
+    /// unset the marks that it changes before returning, so that the cost
+    /// of each solve does not depend on `n`.
+    #[cfg_attr(not(any(feature = "rlu", feature = "rsparse")), allow(dead_code))]
+    pub(crate) fn sparse(&mut self, n: usize) -> (&mut [S], &mut [bool]) {
+        if self.y.len() < n {
+            self.y.resize(n, S::default());
+            self.marked.resize(n, false);
+        }
+        (&mut self.y[..n], &mut self.marked[..n])
+    }
+}